@@ -0,0 +1,118 @@
+#![no_main]
+use arbitrary::Arbitrary;
+use gcmodule::testutil::verify_integrity;
+use gcmodule::{Cc, ObjectSpace, Trace, Tracer};
+use libfuzzer_sys::fuzz_target;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+#[derive(Arbitrary, Debug)]
+enum Connectivity {
+    /// Only the generated edges.
+    Sparse,
+    /// The generated edges, plus edges from each node to the next 2 nodes.
+    Dense,
+}
+
+#[derive(Arbitrary, Debug)]
+struct Graph {
+    /// Number of nodes, mapped to 1..=100.
+    n: u8,
+    connectivity: Connectivity,
+    /// `(from, to)` node indexes.
+    edges: Vec<(u8, u8)>,
+    /// Nodes referring to themselves.
+    self_refs: Vec<u8>,
+    /// Bump the generation before creating these nodes, so edges can point
+    /// across generations.
+    generation_bumps: Vec<u8>,
+    /// Nodes kept alive while collecting.
+    roots: Vec<u8>,
+}
+
+struct Node {
+    id: usize,
+    edges: RefCell<Vec<Cc<Node>>>,
+    drop_counts: Rc<RefCell<Vec<usize>>>,
+}
+
+impl Trace for Node {
+    fn trace(&self, tracer: &mut Tracer) {
+        self.edges.trace(tracer);
+    }
+}
+
+impl Drop for Node {
+    fn drop(&mut self) {
+        let mut drop_counts = self.drop_counts.borrow_mut();
+        drop_counts[self.id] += 1;
+        assert_eq!(drop_counts[self.id], 1, "node {} dropped twice", self.id);
+    }
+}
+
+fuzz_target!(|graph: Graph| {
+    let n = (graph.n as usize) % 100 + 1;
+    let space = ObjectSpace::default();
+    let drop_counts = Rc::new(RefCell::new(vec![0; n]));
+
+    let nodes: Vec<Cc<Node>> = (0..n)
+        .map(|id| {
+            if graph.generation_bumps.iter().any(|&i| i as usize % n == id) {
+                space.generation_bump();
+            }
+            space.create(Node {
+                id,
+                edges: Default::default(),
+                drop_counts: drop_counts.clone(),
+            })
+        })
+        .collect();
+
+    let mut edges: Vec<Vec<usize>> = vec![Vec::new(); n];
+    for &(from, to) in &graph.edges {
+        edges[from as usize % n].push(to as usize % n);
+    }
+    for &i in &graph.self_refs {
+        edges[i as usize % n].push(i as usize % n);
+    }
+    if let Connectivity::Dense = graph.connectivity {
+        for (i, referents) in edges.iter_mut().enumerate() {
+            referents.push((i + 1) % n);
+            referents.push((i + 2) % n);
+        }
+    }
+    for (from, referents) in edges.iter().enumerate() {
+        let mut from_edges = nodes[from].edges.borrow_mut();
+        from_edges.extend(referents.iter().map(|&to| nodes[to].clone()));
+    }
+
+    // Nodes reachable from roots must survive collection.
+    let mut reachable = vec![false; n];
+    let mut stack: Vec<usize> = graph.roots.iter().map(|&i| i as usize % n).collect();
+    while let Some(i) = stack.pop() {
+        if !reachable[i] {
+            reachable[i] = true;
+            stack.extend(edges[i].iter().copied());
+        }
+    }
+    let roots: Vec<Cc<Node>> = (0..n)
+        .filter(|&i| reachable[i])
+        .map(|i| nodes[i].clone())
+        .collect();
+    drop(nodes);
+
+    let _ = space.collect_cycles();
+    verify_integrity(&space);
+    let _ = space.collect_cycles_full();
+    verify_integrity(&space);
+    assert_eq!(space.count_tracked(), roots.len());
+    for (i, &count) in drop_counts.borrow().iter().enumerate() {
+        assert_eq!(count, if reachable[i] { 0 } else { 1 }, "node {}", i);
+    }
+
+    drop(roots);
+    let _ = space.collect_cycles_full();
+    verify_integrity(&space);
+    assert_eq!(space.count_tracked(), 0);
+    assert!(drop_counts.borrow().iter().all(|&count| count == 1));
+});
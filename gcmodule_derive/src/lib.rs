@@ -6,8 +6,9 @@
 //! ```
 //! use jrsonnet_gcmodule_derive::Trace;
 //!
+//! // `T: Trace` is required since `b` is traced.
 //! #[derive(Trace)]
-//! struct S<T: jrsonnet_gcmodule::Trace> {
+//! struct S<T> {
 //!     a: String,
 //!     b: Option<T>,
 //!
@@ -16,18 +17,53 @@
 //! }
 //!
 //! struct MyType;
+//!
+//! // Forward to the only field, like `#[repr(transparent)]`.
+//! #[derive(Trace)]
+//! #[trace(transparent)]
+//! struct Wrapper<T>(T);
+//! ```
+//!
+//! Type parameters that only appear in `PhantomData` or skipped fields
+//! are not required to implement `Trace`:
+//!
+//! ```
+//! use jrsonnet_gcmodule_derive::Trace;
+//! use std::marker::PhantomData;
+//!
+//! struct NotTrace;
+//!
+//! #[derive(Trace)]
+//! struct S<T, U> {
+//!     a: PhantomData<T>,
+//!     #[trace(skip)]
+//!     b: Option<U>,
+//! }
+//!
+//! let _ = S::<NotTrace, NotTrace> { a: PhantomData, b: None };
+//! ```
+//!
+//! A transparent struct needs exactly one field that is not `PhantomData`
+//! or `#[trace(skip)]`:
+//!
+//! ```compile_fail
+//! use jrsonnet_gcmodule_derive::Trace;
+//!
+//! #[derive(Trace)]
+//! #[trace(transparent)]
+//! struct Pair(u8, u8);
 //! ```
 extern crate proc_macro;
 
 use proc_macro::TokenStream;
-use proc_macro2::TokenStream as TokenStream2;
+use proc_macro2::{TokenStream as TokenStream2, TokenTree};
 use quote::{format_ident, quote};
 use syn::{
     parenthesized,
     parse::{Parse, ParseStream},
-    parse_macro_input,
+    parse_macro_input, parse_quote,
     spanned::Spanned,
-    Attribute, Data, DeriveInput, Error, Field, Fields, Ident, Path, Result,
+    Attribute, Data, DeriveInput, Error, Field, Fields, Generics, Ident, Index, Path, Result, Type,
 };
 
 mod kw {
@@ -37,12 +73,14 @@ mod kw {
     syn::custom_keyword!(tracking);
     syn::custom_keyword!(ignore);
     syn::custom_keyword!(force);
+    syn::custom_keyword!(transparent);
 }
 
 enum TraceAttr {
     Skip,
     With(Path),
     TrackingForce(bool),
+    Transparent,
 }
 impl TraceAttr {
     fn force_is_type_tracked(&self) -> Option<TokenStream2> {
@@ -50,6 +88,7 @@ impl TraceAttr {
             Self::TrackingForce(v) => Some(quote! {#v}),
             Self::Skip => Some(quote! {false}),
             Self::With(_) => Some(quote! {true}),
+            Self::Transparent => None,
         }
     }
 }
@@ -78,6 +117,9 @@ impl Parse for TraceAttr {
             let content;
             parenthesized!(content in input);
             Ok(Self::With(content.parse()?))
+        } else if lookahead.peek(kw::transparent) {
+            input.parse::<kw::transparent>()?;
+            Ok(Self::Transparent)
         } else {
             Err(lookahead.error())
         }
@@ -116,6 +158,17 @@ fn derive_fields(
             .iter()
             .map(|f| parse_attr::<TraceAttr, _>(&f.attrs, "trace"))
             .collect::<Result<Vec<_>>>()?;
+        if let Some(field) = fields
+            .iter()
+            .zip(attrs.iter())
+            .find(|(_, a)| matches!(a, Some(TraceAttr::Transparent)))
+            .map(|(f, _)| f)
+        {
+            return Err(Error::new(
+                field.span(),
+                "transparent is only supported on structs",
+            ));
+        }
 
         let trace = names.iter().zip(attrs.iter()).filter_map(|(name, attr)| {
             match attr {
@@ -207,18 +260,146 @@ fn derive_fields(
     }
 }
 
+/// Whether the type is `PhantomData`, which is ignored by
+/// `#[trace(transparent)]` like by `#[repr(transparent)]`.
+fn is_phantom_data(ty: &Type) -> bool {
+    match ty {
+        Type::Path(p) => p
+            .path
+            .segments
+            .last()
+            .is_some_and(|s| s.ident == "PhantomData"),
+        _ => false,
+    }
+}
+
+/// Returns (accessor, type) of the only field of a `#[trace(transparent)]`
+/// struct. Fields that are `PhantomData` or `#[trace(skip)]` are ignored.
+fn transparent_field(input: &DeriveInput) -> Result<(TokenStream2, Type)> {
+    let fields = match &input.data {
+        Data::Struct(s) => &s.fields,
+        _ => {
+            return Err(Error::new(
+                input.span(),
+                "transparent is only supported on structs",
+            ))
+        }
+    };
+    let mut found = Vec::new();
+    for (i, field) in fields.iter().enumerate() {
+        let attr = parse_attr::<TraceAttr, _>(&field.attrs, "trace")?;
+        if matches!(attr, Some(TraceAttr::Skip)) || is_phantom_data(&field.ty) {
+            continue;
+        }
+        if attr.is_some() {
+            return Err(Error::new(
+                field.span(),
+                "this attribute is not supported with transparent",
+            ));
+        }
+        let access = match &field.ident {
+            Some(name) => quote! {#name},
+            None => {
+                let index = Index::from(i);
+                quote! {#index}
+            }
+        };
+        found.push((access, field.ty.clone()));
+    }
+    if found.len() != 1 {
+        return Err(Error::new(
+            input.span(),
+            "transparent struct needs exactly one field that is not PhantomData or skipped",
+        ));
+    }
+    Ok(found.remove(0))
+}
+
+/// Whether `ident` appears anywhere in `tokens`.
+fn mentions(tokens: TokenStream2, ident: &Ident) -> bool {
+    tokens.into_iter().any(|t| match t {
+        TokenTree::Ident(i) => &i == ident,
+        TokenTree::Group(g) => mentions(g.stream(), ident),
+        _ => false,
+    })
+}
+
+/// Add `T: Trace` bounds for type parameters used by traced fields.
+/// Fields that are `PhantomData`, `#[trace(skip)]` or `#[trace(with(..))]`
+/// do not need `T: Trace`.
+fn with_trace_bounds(input: &DeriveInput, trace_attr: &Option<TraceAttr>) -> Result<Generics> {
+    let mut generics = input.generics.clone();
+    if matches!(trace_attr, Some(TraceAttr::Skip)) {
+        return Ok(generics);
+    }
+    let mut fields = Vec::new();
+    match &input.data {
+        Data::Struct(s) => fields.extend(s.fields.iter()),
+        Data::Enum(e) => {
+            for v in &e.variants {
+                let attr = parse_attr::<TraceAttr, _>(&v.attrs, "trace")?;
+                if !matches!(attr, Some(TraceAttr::Skip)) {
+                    fields.extend(v.fields.iter());
+                }
+            }
+        }
+        Data::Union(_) => {}
+    }
+    let mut types = Vec::new();
+    for field in fields {
+        let attr = parse_attr::<TraceAttr, _>(&field.attrs, "trace")?;
+        if !matches!(attr, Some(TraceAttr::Skip | TraceAttr::With(_)))
+            && !is_phantom_data(&field.ty)
+        {
+            types.push(&field.ty);
+        }
+    }
+    let params = generics
+        .type_params()
+        .map(|p| p.ident.clone())
+        .collect::<Vec<_>>();
+    let where_clause = generics.make_where_clause();
+    for param in params {
+        // `Trace` requires `'static`, even for parameters that are not traced.
+        if types.iter().any(|ty| mentions(quote! {#ty}, &param)) {
+            where_clause
+                .predicates
+                .push(parse_quote! {#param: ::jrsonnet_gcmodule::Trace});
+        } else {
+            where_clause.predicates.push(parse_quote! {#param: 'static});
+        }
+    }
+    Ok(generics)
+}
+
 fn derive_trace(input: DeriveInput) -> Result<TokenStream2> {
     let trace_attr = parse_attr::<TraceAttr, _>(&input.attrs, "trace")?;
     if matches!(trace_attr, Some(TraceAttr::With(_))) {
         return Err(Error::new(input.span(), "implement Trace instead"));
     }
     let ident = &input.ident;
-    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    let generics = with_trace_bounds(&input, &trace_attr)?;
+    let (impl_generics, type_generics, where_clause) = generics.split_for_impl();
+    if matches!(trace_attr, Some(TraceAttr::Transparent)) {
+        let (access, ty) = transparent_field(&input)?;
+        return Ok(quote! {
+            impl #impl_generics ::jrsonnet_gcmodule::Trace for #ident #type_generics #where_clause {
+                fn trace(&self, tracer: &mut ::jrsonnet_gcmodule::Tracer) {
+                    <#ty as ::jrsonnet_gcmodule::Trace>::trace(&self.#access, tracer)
+                }
+                fn is_type_tracked() -> bool {
+                    <#ty as ::jrsonnet_gcmodule::Trace>::is_type_tracked()
+                }
+            }
+        });
+    }
     if matches!(trace_attr, Some(TraceAttr::Skip)) {
         return Ok(quote! {
             impl #impl_generics ::jrsonnet_gcmodule::Trace for #ident #type_generics #where_clause {
+                #[inline(always)]
                 fn trace(&self, _tracer: &mut ::jrsonnet_gcmodule::Tracer) {
                 }
+                #[inline(always)]
                 fn is_type_tracked() -> bool {
                     false
                 }
@@ -248,6 +429,12 @@ fn derive_trace(input: DeriveInput) -> Result<TokenStream2> {
                 .map(|v| {
                     let name = &v.ident;
                     let attr = parse_attr::<TraceAttr, _>(&v.attrs, "trace")?;
+                    if matches!(attr, Some(TraceAttr::Transparent)) {
+                        return Err(Error::new(
+                            v.span(),
+                            "transparent is only supported on structs",
+                        ));
+                    }
                     let impls = derive_fields(&attr, &v.fields)?;
                     Ok((name, impls)) as Result<_>
                 })
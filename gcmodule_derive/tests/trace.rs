@@ -15,6 +15,10 @@ fn test_empty() {
     enum E1 {
         _A,
     }
+
+    assert!(!S0::is_type_tracked());
+    assert!(!E0::is_type_tracked());
+    assert!(!E1::is_type_tracked());
 }
 
 #[test]
@@ -52,6 +56,34 @@ fn test_type_parameters() {
     assert!(!S1::<Box<dyn Trace>>::is_type_tracked());
 }
 
+#[test]
+fn test_inferred_bounds() {
+    #[derive(DeriveTrace)]
+    struct S0<T> {
+        a: Option<T>,
+    }
+    assert!(!S0::<u8>::is_type_tracked());
+    assert!(S0::<Box<dyn Trace>>::is_type_tracked());
+
+    #[derive(DeriveTrace)]
+    enum E0<T, U> {
+        _A(T),
+        _B { _b: Vec<U> },
+    }
+    assert!(!E0::<u8, String>::is_type_tracked());
+    assert!(E0::<u8, Box<dyn Trace>>::is_type_tracked());
+
+    struct NotTrace;
+
+    #[derive(DeriveTrace)]
+    struct S1<T> {
+        _a: std::marker::PhantomData<T>,
+        #[trace(skip)]
+        _b: Option<Rc<T>>,
+    }
+    assert!(!S1::<NotTrace>::is_type_tracked());
+}
+
 #[test]
 fn test_field_skip() {
     #[derive(DeriveTrace)]
@@ -148,4 +180,36 @@ fn test_with() {
 
     #[derive(DeriveTrace)]
     struct Parent(#[trace(with(trace_child))] Child);
+
+    Parent(Child).trace(&mut Tracer::new(&mut |_| {}));
+}
+
+#[test]
+fn test_transparent() {
+    use std::marker::PhantomData;
+
+    #[derive(DeriveTrace)]
+    #[trace(transparent)]
+    struct W0<T: Trace>(T);
+    assert!(!W0::<u8>::is_type_tracked());
+    assert!(W0::<Box<dyn Trace>>::is_type_tracked());
+
+    #[derive(DeriveTrace)]
+    #[trace(transparent)]
+    struct W1 {
+        _marker: PhantomData<u8>,
+        inner: RefCell<Option<Box<dyn Trace>>>,
+        #[trace(skip)]
+        _skipped: Box<dyn Trace>,
+    }
+    assert!(W1::is_type_tracked());
+    {
+        let w: Cc<W1> = Cc::new(W1 {
+            _marker: PhantomData,
+            inner: Default::default(),
+            _skipped: Box::new(1u8),
+        });
+        *w.inner.borrow_mut() = Some(Box::new(w.clone()));
+    }
+    assert_eq!(jrsonnet_gcmodule::collect_thread_cycles(), 1);
 }
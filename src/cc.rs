@@ -1,13 +1,26 @@
 use crate::collect;
 use crate::collect::AbstractObjectSpace;
+use crate::collect::GcHeader;
+use crate::collect::GenerationId;
 use crate::collect::ObjectSpace;
 use crate::debug;
+use crate::graph::ObjectId;
 use crate::ref_count::RefCount;
 use crate::trace::Trace;
 use crate::trace::Tracer;
+#[cfg(feature = "allocator-api")]
+use std::alloc::Allocator;
+#[cfg(feature = "allocator-api")]
+pub use std::alloc::Global;
+use std::alloc::Layout;
+use std::any::Any;
+use std::any::TypeId;
+use std::cell::Cell;
 use std::cell::UnsafeCell;
 use std::mem;
 use std::mem::ManuallyDrop;
+use std::mem::MaybeUninit;
+use std::num::NonZeroUsize;
 use std::ops::Deref;
 use std::ops::DerefMut;
 use std::panic::UnwindSafe;
@@ -37,12 +50,19 @@ use std::ptr::NonNull;
 /// The data shared by multiple `RawCc<T, O>` pointers.
 #[repr(C)]
 #[doc(hidden)]
-pub struct RawCcBox<T: ?Sized, O: AbstractObjectSpace> {
+pub struct RawCcBox<T: ?Sized, O: AbstractObjectSpace, A: CcAllocator = Global> {
     pub(crate) ref_count: O::RefCount,
 
     #[cfg(test)]
     pub(crate) name: String,
 
+    /// User-defined tag. See `Cc::set_tag`.
+    #[cfg(feature = "tag")]
+    tag: Cell<u32>,
+
+    /// Allocator of this box. Zero-sized for the global allocator.
+    allocator: ManuallyDrop<A>,
+
     value: UnsafeCell<ManuallyDrop<T>>,
 }
 
@@ -51,9 +71,9 @@ pub struct RawCcBox<T: ?Sized, O: AbstractObjectSpace> {
 ///
 /// This is a private type.
 #[repr(C)]
-pub struct RawCcBoxWithGcHeader<T: ?Sized, O: AbstractObjectSpace> {
+pub struct RawCcBoxWithGcHeader<T: ?Sized, O: AbstractObjectSpace, A: CcAllocator = Global> {
     header: O::Header,
-    cc_box: RawCcBox<T, O>,
+    cc_box: RawCcBox<T, O, A>,
 }
 
 /// A single-threaded reference-counting pointer that integrates
@@ -72,25 +92,87 @@ pub struct RawCcBoxWithGcHeader<T: ?Sized, O: AbstractObjectSpace> {
 ///     println!("{}", cc.deref());
 /// });
 /// ```
-pub type Cc<T> = RawCc<T, ObjectSpace>;
+pub type Cc<T, A = Global> = RawCc<T, ObjectSpace, A>;
 
 /// Weak reference of [`Cc`](type.Cc.html).
-pub type Weak<T> = RawWeak<T, ObjectSpace>;
+pub type Weak<T, A = Global> = RawWeak<T, ObjectSpace, A>;
 
 /// Low-level type for [`Cc<T>`](type.Cc.html).
-pub struct RawCc<T: ?Sized, O: AbstractObjectSpace>(NonNull<RawCcBox<T, O>>);
+pub struct RawCc<T: ?Sized, O: AbstractObjectSpace, A: CcAllocator = Global>(
+    NonNull<RawCcBox<T, O, A>>,
+);
 
 /// Low-level type for [`Weak<T>`](type.Weak.html).
-pub struct RawWeak<T: ?Sized, O: AbstractObjectSpace>(NonNull<RawCcBox<T, O>>);
+pub struct RawWeak<T: ?Sized, O: AbstractObjectSpace, A: CcAllocator = Global>(
+    NonNull<RawCcBox<T, O, A>>,
+);
+
+/// The global memory allocator. This is `std::alloc::Global` with the
+/// `allocator-api` feature.
+#[cfg(not(feature = "allocator-api"))]
+#[derive(Copy, Clone, Default, Debug)]
+pub struct Global;
+
+/// Allocator of `CcBox`es. Without the `allocator-api` feature, only the
+/// global allocator is supported.
+///
+/// This is a private type.
+pub trait CcAllocator: 'static {
+    /// Allocate memory for `layout`, which has a non-zero size.
+    fn allocate_box(&self, layout: Layout) -> NonNull<u8>;
+
+    /// Release memory allocated by `allocate_box` with the same `layout`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be allocated by this allocator with `layout`.
+    unsafe fn deallocate_box(&self, ptr: NonNull<u8>, layout: Layout);
+}
+
+#[cfg(feature = "allocator-api")]
+impl<A: Allocator + 'static> CcAllocator for A {
+    fn allocate_box(&self, layout: Layout) -> NonNull<u8> {
+        match self.allocate(layout) {
+            Ok(ptr) => ptr.cast(),
+            Err(_) => std::alloc::handle_alloc_error(layout),
+        }
+    }
+
+    unsafe fn deallocate_box(&self, ptr: NonNull<u8>, layout: Layout) {
+        self.deallocate(ptr, layout)
+    }
+}
+
+#[cfg(not(feature = "allocator-api"))]
+impl CcAllocator for Global {
+    fn allocate_box(&self, layout: Layout) -> NonNull<u8> {
+        // safety: The layout has a non-zero size.
+        match NonNull::new(unsafe { std::alloc::alloc(layout) }) {
+            Some(ptr) => ptr,
+            None => std::alloc::handle_alloc_error(layout),
+        }
+    }
+
+    unsafe fn deallocate_box(&self, ptr: NonNull<u8>, layout: Layout) {
+        std::alloc::dealloc(ptr.as_ptr(), layout)
+    }
+}
+
+// `NonNull` lets `Option<Cc<T>>` use the null niche. Keep it that way.
+const _: () = assert!(mem::size_of::<Option<Cc<u32>>>() == mem::size_of::<Cc<u32>>());
+const _: () = assert!(mem::size_of::<Option<Weak<u32>>>() == mem::size_of::<Weak<u32>>());
 
 // `ManuallyDrop<T>` does not implement `UnwindSafe`. But `CcBox::drop` does
 // make sure `T` is dropped. If `T` is unwind-safe, so does `CcBox<T>`.
-impl<T: UnwindSafe + ?Sized> UnwindSafe for RawCcBox<T, ObjectSpace> {}
+impl<T: UnwindSafe + ?Sized, A: CcAllocator> UnwindSafe for RawCcBox<T, ObjectSpace, A> {}
 
 // `NonNull` does not implement `UnwindSafe`. But `Cc` and `Weak` only use it
 // as a "const" pointer. If `T` is unwind-safe, so does `Cc<T>`.
-impl<T: UnwindSafe + ?Sized, O: AbstractObjectSpace> UnwindSafe for RawCc<T, O> {}
-impl<T: UnwindSafe + ?Sized, O: AbstractObjectSpace> UnwindSafe for RawWeak<T, O> {}
+impl<T: UnwindSafe + ?Sized, O: AbstractObjectSpace, A: CcAllocator> UnwindSafe for RawCc<T, O, A> {}
+impl<T: UnwindSafe + ?Sized, O: AbstractObjectSpace, A: CcAllocator> UnwindSafe
+    for RawWeak<T, O, A>
+{
+}
 
 /// Type-erased `Cc<T>` with interfaces needed by GC.
 ///
@@ -108,6 +190,21 @@ pub trait CcDyn {
     /// - Operate on the object.
     fn gc_clone(&self) -> Box<dyn GcClone>;
 
+    /// Name of the type `T`. See `Trace::type_name`.
+    fn gc_type_name(&self) -> &'static str;
+
+    /// `TypeId` of the type `T`. Useful to downcast to the concrete type.
+    fn gc_type_id(&self) -> TypeId;
+
+    /// Whether `T` has a finalizer that has not run yet. See `Finalize`.
+    fn gc_needs_finalize(&self) -> bool;
+
+    /// The value `T`, or `None` if it was dropped.
+    fn gc_as_any(&self) -> Option<&dyn Any>;
+
+    /// Size of the `CcBox`, including `T` but not the `GcHeader`.
+    fn gc_size(&self) -> usize;
+
     #[cfg(feature = "debug")]
     /// Name used in collect.rs.
     fn gc_debug_name(&self) -> String {
@@ -124,16 +221,24 @@ pub trait GcClone {
 
     /// Returns the reference count. This is useful for verification.
     fn gc_ref_count(&self) -> usize;
+
+    /// Run the finalizer of T. Does nothing if it has already run.
+    fn gc_finalize(&self);
 }
 
 /// A dummy implementation without drop side-effects.
 pub(crate) struct CcDummy;
 
 impl CcDummy {
-    pub(crate) fn ccdyn_vptr() -> *mut () {
-        let mut dummy = CcDummy;
+    pub(crate) fn ccdyn_vptr() -> *const () {
+        Self::ccdyn_vptr_for::<CcDummy>()
+    }
+
+    /// Vtable pointer of `D` as `dyn CcDyn`, without a value of `D`.
+    pub(crate) fn ccdyn_vptr_for<D: CcDyn + 'static>() -> *const () {
+        let ptr: *const dyn CcDyn = std::ptr::null::<D>();
         // safety: To access vtable pointer. Stable API cannot do it.
-        let fat_ptr: [*mut (); 2] = unsafe { mem::transmute(&mut dummy as &mut dyn CcDyn) };
+        let fat_ptr: [*const (); 2] = unsafe { mem::transmute(ptr) };
         fat_ptr[1]
     }
 }
@@ -146,6 +251,21 @@ impl CcDyn for CcDummy {
     fn gc_clone(&self) -> Box<dyn GcClone> {
         panic!("bug: CcDummy::gc_clone should never be called");
     }
+    fn gc_type_name(&self) -> &'static str {
+        "<dummy>"
+    }
+    fn gc_type_id(&self) -> TypeId {
+        TypeId::of::<CcDummy>()
+    }
+    fn gc_needs_finalize(&self) -> bool {
+        false
+    }
+    fn gc_as_any(&self) -> Option<&dyn Any> {
+        None
+    }
+    fn gc_size(&self) -> usize {
+        0
+    }
 }
 
 impl<T: Trace> Cc<T> {
@@ -155,6 +275,73 @@ impl<T: Trace> Cc<T> {
     pub fn new(value: T) -> Cc<T> {
         collect::THREAD_OBJECT_SPACE.with(|space| Self::new_in_space(value, space))
     }
+
+    /// Constructs a new [`Cc<T>`](type.Cc.html) in the given
+    /// [`ObjectSpace`](struct.ObjectSpace.html).
+    ///
+    /// This is the same as [`ObjectSpace::create`](struct.ObjectSpace.html#method.create).
+    /// To collect cycles, use
+    /// [`ObjectSpace::collect_cycles`](struct.ObjectSpace.html#method.collect_cycles).
+    ///
+    /// ```
+    /// use jrsonnet_gcmodule::{Cc, ObjectSpace};
+    ///
+    /// let space = ObjectSpace::default();
+    /// let value = Cc::new_with_space(&space, vec![1, 2, 3]);
+    /// assert_eq!(value.len(), 3);
+    /// ```
+    pub fn new_with_space(space: &ObjectSpace, value: T) -> Cc<T> {
+        Self::new_in_space(value, space)
+    }
+
+    /// Constructs a new [`Cc<T>`](type.Cc.html) in a thread-local storage,
+    /// with a value that can know its own address.
+    ///
+    /// `f` receives a pointer to the uninitialized storage of the value, and
+    /// returns the value to store there. The pointer stays valid as long as
+    /// the returned `Cc<T>` or its clones are alive.
+    ///
+    /// Prefer [`Weak`](type.Weak.html) references for self-referential
+    /// structures when possible.
+    ///
+    /// # Safety
+    ///
+    /// `f` must not dereference the pointer. It can only be dereferenced
+    /// after `new_cyclic_unsafe` returns.
+    ///
+    /// ```
+    /// use jrsonnet_gcmodule::{Cc, Trace};
+    ///
+    /// struct Node {
+    ///     this: *const Node,
+    /// }
+    /// impl Trace for Node {}
+    ///
+    /// let node = unsafe { Cc::new_cyclic_unsafe(|this| Node { this }) };
+    /// assert!(std::ptr::eq(node.this, &*node));
+    /// ```
+    pub unsafe fn new_cyclic_unsafe(f: impl FnOnce(*mut T) -> T) -> Cc<T> {
+        collect::THREAD_OBJECT_SPACE.with(|space| {
+            let result = Self::new_in_space_with_init(space, Global, f, |header, value| {
+                space.insert(header, value)
+            });
+            space.on_alloc(T::type_name());
+            result
+        })
+    }
+}
+
+#[cfg(feature = "allocator-api")]
+impl<T: Trace, A: Allocator + 'static> Cc<T, A> {
+    /// Constructs a new [`Cc<T, A>`](type.Cc.html) in a thread-local storage,
+    /// allocated by `allocator`.
+    ///
+    /// This is the same as
+    /// [`ObjectSpace::create_in`](struct.ObjectSpace.html#method.create_in)
+    /// for the thread-local space.
+    pub fn new_in(value: T, allocator: A) -> Cc<T, A> {
+        collect::THREAD_OBJECT_SPACE.with(|space| Self::new_in_space_in(value, space, allocator))
+    }
 }
 
 fn aligned_size(size: usize, align: usize) -> usize {
@@ -167,36 +354,109 @@ impl<T: Trace, O: AbstractObjectSpace> RawCc<T, O> {
     ///
     /// To collect cycles, call `ObjectSpace::collect_cycles()`.
     pub(crate) fn new_in_space(value: T, space: &O) -> Self {
+        Self::new_in_space_in(value, space, Global)
+    }
+
+    /// Like `new_in_space`, but `link` is responsible for inserting the
+    /// `GcHeader` of tracked objects. `on_alloc` is not called.
+    pub(crate) fn new_in_space_with(
+        value: T,
+        space: &O,
+        link: impl FnOnce(&mut O::Header, &dyn CcDyn),
+    ) -> Self {
+        Self::new_in_space_with_init(space, Global, |_| value, link)
+    }
+}
+
+impl<T: Trace, O: AbstractObjectSpace, A: CcAllocator> RawCc<T, O, A> {
+    /// Like `new_in_space`, but allocate the `CcBox` using `allocator`.
+    pub(crate) fn new_in_space_in(value: T, space: &O, allocator: A) -> Self {
+        let result = Self::new_in_space_with_init(
+            space,
+            allocator,
+            |_| value,
+            |header, value| space.insert(header, value),
+        );
+        space.on_alloc(T::type_name());
+        result
+    }
+
+    /// Like `new_in_space_with`, but allocate the `CcBox` using `allocator`,
+    /// and the value is produced by `init`, which receives the address the
+    /// value will be written to.
+    pub(crate) fn new_in_space_with_init(
+        space: &O,
+        allocator: A,
+        init: impl FnOnce(*mut T) -> T,
+        link: impl FnOnce(&mut O::Header, &dyn CcDyn),
+    ) -> Self {
         let is_tracked = T::is_type_tracked();
-        let cc_box = RawCcBox {
-            ref_count: space.new_ref_count(is_tracked),
-            value: UnsafeCell::new(ManuallyDrop::new(value)),
-            #[cfg(test)]
-            name: debug::NEXT_DEBUG_NAME.with(|n| n.get().to_string()),
-        };
-        let ccbox_ptr: *mut RawCcBox<T, O> = if is_tracked {
-            // Create a GcHeader before the CcBox. This is similar to cpython.
-            let header = space.empty_header();
-            let cc_box_with_header = RawCcBoxWithGcHeader { header, cc_box };
-            let mut boxed = Box::new(cc_box_with_header);
-            // Fix-up fields in GcHeader. This is done after the creation of the
-            // Box so the memory addresses are stable.
-            space.insert(&mut boxed.header, &boxed.cc_box);
-            assert!(mem::align_of::<O::Header>() >= mem::align_of::<RawCcBox<T, O>>());
+        // Create a GcHeader before the CcBox for tracked objects. This is
+        // similar to cpython.
+        let layout = if is_tracked {
+            assert!(mem::align_of::<O::Header>() >= mem::align_of::<RawCcBox<T, O, A>>());
             debug_assert_eq!(
                 mem::size_of::<O::Header>()
                     + aligned_size(
-                        mem::size_of::<RawCcBox<T, O>>(),
-                        mem::align_of::<RawCcBox<T, O>>().max(mem::align_of::<O::Header>())
+                        mem::size_of::<RawCcBox<T, O, A>>(),
+                        mem::align_of::<RawCcBox<T, O, A>>().max(mem::align_of::<O::Header>())
                     ),
-                mem::size_of::<RawCcBoxWithGcHeader<T, O>>()
+                mem::size_of::<RawCcBoxWithGcHeader<T, O, A>>()
             );
-            let ptr: *mut RawCcBox<T, O> = &mut boxed.cc_box;
-            Box::leak(boxed);
-            ptr
+            Layout::new::<RawCcBoxWithGcHeader<T, O, A>>()
+        } else {
+            Layout::new::<RawCcBox<T, O, A>>()
+        };
+        let ref_count = space.new_ref_count(is_tracked);
+        #[cfg(test)]
+        let name = debug::NEXT_DEBUG_NAME.with(|n| n.get().to_string());
+
+        let ptr = allocator.allocate_box(layout);
+        let ccbox_ptr: *mut RawCcBox<T, O, A> = if is_tracked {
+            // safety: Only computes the address. Nothing is read.
+            unsafe {
+                std::ptr::addr_of_mut!(
+                    (*(ptr.as_ptr() as *mut RawCcBoxWithGcHeader<T, O, A>)).cc_box
+                )
+            }
         } else {
-            Box::into_raw(Box::new(cc_box))
+            ptr.as_ptr() as _
+        };
+        let value = {
+            // Release the memory if `init` panics.
+            let guard = ReleaseOnUnwind(&allocator, ptr, layout);
+            // safety: Only computes the address. `UnsafeCell` and
+            // `ManuallyDrop` have the same layout as `T`.
+            let value_ptr = unsafe { std::ptr::addr_of_mut!((*ccbox_ptr).value) } as *mut T;
+            let value = init(value_ptr);
+            mem::forget(guard);
+            value
+        };
+        let cc_box = RawCcBox {
+            ref_count,
+            #[cfg(feature = "tag")]
+            tag: Cell::new(0),
+            allocator: ManuallyDrop::new(allocator),
+            value: UnsafeCell::new(ManuallyDrop::new(value)),
+            #[cfg(test)]
+            name,
         };
+        if is_tracked {
+            let header = space.empty_header();
+            let boxed = ptr.as_ptr() as *mut RawCcBoxWithGcHeader<T, O, A>;
+            // safety: The memory is allocated for the box, and the value is
+            // written to the address `init` received.
+            let boxed = unsafe {
+                boxed.write(RawCcBoxWithGcHeader { header, cc_box });
+                &mut *boxed
+            };
+            // Fix-up fields in GcHeader. This is done after the box is
+            // written so the memory addresses are stable.
+            link(&mut boxed.header, &boxed.cc_box);
+        } else {
+            // safety: The memory is allocated for the box.
+            unsafe { ccbox_ptr.write(cc_box) };
+        }
         // safety: ccbox_ptr cannot be null from the above code.
         let non_null = unsafe { NonNull::new_unchecked(ccbox_ptr) };
         let result = Self(non_null);
@@ -210,7 +470,7 @@ impl<T: Trace, O: AbstractObjectSpace> RawCc<T, O> {
     }
 
     /// Convert to `RawCc<dyn Trace>`.
-    pub fn into_dyn(self) -> RawCc<dyn Trace, O> {
+    pub fn into_dyn(self) -> RawCc<dyn Trace, O, A> {
         #[cfg(feature = "nightly")]
         {
             // Requires CoerceUnsized, which is currently unstable.
@@ -234,6 +494,32 @@ impl<T: Trace, O: AbstractObjectSpace> RawCc<T, O> {
 }
 
 /// Create Cc<dyn Trait> from Cc<T> where T: impl Trait, Trait is trait object
+///
+/// This works on stable Rust. With the `nightly` feature, `Cc<T>` also
+/// coerces to `Cc<dyn Trait>` directly.
+///
+/// ```
+/// use jrsonnet_gcmodule::{cc_dyn, Trace};
+///
+/// trait Animal: Trace {
+///     fn name(&self) -> &'static str;
+/// }
+/// cc_dyn!(CcAnimal, Animal);
+///
+/// #[derive(Trace)]
+/// struct Dog;
+/// impl Animal for Dog {
+///     fn name(&self) -> &'static str { "dog" }
+/// }
+///
+/// #[derive(Trace)]
+/// struct Cat;
+/// impl Animal for Cat {
+///     fn name(&self) -> &'static str { "cat" }
+/// }
+///
+/// let animals = vec![CcAnimal::new(Dog), CcAnimal::new(Cat)];
+/// ```
 #[macro_export]
 macro_rules! cc_dyn {
     ($(#[$($meta:meta)+])* $conv:ident, $t:path $(, $new_vis:vis fn new() {...})?) => {
@@ -266,6 +552,56 @@ macro_rules! cc_dyn {
     };
 }
 
+impl<T: Trace> Cc<T> {
+    /// Move the tracked object from its linked list to the one of `space`.
+    /// The caller must hold the only strong reference.
+    pub(crate) fn relink(&self, space: &ObjectSpace) {
+        let inner = self.inner();
+        if !inner.is_tracked() {
+            return;
+        }
+        // Moving is not a deallocation. Do not report it.
+        inner.header().listener.set(0);
+        ObjectSpace::remove(inner.header());
+        // safety: GcHeader is before CcBox for tracked objects. It is
+        // unlinked, and nothing else refers to it.
+        let header = unsafe { &mut *(self.0.as_ptr() as *mut GcHeader).sub(1) };
+        space.insert(header, inner);
+    }
+
+    /// Return the inner value if `this` is the only strong reference.
+    /// Otherwise return `this` unchanged.
+    ///
+    /// Weak references can no longer be upgraded afterwards. Like dropping
+    /// the last `Cc`, the object stays in its
+    /// [`ObjectSpace`](struct.ObjectSpace.html) until the weak references
+    /// are gone. It is marked as dropped first, so the collector skips it.
+    /// The collector cannot run in the middle of this call, since `Cc` is
+    /// not `Send` and the call does not run user code.
+    ///
+    /// ```
+    /// use jrsonnet_gcmodule::Cc;
+    ///
+    /// let a = Cc::new(3);
+    /// let b = a.clone();
+    /// let a = Cc::try_unwrap(a).unwrap_err();
+    /// drop(b);
+    /// assert_eq!(Cc::try_unwrap(a), Ok(3));
+    /// ```
+    pub fn try_unwrap(this: Self) -> Result<T, Self> {
+        let inner = this.inner();
+        if inner.ref_count() != 1 || inner.is_dropped() {
+            return Err(this);
+        }
+        // safety: This is the only strong reference, and the value is not
+        // dropped. `take_t` marks it as dropped so dropping `this` does not
+        // drop it again.
+        let value = unsafe { inner.take_t() };
+        drop(this);
+        Ok(value)
+    }
+}
+
 impl<T: Trace + Clone> Cc<T> {
     /// Update the value `T` in a copy-on-write way.
     ///
@@ -283,9 +619,169 @@ impl<T: Trace + Clone> Cc<T> {
             update_func(value_mut);
         }
     }
+
+    /// Returns a mutable reference to the value, cloning it into a new
+    /// `Cc<T>` first if it is shared with other `Cc` or
+    /// [`Weak`](type.Weak.html) references. Like `Rc::make_mut`, weak
+    /// references are disassociated from the clone.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `T` is tracked. See [`get_mut`](#method.get_mut) for
+    /// why. Use [`update_with`](#method.update_with) for such types.
+    ///
+    /// ```
+    /// use jrsonnet_gcmodule::Cc;
+    ///
+    /// let mut a = Cc::new(vec![1]);
+    /// let b = a.clone();
+    /// Cc::make_mut(&mut a).push(2);
+    /// assert_eq!(*a, [1, 2]);
+    /// assert_eq!(*b, [1]);
+    /// ```
+    pub fn make_mut(this: &mut Self) -> &mut T {
+        assert!(
+            !T::is_type_tracked(),
+            "make_mut does not support tracked types, use update_with"
+        );
+        if Cc::get_mut(this).is_none() {
+            *this = Cc::new(<Cc<T>>::deref(this).clone());
+        }
+        // Untracked, and only referred by `this`.
+        Cc::get_mut(this).unwrap()
+    }
+
+    /// Constructs a new [`Cc<T>`](type.Cc.html) in a thread-local storage,
+    /// with a clone of the value of `other`.
+    ///
+    /// Unlike `other.clone()`, which shares the value, changing the value
+    /// of the new `Cc<T>` does not affect `other`.
+    ///
+    /// This is the same as
+    /// [`ObjectSpace::create_copy`](struct.ObjectSpace.html#method.create_copy)
+    /// for the thread-local space. Use that for objects of other spaces.
+    ///
+    /// ```
+    /// use jrsonnet_gcmodule::Cc;
+    /// use std::cell::Cell;
+    ///
+    /// let a = Cc::new(Cell::new(1));
+    /// let b = Cc::new_copy(&a);
+    /// b.set(2);
+    /// assert_eq!(a.get(), 1);
+    /// ```
+    pub fn new_copy(other: &Cc<T>) -> Cc<T> {
+        collect::THREAD_OBJECT_SPACE.with(|space| space.create_copy(other))
+    }
+
+    /// Convert to `Box<T>`. The value is moved out if `this` is the only
+    /// strong reference, and cloned otherwise.
+    ///
+    /// This cannot be `impl From<Cc<T>> for Box<T>` because of the orphan
+    /// rule.
+    ///
+    /// ```
+    /// use jrsonnet_gcmodule::Cc;
+    ///
+    /// let a = Cc::new(vec![1, 2]);
+    /// let b = a.clone();
+    /// assert_eq!(*Cc::into_box(a), [1, 2]);
+    /// assert_eq!(*Cc::into_box(b), [1, 2]);
+    /// ```
+    pub fn into_box(this: Self) -> Box<T> {
+        match Cc::try_unwrap(this) {
+            Ok(value) => Box::new(value),
+            Err(this) => Box::new(<Cc<T>>::deref(&this).clone()),
+        }
+    }
 }
 
-impl<T: ?Sized, O: AbstractObjectSpace> RawCcBox<T, O> {
+impl<T: ?Sized, A: CcAllocator> Cc<T, A> {
+    /// The [`ObjectSpace`](struct.ObjectSpace.html) generation this object
+    /// was created in. `None` if the object is not tracked.
+    ///
+    /// See [`ObjectSpace::generation_bump`](struct.ObjectSpace.html#method.generation_bump).
+    pub fn generation(&self) -> Option<GenerationId> {
+        let inner = self.inner();
+        if inner.is_tracked() {
+            Some(GenerationId(inner.header().generation))
+        } else {
+            None
+        }
+    }
+
+    /// The [`ObjectId`](struct.ObjectId.html) of this object, as reported by
+    /// [`ObjectSpace`](struct.ObjectSpace.html) methods like
+    /// [`find_root_objects`](struct.ObjectSpace.html#method.find_root_objects).
+    /// `None` if the object is not tracked.
+    pub fn object_id(&self) -> Option<ObjectId> {
+        let inner = self.inner();
+        if inner.is_tracked() {
+            Some(ObjectId::from_header(inner.header()))
+        } else {
+            None
+        }
+    }
+
+    /// Store a user-defined tag in the object, for example, a type tag or a
+    /// visited flag used by a language runtime. New objects have tag 0.
+    ///
+    /// The tag is stored in the allocation of the object, so it does not
+    /// need a side table. It is opaque to the collector, and is shared by
+    /// clones of this `Cc<T>`. It takes space in every object, so it is only
+    /// available with the `tag` feature.
+    ///
+    /// ```
+    /// use jrsonnet_gcmodule::Cc;
+    ///
+    /// let a = Cc::new(1u32);
+    /// let b = a.clone();
+    /// Cc::set_tag(&a, 42);
+    /// assert_eq!(Cc::get_tag(&b), 42);
+    /// ```
+    #[cfg(feature = "tag")]
+    #[inline]
+    pub fn set_tag(this: &Self, tag: u32) {
+        this.inner().tag.set(tag);
+    }
+
+    /// Get the tag set by [`set_tag`](#method.set_tag).
+    #[cfg(feature = "tag")]
+    #[inline]
+    pub fn get_tag(this: &Self) -> u32 {
+        this.inner().tag.get()
+    }
+
+    /// Replace the `Cc<T>` in `this` with `new` if it points to the same
+    /// allocation as `expected`. Return whether it was replaced.
+    ///
+    /// The previous value is dropped after `this` is updated. `new` is
+    /// dropped if it was not stored.
+    ///
+    /// ```
+    /// use jrsonnet_gcmodule::Cc;
+    /// use std::cell::Cell;
+    ///
+    /// let a = Cc::new(1);
+    /// let cell = Cell::new(a.clone());
+    /// assert!(!Cc::compare_and_swap_ptr(&cell, &Cc::new(1), Cc::new(2)));
+    /// assert!(Cc::compare_and_swap_ptr(&cell, &a, Cc::new(3)));
+    /// assert_eq!(*cell.into_inner(), 3);
+    /// ```
+    pub fn compare_and_swap_ptr(this: &Cell<Self>, expected: &Self, new: Self) -> bool {
+        // safety: `Cc<T>` is not `Sync`, so `this` is not accessed by other
+        // threads. `ptr_eq` does not run code that could access `this`.
+        let current = unsafe { &*this.as_ptr() };
+        if Self::ptr_eq(current, expected) {
+            drop(this.replace(new));
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl<T: ?Sized, O: AbstractObjectSpace, A: CcAllocator> RawCcBox<T, O, A> {
     #[inline]
     fn header_ptr(&self) -> *const () {
         self.header() as *const _ as _
@@ -344,8 +840,23 @@ impl<T: ?Sized, O: AbstractObjectSpace> RawCcBox<T, O> {
         }
     }
 
+    /// Move `T` out and mark it as dropped.
+    ///
+    /// # Safety
+    ///
+    /// `T` must not be dropped yet, and nothing else may access it.
+    pub(crate) unsafe fn take_t(&self) -> T
+    where
+        T: Sized,
+    {
+        let value = ManuallyDrop::take(&mut *self.value.get());
+        self.set_dropped();
+        debug::log(|| (self.debug_name(), "unwrap (T)"));
+        value
+    }
+
     pub(crate) fn trace_t(&self, tracer: &mut Tracer) {
-        if !self.is_tracked() {
+        if !self.is_tracked() || !tracer.visits_header::<O::Header>() {
             return;
         }
         debug::log(|| (self.debug_name(), "trace"));
@@ -354,7 +865,7 @@ impl<T: ?Sized, O: AbstractObjectSpace> RawCcBox<T, O> {
         // `trace` stops here, is non-recursive, and does apply `tracer`
         // to the actual `GcHeader`. It's expected that the upper layer
         // calls `gc_traverse` on everything (not just roots).
-        tracer(self.header_ptr());
+        tracer.visit_header(self.header_ptr());
     }
 
     pub(crate) fn debug_name(&self) -> String {
@@ -401,9 +912,9 @@ impl<T: std::fmt::Debug + ?Sized> OptionalDebug for T {
     }
 }
 
-impl<T: ?Sized, O: AbstractObjectSpace> RawCc<T, O> {
+impl<T: ?Sized, O: AbstractObjectSpace, A: CcAllocator> RawCc<T, O, A> {
     /// Obtains a "weak reference", a non-owning pointer.
-    pub fn downgrade(&self) -> RawWeak<T, O> {
+    pub fn downgrade(&self) -> RawWeak<T, O, A> {
         let inner = self.inner();
         inner.ref_count.inc_weak();
         debug::log(|| {
@@ -416,23 +927,109 @@ impl<T: ?Sized, O: AbstractObjectSpace> RawCc<T, O> {
     }
 
     /// Gets the reference count not considering weak references.
+    ///
+    /// The count is read the same way the references update it, so this
+    /// is also safe to call on a [`ThreadedCc`](type.ThreadedCc.html)
+    /// shared by threads. Other threads might change it right after.
+    ///
+    /// ```
+    /// use jrsonnet_gcmodule::Cc;
+    ///
+    /// let a = Cc::new(1);
+    /// assert_eq!(a.strong_count(), 1);
+    /// let b = a.clone();
+    /// assert_eq!(a.strong_count(), 2);
+    /// drop(b);
+    /// assert_eq!(a.strong_count(), 1);
+    /// ```
     #[inline]
     pub fn strong_count(&self) -> usize {
         self.ref_count()
     }
 
-    /// Returns `true` if the two `Cc`s point to the same allocation
+    /// Returns `true` if the two `Cc`s point to the same allocation.
+    ///
+    /// Unlike `==`, which compares the values, like `Rc::ptr_eq`.
+    ///
+    /// ```
+    /// use jrsonnet_gcmodule::Cc;
+    ///
+    /// let a = Cc::new(42);
+    /// let b = Cc::new(42);
+    /// assert!(a == b);
+    /// assert!(!Cc::ptr_eq(&a, &b));
+    /// assert!(Cc::ptr_eq(&a, &a.clone()));
+    /// ```
     #[inline]
     pub fn ptr_eq(this: &Self, other: &Self) -> bool {
         std::ptr::addr_eq(this.0.as_ptr(), other.0.as_ptr())
     }
+
+    /// Returns a mutable reference to the value, if there are no other
+    /// `Cc` or [`Weak`](type.Weak.html) references to it, and the object
+    /// is not tracked by the collector.
+    ///
+    /// Tracked objects always return `None`. The collector reads their
+    /// values, and it can be started while the returned reference is
+    /// alive. Use interior mutability, or
+    /// [`update_with`](#method.update_with), for them instead.
+    ///
+    /// ```
+    /// use jrsonnet_gcmodule::Cc;
+    ///
+    /// let mut a = Cc::new(3);
+    /// *Cc::get_mut(&mut a).unwrap() = 4;
+    /// let b = a.clone();
+    /// assert!(Cc::get_mut(&mut a).is_none());
+    /// drop(b);
+    /// assert_eq!(Cc::get_mut(&mut a), Some(&mut 4));
+    /// ```
+    pub fn get_mut(this: &mut Self) -> Option<&mut T> {
+        let inner = this.inner();
+        if inner.is_tracked() || inner.ref_count() != 1 || inner.weak_count() != 0 {
+            return None;
+        }
+        // safety: The value is not shared with other references, and the
+        // collector does not visit untracked objects. The returned
+        // reference borrows `this` mutably, so no new references can be
+        // created while it is alive.
+        Some(unsafe { &mut *inner.value.get() })
+    }
+
+    /// Address of the value `T`. Clones of a `Cc` share the same address.
+    #[inline]
+    pub fn as_ptr(this: &Self) -> *const T {
+        this.inner().value.get() as *const T
+    }
+
+    /// Identity of the object, for example, to track visited objects in a
+    /// `HashSet<NonZeroUsize>`.
+    ///
+    /// The identity is the address of the allocation. It is the same for
+    /// clones of a `Cc`, does not change when the `Cc` is moved, and is
+    /// stable while the object is alive. It might be reused by a different
+    /// object after this one is released.
+    ///
+    /// ```
+    /// use jrsonnet_gcmodule::Cc;
+    ///
+    /// let a = Cc::new(1);
+    /// let b = Box::new(a.clone());
+    /// assert_eq!(Cc::ref_id(&a), Cc::ref_id(&b));
+    /// assert_ne!(Cc::ref_id(&a), Cc::ref_id(&Cc::new(1)));
+    /// ```
+    #[inline]
+    pub fn ref_id(this: &Self) -> NonZeroUsize {
+        // The pointer cannot be null.
+        NonZeroUsize::new(this.0.as_ptr() as *const () as usize).unwrap()
+    }
 }
 
-impl<T: ?Sized, O: AbstractObjectSpace> RawWeak<T, O> {
+impl<T: ?Sized, O: AbstractObjectSpace, A: CcAllocator> RawWeak<T, O, A> {
     /// Attempts to obtain a "strong reference".
     ///
     /// Returns `None` if the value has already been dropped.
-    pub fn upgrade(&self) -> Option<RawCc<T, O>> {
+    pub fn upgrade(&self) -> Option<RawCc<T, O, A>> {
         let inner = self.inner();
         // Make the below operation "atomic".
         let _locked = inner.ref_count.locked();
@@ -450,6 +1047,14 @@ impl<T: ?Sized, O: AbstractObjectSpace> RawWeak<T, O> {
         }
     }
 
+    /// Returns `true` if the value has not been dropped, by reference
+    /// counting or by the cycle collector. If `false`,
+    /// [`upgrade`](#method.upgrade) returns `None`.
+    #[inline]
+    pub fn is_alive(&self) -> bool {
+        !self.inner().is_dropped()
+    }
+
     /// Gets the reference count not considering weak references.
     #[inline]
     pub fn strong_count(&self) -> usize {
@@ -469,10 +1074,10 @@ impl<T: ?Sized, O: AbstractObjectSpace> RawWeak<T, O> {
     }
 }
 
-impl<T: ?Sized, O: AbstractObjectSpace> RawCc<T, O> {
+impl<T: ?Sized, O: AbstractObjectSpace, A: CcAllocator> RawCc<T, O, A> {
     #[inline]
     #[doc(hidden)]
-    pub fn inner(&self) -> &RawCcBox<T, O> {
+    pub fn inner(&self) -> &RawCcBox<T, O, A> {
         // safety: CcBox lifetime maintained by ref count. Pointer is valid.
         unsafe { self.0.as_ref() }
     }
@@ -484,7 +1089,7 @@ impl<T: ?Sized, O: AbstractObjectSpace> RawCc<T, O> {
     /// `Trace` for `S`, it can use `Cc::trace(&self.0, tracer)`.
     #[inline]
     pub fn trace(&self, tracer: &mut Tracer) {
-        self.inner().trace_t(tracer);
+        tracer.trace_ptr(self);
     }
 
     #[inline]
@@ -513,15 +1118,38 @@ impl<T: ?Sized, O: AbstractObjectSpace> RawCc<T, O> {
     }
 }
 
-impl<T: ?Sized, O: AbstractObjectSpace> RawWeak<T, O> {
+impl<T: ?Sized, O: AbstractObjectSpace, A: CcAllocator> RawCc<T, O, A> {
+    /// The header of the object, if it is tracked.
+    #[cfg(debug_assertions)]
+    pub(crate) fn gc_header(&self) -> Option<&O::Header> {
+        let inner = self.inner();
+        if inner.is_tracked() {
+            Some(inner.header())
+        } else {
+            None
+        }
+    }
+}
+
+impl<T: ?Sized, O: AbstractObjectSpace, A: CcAllocator> RawWeak<T, O, A> {
     #[inline]
-    fn inner(&self) -> &RawCcBox<T, O> {
+    fn inner(&self) -> &RawCcBox<T, O, A> {
         // safety: CcBox lifetime maintained by ref count. Pointer is valid.
         unsafe { self.0.as_ref() }
     }
+
+    /// The address `Tracer` visits for the object, if it is tracked.
+    pub(crate) fn tracked_header(&self) -> Option<*const ()> {
+        let inner = self.inner();
+        if inner.is_tracked() {
+            Some(inner.header_ptr())
+        } else {
+            None
+        }
+    }
 }
 
-impl<T: ?Sized, O: AbstractObjectSpace> Clone for RawCc<T, O> {
+impl<T: ?Sized, O: AbstractObjectSpace, A: CcAllocator> Clone for RawCc<T, O, A> {
     #[inline]
     fn clone(&self) -> Self {
         // In theory self.inner().ref_count.locked() is needed.
@@ -534,7 +1162,7 @@ impl<T: ?Sized, O: AbstractObjectSpace> Clone for RawCc<T, O> {
     }
 }
 
-impl<T: ?Sized, O: AbstractObjectSpace> Clone for RawWeak<T, O> {
+impl<T: ?Sized, O: AbstractObjectSpace, A: CcAllocator> Clone for RawWeak<T, O, A> {
     #[inline]
     fn clone(&self) -> Self {
         let inner = self.inner();
@@ -550,7 +1178,7 @@ impl<T: ?Sized, O: AbstractObjectSpace> Clone for RawWeak<T, O> {
     }
 }
 
-impl<T: ?Sized> Deref for Cc<T> {
+impl<T: ?Sized, A: CcAllocator> Deref for Cc<T, A> {
     type Target = T;
 
     #[inline]
@@ -559,7 +1187,7 @@ impl<T: ?Sized> Deref for Cc<T> {
     }
 }
 
-impl<T: ?Sized, O: AbstractObjectSpace> Deref for RawCcBox<T, O> {
+impl<T: ?Sized, O: AbstractObjectSpace, A: CcAllocator> Deref for RawCcBox<T, O, A> {
     type Target = T;
 
     #[inline]
@@ -579,32 +1207,61 @@ impl<T: ?Sized, O: AbstractObjectSpace> Deref for RawCcBox<T, O> {
     }
 }
 
-fn drop_ccbox<T: ?Sized, O: AbstractObjectSpace>(cc_box: *mut RawCcBox<T, O>) {
-    // safety: See Cc::new. The pointer was created by Box::into_raw.
-    let cc_box: Box<RawCcBox<T, O>> = unsafe { Box::from_raw(cc_box) };
-    let is_tracked = cc_box.is_tracked();
+fn drop_ccbox<T: ?Sized, O: AbstractObjectSpace, A: CcAllocator>(cc_box: *mut RawCcBox<T, O, A>) {
+    // safety: See Cc::new. The pointer was allocated by the allocator
+    // stored in the box.
+    let is_tracked = unsafe { (*cc_box).is_tracked() };
     if is_tracked {
-        // The real object is CcBoxWithGcHeader. Drop that instead.
+        // The real object is CcBoxWithGcHeader. Release that instead.
         // safety: See Cc::new for CcBoxWithGcHeader.
-        let gc_box: Box<RawCcBoxWithGcHeader<T, O>> = unsafe { cast_box(cc_box) };
-        O::remove(&gc_box.header);
+        let gc_box = unsafe { cast_box_ptr(cc_box) };
+        let gc_box_ref = unsafe { &*gc_box };
+        O::remove(&gc_box_ref.header);
         // Drop T if it hasn't been dropped yet.
         // This needs to be after O::remove so the collector won't have a
         // chance to read dropped content.
-        gc_box.cc_box.drop_t();
-        debug::log(|| (gc_box.cc_box.debug_name(), "drop (CcBoxWithGcHeader)"));
-        drop(gc_box);
+        gc_box_ref.cc_box.drop_t();
+        debug::log(|| (gc_box_ref.cc_box.debug_name(), "drop (CcBoxWithGcHeader)"));
+        // safety: The box is no longer referred.
+        unsafe { release_box(gc_box, cc_box) };
     } else {
+        let cc_box_ref = unsafe { &*cc_box };
         // Drop T if it hasn't been dropped yet.
-        cc_box.drop_t();
-        debug::log(|| (cc_box.debug_name(), "drop (CcBox)"));
-        drop(cc_box);
+        cc_box_ref.drop_t();
+        debug::log(|| (cc_box_ref.debug_name(), "drop (CcBox)"));
+        // safety: The box is no longer referred.
+        unsafe { release_box(cc_box, cc_box) };
     }
 }
 
-impl<T: ?Sized, O: AbstractObjectSpace> Drop for RawCc<T, O> {
+/// Drop the fields of the allocation `ptr`, except the value `T`, and
+/// release its memory using the allocator stored in `cc_box`.
+///
+/// `ptr` is `cc_box`, or the `CcBoxWithGcHeader` that contains it.
+unsafe fn release_box<B: ?Sized, T: ?Sized, O: AbstractObjectSpace, A: CcAllocator>(
+    ptr: *mut B,
+    cc_box: *mut RawCcBox<T, O, A>,
+) {
+    let layout = Layout::for_value(&*ptr);
+    let allocator = ManuallyDrop::take(&mut (*cc_box).allocator);
+    std::ptr::drop_in_place(ptr);
+    allocator.deallocate_box(NonNull::new_unchecked(ptr as *mut u8), layout);
+}
+
+/// Release memory allocated by `new_in_space_with_init` if the value could
+/// not be created.
+struct ReleaseOnUnwind<'a, A: CcAllocator>(&'a A, NonNull<u8>, Layout);
+
+impl<A: CcAllocator> Drop for ReleaseOnUnwind<'_, A> {
     fn drop(&mut self) {
-        let ptr: *mut RawCcBox<T, O> = self.0.as_ptr();
+        // safety: Nothing was written to the memory yet.
+        unsafe { self.0.deallocate_box(self.1, self.2) };
+    }
+}
+
+impl<T: ?Sized, O: AbstractObjectSpace, A: CcAllocator> Drop for RawCc<T, O, A> {
+    fn drop(&mut self) {
+        let ptr: *mut RawCcBox<T, O, A> = self.0.as_ptr();
         let inner = self.inner();
         // Block threaded collector. This is needed because "drop()" is a
         // complex operation. The whole operation needs to be "atomic".
@@ -623,9 +1280,9 @@ impl<T: ?Sized, O: AbstractObjectSpace> Drop for RawCc<T, O> {
     }
 }
 
-impl<T: ?Sized, O: AbstractObjectSpace> Drop for RawWeak<T, O> {
+impl<T: ?Sized, O: AbstractObjectSpace, A: CcAllocator> Drop for RawWeak<T, O, A> {
     fn drop(&mut self) {
-        let ptr: *mut RawCcBox<T, O> = self.0.as_ptr();
+        let ptr: *mut RawCcBox<T, O, A> = self.0.as_ptr();
         let inner = self.inner();
         let ref_count = &inner.ref_count;
         // Block threaded collector to "freeze" the ref count, for safety.
@@ -646,7 +1303,7 @@ impl<T: ?Sized, O: AbstractObjectSpace> Drop for RawWeak<T, O> {
     }
 }
 
-impl<T: Trace + ?Sized, O: AbstractObjectSpace> CcDyn for RawCcBox<T, O> {
+impl<T: Trace, O: AbstractObjectSpace, A: CcAllocator> CcDyn for RawCcBox<T, O, A> {
     fn gc_ref_count(&self) -> usize {
         self.ref_count()
     }
@@ -670,19 +1327,42 @@ impl<T: Trace + ?Sized, O: AbstractObjectSpace> CcDyn for RawCcBox<T, O> {
         // safety: The pointer is compatible. The mutability is different only
         // to satisfy NonNull (NonNull::new requires &mut). The returned value
         // is still "immutable". &self can also never be nonnull.
-        let ptr: NonNull<RawCcBox<T, O>> =
+        let ptr: NonNull<RawCcBox<T, O, A>> =
             unsafe { NonNull::new_unchecked(self as *const _ as *mut _) };
-        let cc = RawCc::<T, O>(ptr);
+        let cc = RawCc::<T, O, A>(ptr);
         Box::new(cc)
     }
 
+    fn gc_type_name(&self) -> &'static str {
+        T::type_name()
+    }
+
+    fn gc_type_id(&self) -> TypeId {
+        TypeId::of::<T>()
+    }
+
+    fn gc_needs_finalize(&self) -> bool {
+        !self.is_dropped() && !self.ref_count.is_finalized() && self.deref().as_finalize().is_some()
+    }
+
+    fn gc_as_any(&self) -> Option<&dyn Any> {
+        if self.is_dropped() {
+            return None;
+        }
+        Some(self.deref())
+    }
+
+    fn gc_size(&self) -> usize {
+        mem::size_of::<Self>()
+    }
+
     #[cfg(feature = "debug")]
     fn gc_debug_name(&self) -> String {
         self.debug_name()
     }
 }
 
-impl<T: Trace + ?Sized, O: AbstractObjectSpace> GcClone for RawCc<T, O> {
+impl<T: Trace + ?Sized, O: AbstractObjectSpace, A: CcAllocator> GcClone for RawCc<T, O, A> {
     fn gc_ref_count(&self) -> usize {
         self.ref_count()
     }
@@ -690,11 +1370,22 @@ impl<T: Trace + ?Sized, O: AbstractObjectSpace> GcClone for RawCc<T, O> {
     fn gc_drop_t(&self) {
         self.inner().drop_t()
     }
+
+    fn gc_finalize(&self) {
+        let inner = self.inner();
+        if inner.is_dropped() || inner.ref_count.set_finalized() {
+            return;
+        }
+        if let Some(value) = inner.deref().as_finalize() {
+            debug::log(|| (inner.debug_name(), "finalize"));
+            value.finalize();
+        }
+    }
 }
 
-impl<T: Trace> Trace for Cc<T> {
+impl<T: Trace, A: CcAllocator> Trace for Cc<T, A> {
     fn trace(&self, tracer: &mut Tracer) {
-        Cc::<T>::trace(self, tracer)
+        Cc::<T, A>::trace(self, tracer)
     }
 
     #[inline]
@@ -703,9 +1394,9 @@ impl<T: Trace> Trace for Cc<T> {
     }
 }
 
-impl Trace for Cc<dyn Trace> {
+impl<A: CcAllocator> Trace for Cc<dyn Trace, A> {
     fn trace(&self, tracer: &mut Tracer) {
-        Cc::<dyn Trace>::trace(self, tracer)
+        Cc::<dyn Trace, A>::trace(self, tracer)
     }
 
     #[inline]
@@ -716,11 +1407,181 @@ impl Trace for Cc<dyn Trace> {
 }
 
 #[cfg(feature = "nightly")]
-impl<T: ?Sized + std::marker::Unsize<U>, U: ?Sized, O: AbstractObjectSpace>
-    std::ops::CoerceUnsized<RawCc<U, O>> for RawCc<T, O>
+impl<T: ?Sized + std::marker::Unsize<U>, U: ?Sized, O: AbstractObjectSpace, A: CcAllocator>
+    std::ops::CoerceUnsized<RawCc<U, O, A>> for RawCc<T, O, A>
 {
 }
 
+/// Value of a `CcSlice<T>`: the elements, preceded by their count.
+///
+/// `GcHeader` only stores a vtable, so the collector cannot operate on a
+/// `CcBox<[T]>` directly. It operates on `CcBox<SliceHead<T>>` instead,
+/// which finds the elements by the count stored here.
+#[repr(C)]
+pub(crate) struct SliceValue<T> {
+    len: usize,
+    pub(crate) items: [T],
+}
+
+/// Sized prefix of `SliceValue<T>`.
+#[repr(C)]
+pub(crate) struct SliceHead<T> {
+    len: usize,
+    items: [T; 0],
+}
+
+impl<T: Trace> Trace for SliceValue<T> {
+    fn trace(&self, tracer: &mut Tracer) {
+        for item in self.items.iter() {
+            item.trace(tracer);
+        }
+    }
+}
+
+impl<T: Trace, O: AbstractObjectSpace> RawCc<SliceValue<T>, O> {
+    /// Constructs a slice with the elements of `vec`, stored in the same
+    /// allocation as the `CcBox`.
+    pub(crate) fn new_slice_in_space(mut vec: Vec<T>, space: &O) -> Self {
+        let is_tracked = T::is_type_tracked();
+        let len = vec.len();
+        let header_size = if is_tracked {
+            mem::size_of::<O::Header>()
+        } else {
+            0
+        };
+        assert!(
+            !is_tracked
+                || mem::align_of::<O::Header>() >= mem::align_of::<RawCcBox<SliceHead<T>, O>>()
+        );
+        let (items_offset, align) = {
+            let uninit = MaybeUninit::<RawCcBox<SliceHead<T>, O>>::uninit();
+            let base = uninit.as_ptr();
+            // safety: Only computes addresses. Nothing is read.
+            let items = unsafe {
+                let head = std::ptr::addr_of!((*base).value) as *const SliceHead<T>;
+                std::ptr::addr_of!((*head).items)
+            };
+            let offset = items as usize - base as usize;
+            let align = mem::align_of::<RawCcBox<SliceHead<T>, O>>();
+            (
+                offset,
+                align.max(if is_tracked {
+                    mem::align_of::<O::Header>()
+                } else {
+                    1
+                }),
+            )
+        };
+        let size = mem::size_of::<T>()
+            .checked_mul(len)
+            .and_then(|size| size.checked_add(header_size + items_offset))
+            .expect("CcSlice is too large");
+        let layout = std::alloc::Layout::from_size_align(size, align)
+            .expect("CcSlice is too large")
+            .pad_to_align();
+
+        let base = Global.allocate_box(layout).as_ptr();
+        // safety: The allocation fits the header, the box, and `len`
+        // elements. Fields are written before the box is used.
+        let (header, thin): (*mut O::Header, *mut RawCcBox<SliceHead<T>, O>) = unsafe {
+            let thin = base.add(header_size) as *mut RawCcBox<SliceHead<T>, O>;
+            std::ptr::addr_of_mut!((*thin).ref_count).write(space.new_ref_count(is_tracked));
+            #[cfg(feature = "tag")]
+            std::ptr::addr_of_mut!((*thin).tag).write(Cell::new(0));
+            std::ptr::addr_of_mut!((*thin).allocator).write(ManuallyDrop::new(Global));
+            #[cfg(test)]
+            std::ptr::addr_of_mut!((*thin).name)
+                .write(debug::NEXT_DEBUG_NAME.with(|n| n.get().to_string()));
+            let head = std::ptr::addr_of_mut!((*thin).value) as *mut SliceHead<T>;
+            std::ptr::addr_of_mut!((*head).len).write(len);
+            let items = std::ptr::addr_of_mut!((*head).items) as *mut T;
+            // Move the elements. `vec` only releases its buffer afterwards.
+            std::ptr::copy_nonoverlapping(vec.as_ptr(), items, len);
+            vec.set_len(0);
+            (base as *mut O::Header, thin)
+        };
+        drop(vec);
+        if is_tracked {
+            // safety: The header is at the start of the allocation.
+            unsafe {
+                header.write(space.empty_header());
+                space.insert(&mut *header, &*thin);
+            }
+        }
+        let fat = std::ptr::slice_from_raw_parts_mut(thin as *mut T, len)
+            as *mut RawCcBox<SliceValue<T>, O>;
+        // safety: `fat` points to the box created above.
+        let result = Self(unsafe { NonNull::new_unchecked(fat) });
+        debug_assert_eq!(
+            mem::size_of_val(result.inner()) + header_size,
+            layout.size()
+        );
+        if is_tracked {
+            debug::log(|| (result.debug_name(), "new (CcBoxWithGcHeader)"));
+        } else {
+            debug::log(|| (result.debug_name(), "new (CcBox)"));
+        }
+        space.on_alloc(std::any::type_name::<[T]>());
+        result
+    }
+}
+
+impl<T: Trace, O: AbstractObjectSpace> RawCcBox<SliceHead<T>, O> {
+    /// The `CcBox<SliceValue<T>>` this is the prefix of.
+    fn slice_box(&self) -> &RawCcBox<SliceValue<T>, O> {
+        // safety: The count is written before the box is used, and is not
+        // changed by dropping the elements.
+        let len = unsafe { (*self.value.get()).len };
+        let ptr = std::ptr::slice_from_raw_parts(self as *const Self as *const T, len)
+            as *const RawCcBox<SliceValue<T>, O>;
+        // safety: `self` is the prefix of a `CcBox<SliceValue<T>>`. See
+        // `new_slice_in_space`.
+        unsafe { &*ptr }
+    }
+}
+
+impl<T: Trace, O: AbstractObjectSpace> CcDyn for RawCcBox<SliceHead<T>, O> {
+    fn gc_ref_count(&self) -> usize {
+        self.slice_box().ref_count()
+    }
+
+    fn gc_traverse(&self, tracer: &mut Tracer) {
+        let slice_box = self.slice_box();
+        if slice_box.is_dropped() {
+            return;
+        }
+        slice_box.deref().trace(tracer)
+    }
+
+    fn gc_clone(&self) -> Box<dyn GcClone> {
+        let slice_box = self.slice_box();
+        slice_box.ref_count.inc_ref();
+        let cc = RawCc::<SliceValue<T>, O>(NonNull::from(slice_box));
+        Box::new(cc)
+    }
+
+    fn gc_type_name(&self) -> &'static str {
+        std::any::type_name::<[T]>()
+    }
+
+    fn gc_type_id(&self) -> TypeId {
+        TypeId::of::<[T]>()
+    }
+
+    fn gc_needs_finalize(&self) -> bool {
+        false
+    }
+
+    fn gc_as_any(&self) -> Option<&dyn Any> {
+        // `[T]` is not `Sized`, so it cannot be `dyn Any`.
+        None
+    }
+
+    fn gc_size(&self) -> usize {
+        mem::size_of_val(self.slice_box())
+    }
+}
+
 #[inline]
 unsafe fn cast_ref<T: ?Sized, R>(value: &T, offset_bytes: isize) -> &R {
     let ptr: *const T = value;
@@ -730,18 +1591,15 @@ unsafe fn cast_ref<T: ?Sized, R>(value: &T, offset_bytes: isize) -> &R {
 }
 
 #[inline]
-unsafe fn cast_box<T: ?Sized, O: AbstractObjectSpace>(
-    value: Box<RawCcBox<T, O>>,
-) -> Box<RawCcBoxWithGcHeader<T, O>> {
-    let mut ptr: *const RawCcBox<T, O> = Box::into_raw(value);
-
+unsafe fn cast_box_ptr<T: ?Sized, O: AbstractObjectSpace, A: CcAllocator>(
+    mut ptr: *mut RawCcBox<T, O, A>,
+) -> *mut RawCcBoxWithGcHeader<T, O, A> {
     // ptr can be "thin" (1 pointer) or "fat" (2 pointers).
     // Change the first byte to point to the GcHeader.
-    let pptr: *mut *const RawCcBox<T, O> = &mut ptr;
+    let pptr: *mut *mut RawCcBox<T, O, A> = &mut ptr;
     let pptr: *mut *const O::Header = pptr as _;
     *pptr = (*pptr).offset(-1);
-    let ptr: *mut RawCcBoxWithGcHeader<T, O> = mem::transmute(ptr);
-    Box::from_raw(ptr)
+    mem::transmute(ptr)
 }
 
 #[cfg(test)]
@@ -766,6 +1624,39 @@ mod tests {
         assert_eq!(v4.gc_ref_count(), 2);
     }
 
+    #[test]
+    fn test_gc_type_id() {
+        let v: Cc<Box<dyn Trace>> = Cc::new(Box::new(1));
+        let value: &dyn CcDyn = v.inner().header().value();
+        assert_eq!(value.gc_type_id(), TypeId::of::<Box<dyn Trace>>());
+        assert_eq!(value.gc_ref_count(), 1);
+    }
+
+    /// Untracked types are allocated as a bare `CcBox` without `GcHeader`.
+    #[test]
+    fn test_untracked_layout_has_no_header() {
+        let v: Cc<u64> = Cc::new(1);
+        assert!(!v.inner().is_tracked());
+        assert_eq!(
+            mem::size_of::<RawCcBoxWithGcHeader<u64, ObjectSpace>>()
+                - mem::size_of::<RawCcBox<u64, ObjectSpace>>(),
+            mem::size_of::<crate::collect::GcHeader>()
+        );
+    }
+
+    #[test]
+    fn test_ccdyn_vptr_for() {
+        let v: Cc<Box<dyn Trace>> = Cc::new(Box::new(1));
+        let _v2 = v.clone();
+        // Vtable pointers might differ across codegen units. Check that the
+        // vtable works instead.
+        let vptr = crate::interop::ccdyn_vptr_for::<Box<dyn Trace>>();
+        let fat_ptr: (*const (), *const ()) = (v.inner() as *const _ as _, vptr);
+        let value: &dyn CcDyn = unsafe { mem::transmute(fat_ptr) };
+        assert_eq!(value.gc_ref_count(), 2);
+        assert_eq!(value.gc_type_id(), TypeId::of::<Box<dyn Trace>>());
+    }
+
     #[cfg(feature = "nightly")]
     #[test]
     fn test_unsize_coerce() {
@@ -1,10 +1,14 @@
 //! Additional impls about `AbstractCc<T, O>` to make it easier to use.
 
+use crate::cc::CcAllocator;
 use crate::cc::RawCc;
+use crate::cc::RawWeak;
+use crate::collect::AbstractObjectSpace;
 use crate::collect::ObjectSpace as O;
 use crate::Cc;
 use crate::Trace;
 use std::cmp::Ordering;
+use std::error::Error;
 use std::fmt;
 use std::hash;
 use std::ops::Deref;
@@ -16,71 +20,89 @@ impl<T: Default + Trace> Default for Cc<T> {
     }
 }
 
-impl<T: PartialEq + ?Sized> PartialEq for RawCc<T, O> {
+impl<T: PartialEq + ?Sized, A: CcAllocator> PartialEq for RawCc<T, O, A> {
     #[inline]
-    fn eq(&self, other: &RawCc<T, O>) -> bool {
+    fn eq(&self, other: &RawCc<T, O, A>) -> bool {
         **self == **other
     }
 }
 
-impl<T: hash::Hash + ?Sized> hash::Hash for RawCc<T, O> {
+impl<T: hash::Hash + ?Sized, A: CcAllocator> hash::Hash for RawCc<T, O, A> {
     fn hash<H: hash::Hasher>(&self, state: &mut H) {
         (**self).hash(state)
     }
 }
 
-impl<T: Eq + ?Sized> Eq for RawCc<T, O> {}
+impl<T: Eq + ?Sized, A: CcAllocator> Eq for RawCc<T, O, A> {}
 
-impl<T: PartialOrd + ?Sized> PartialOrd for RawCc<T, O> {
+impl<T: PartialOrd + ?Sized, A: CcAllocator> PartialOrd for RawCc<T, O, A> {
     #[inline]
-    fn partial_cmp(&self, other: &RawCc<T, O>) -> Option<Ordering> {
+    fn partial_cmp(&self, other: &RawCc<T, O, A>) -> Option<Ordering> {
         (**self).partial_cmp(&**other)
     }
 
     #[inline]
-    fn lt(&self, other: &RawCc<T, O>) -> bool {
+    fn lt(&self, other: &RawCc<T, O, A>) -> bool {
         **self < **other
     }
 
     #[inline]
-    fn le(&self, other: &RawCc<T, O>) -> bool {
+    fn le(&self, other: &RawCc<T, O, A>) -> bool {
         **self <= **other
     }
 
     #[inline]
-    fn gt(&self, other: &RawCc<T, O>) -> bool {
+    fn gt(&self, other: &RawCc<T, O, A>) -> bool {
         **self > **other
     }
 
     #[inline]
-    fn ge(&self, other: &RawCc<T, O>) -> bool {
+    fn ge(&self, other: &RawCc<T, O, A>) -> bool {
         **self >= **other
     }
 }
 
-impl<T: Ord + ?Sized> Ord for RawCc<T, O> {
+impl<T: Ord + ?Sized, A: CcAllocator> Ord for RawCc<T, O, A> {
     #[inline]
-    fn cmp(&self, other: &RawCc<T, O>) -> Ordering {
+    fn cmp(&self, other: &RawCc<T, O, A>) -> Ordering {
         (**self).cmp(&**other)
     }
 }
 
-impl<T: fmt::Debug + ?Sized> fmt::Debug for RawCc<T, O> {
+impl<T: fmt::Debug + ?Sized, A: CcAllocator> fmt::Debug for RawCc<T, O, A> {
     #[inline]
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_tuple("Cc").field(&self.inner().deref()).finish()
     }
 }
 
-impl<T: fmt::Display + ?Sized> fmt::Display for RawCc<T, O> {
+impl<T: fmt::Display + ?Sized, A: CcAllocator> fmt::Display for RawCc<T, O, A> {
     #[inline]
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         (**self).fmt(f)
     }
 }
 
-impl<T: ?Sized> fmt::Pointer for RawCc<T, O> {
+impl<T: Error + ?Sized, A: CcAllocator> Error for RawCc<T, O, A> {
+    #[allow(deprecated)]
+    fn description(&self) -> &str {
+        (**self).description()
+    }
+
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        (**self).source()
+    }
+}
+
+impl<T: ?Sized, S: AbstractObjectSpace, A: CcAllocator> fmt::Debug for RawWeak<T, S, A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Like `std::rc::Weak`, do not print the value.
+        f.write_str("(Weak)")
+    }
+}
+
+impl<T: ?Sized, S: AbstractObjectSpace, A: CcAllocator> fmt::Pointer for RawCc<T, S, A> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        fmt::Pointer::fmt(&self.inner().deref(), f)
+        fmt::Pointer::fmt(&RawCc::as_ptr(self), f)
     }
 }
@@ -0,0 +1,171 @@
+//! `GcCell<T>`: shorthand for the common `Cc<RefCell<T>>` pattern, and
+//! `TraceUnsafeCell<T>`: an `UnsafeCell<T>` the collector can trace.
+
+use crate::collect::ObjectSpace;
+use crate::Cc;
+use crate::Trace;
+use crate::Tracer;
+use std::cell::{BorrowError, BorrowMutError, Ref, RefCell, RefMut, UnsafeCell};
+use std::fmt;
+
+/// A [`Cc<RefCell<T>>`](type.Cc.html) with direct borrow methods.
+///
+/// Cloning a `GcCell<T>` clones the reference, not the data.
+///
+/// ```
+/// use jrsonnet_gcmodule::GcCell;
+///
+/// let a = GcCell::new(vec![1]);
+/// let b = a.clone();
+/// b.borrow_mut().push(2);
+/// assert_eq!(*a.borrow(), [1, 2]);
+/// ```
+pub struct GcCell<T>(Cc<RefCell<T>>);
+
+impl<T: Trace> GcCell<T> {
+    /// Constructs a new `GcCell<T>` in a thread-local storage.
+    /// See [`Cc::new`](type.Cc.html#method.new).
+    pub fn new(value: T) -> Self {
+        Self(Cc::new(RefCell::new(value)))
+    }
+
+    /// Constructs a new `GcCell<T>` in the given
+    /// [`ObjectSpace`](struct.ObjectSpace.html).
+    pub fn new_with_space(space: &ObjectSpace, value: T) -> Self {
+        Self(space.create(RefCell::new(value)))
+    }
+}
+
+impl<T> GcCell<T> {
+    /// Immutably borrows the wrapped value. See [`RefCell::borrow`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value is currently mutably borrowed.
+    pub fn borrow(&self) -> Ref<'_, T> {
+        self.0.borrow()
+    }
+
+    /// Mutably borrows the wrapped value. See [`RefCell::borrow_mut`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value is currently borrowed.
+    pub fn borrow_mut(&self) -> RefMut<'_, T> {
+        self.0.borrow_mut()
+    }
+
+    /// Immutably borrows the wrapped value, returning an error if the value
+    /// is currently mutably borrowed.
+    pub fn try_borrow(&self) -> Result<Ref<'_, T>, BorrowError> {
+        self.0.try_borrow()
+    }
+
+    /// Mutably borrows the wrapped value, returning an error if the value is
+    /// currently borrowed.
+    pub fn try_borrow_mut(&self) -> Result<RefMut<'_, T>, BorrowMutError> {
+        self.0.try_borrow_mut()
+    }
+
+    /// The underlying `Cc<RefCell<T>>`.
+    pub fn as_cc(&self) -> &Cc<RefCell<T>> {
+        &self.0
+    }
+}
+
+impl<T> From<Cc<RefCell<T>>> for GcCell<T> {
+    fn from(cc: Cc<RefCell<T>>) -> Self {
+        Self(cc)
+    }
+}
+
+impl<T> Clone for GcCell<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T: Trace> Trace for GcCell<T> {
+    fn trace(&self, tracer: &mut Tracer) {
+        self.0.trace(tracer)
+    }
+
+    #[inline]
+    fn is_type_tracked() -> bool {
+        Cc::<RefCell<T>>::is_type_tracked()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for GcCell<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.try_borrow() {
+            Ok(value) => f.debug_tuple("GcCell").field(&*value).finish(),
+            Err(_) => f
+                .debug_tuple("GcCell")
+                .field(&format_args!("<borrowed>"))
+                .finish(),
+        }
+    }
+}
+
+/// An [`UnsafeCell<T>`] that implements [`Trace`](trait.Trace.html).
+///
+/// The collector reads the value without synchronization, so
+/// `UnsafeCell<T>` itself does not implement `Trace`. Constructing this
+/// type is `unsafe` instead.
+///
+/// ```
+/// use jrsonnet_gcmodule::{Cc, TraceUnsafeCell};
+///
+/// // safety: `get` is not dereferenced mutably while collecting.
+/// let a = Cc::new(unsafe { TraceUnsafeCell::new(1) });
+/// unsafe { *a.get() = 2 };
+/// assert_eq!(unsafe { *a.get() }, 2);
+/// ```
+#[repr(transparent)]
+pub struct TraceUnsafeCell<T: ?Sized>(UnsafeCell<T>);
+
+impl<T> TraceUnsafeCell<T> {
+    /// Wraps `value`.
+    ///
+    /// # Safety
+    ///
+    /// The collector treats `trace` as a reader. No `&mut T` obtained from
+    /// [`get`](#method.get) may be alive while the value is traced,
+    /// including when `collect_cycles` is called while such a reference is
+    /// held.
+    pub const unsafe fn new(value: T) -> Self {
+        Self(UnsafeCell::new(value))
+    }
+
+    /// Unwraps the value.
+    pub fn into_inner(self) -> T {
+        self.0.into_inner()
+    }
+}
+
+impl<T: ?Sized> TraceUnsafeCell<T> {
+    /// Gets a mutable pointer to the wrapped value. See [`UnsafeCell::get`].
+    pub const fn get(&self) -> *mut T {
+        self.0.get()
+    }
+
+    /// Returns a mutable reference to the wrapped value.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.0.get_mut()
+    }
+}
+
+impl<T: Trace> Trace for TraceUnsafeCell<T> {
+    fn trace(&self, tracer: &mut Tracer) {
+        // safety: See `new`. Mutable references to the value are not alive
+        // during tracing.
+        unsafe { (*self.get()).trace(tracer) }
+    }
+
+    #[inline]
+    fn is_type_tracked() -> bool {
+        T::is_type_tracked()
+    }
+}
@@ -1,23 +1,43 @@
 // The main idea comes from cpython 3.8's `gcmodule.c` [1].
 //
 // [1]: https://github.com/python/cpython/blob/v3.8.0/Modules/gcmodule.c
-
-// NOTE: Consider adding generation support if necessary. It won't be too hard.
+//
+// Marking is sequential. Values in an `ObjectSpace` are not `Sync`, for
+// example, `Trace` for `RefCell<T>` updates its borrow flag. The ref counts
+// stored in `GcHeader.prev` during collection are not atomic either. So
+// visiting objects from multiple threads would be a data race.
 
 use crate::cc::CcDummy;
 use crate::cc::CcDyn;
 use crate::cc::GcClone;
 use crate::debug;
+use crate::ephemeron;
+use crate::ephemeron::EphemeronEntries;
+use crate::ephemeron::EphemeronKey;
+use crate::graph;
+use crate::graph::ObjectId;
 use crate::ref_count::RefCount;
 use crate::ref_count::SingleThreadRefCount;
+use crate::string::InternTable;
 use crate::Cc;
 use crate::Trace;
+use crate::Tracer;
+use crate::TracerMode;
+use std::any::Any;
 use std::cell::Cell;
 use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io;
+use std::io::Write;
 use std::marker::PhantomData;
 use std::mem;
 use std::ops::Deref;
+use std::panic;
+use std::panic::AssertUnwindSafe;
 use std::pin::Pin;
+use std::rc::Rc;
+use std::time::Duration;
+use std::time::Instant;
 
 /// Provides advanced explicit control about where to store [`Cc`](type.Cc.html)
 /// objects.
@@ -47,7 +67,7 @@ use std::pin::Pin;
 /// {
 ///     type List = Cc<RefCell<Vec<Box<dyn Trace>>>>;
 ///     let a: List = space.create(Default::default());
-///     let b: List = space.create(Default::default());
+///     let b: List = Cc::new_with_space(&space, Default::default());
 ///     a.borrow_mut().push(Box::new(b.clone()));
 ///     b.borrow_mut().push(Box::new(a.clone()));
 /// }
@@ -57,18 +77,264 @@ use std::pin::Pin;
 /// ```
 pub struct ObjectSpace {
     /// Linked list to the tracked objects.
+    ///
+    /// The list head is boxed so its address stays stable when the
+    /// `ObjectSpace` moves. Storing it inline would require every method to
+    /// take `Pin<&Self>`, and would prevent `leak` and `interop` from swapping
+    /// the list cheaply. One allocation per space is not worth that.
     pub(crate) list: RefCell<Pin<Box<GcHeader>>>,
 
+    /// Index of the listener receiving events in `LISTENERS`, plus one.
+    /// 0 means no listener. Tracked objects store a copy in their header.
+    listener_slot: Cell<u16>,
+
+    /// Called before and after collecting. See `on_collect`.
+    hooks: RefCell<Vec<CollectHook>>,
+
+    /// Whether `collect_cycles` is running. Used to reject reentrant calls
+    /// from `Drop` implementations.
+    is_collecting: Cell<bool>,
+
+    /// Generation of newly created objects. See `generation_bump`.
+    generation: Cell<u8>,
+
+    /// Where the next `collect_cycles_step` continues. Linked into the list
+    /// during a pass, right before the next object to take. Objects are
+    /// linked and unlinked around it, so it stays in place.
+    step_marker: Pin<Box<GcHeader>>,
+
+    /// Objects collected by `collect_cycles_step` in the current pass.
+    step_collected: Cell<usize>,
+
+    /// Objects scanned by `collect_cycles_step` in the current pass.
+    step_scanned: Cell<usize>,
+
+    /// Objects tracked since the last collection. See
+    /// `collect_cycles_heuristic`.
+    allocations: Cell<usize>,
+
+    /// Objects tracked right after the last collection.
+    survivors: Cell<usize>,
+
+    /// Strings interned by `CcString::intern`.
+    pub(crate) interned: RefCell<InternTable>,
+
+    /// Heuristics used by `collect_cycles`.
+    policy: CollectionPolicy,
+
     /// Mark `ObjectSpace` as `!Send` and `!Sync`. This enforces thread-exclusive
     /// access to the linked list so methods can use `&self` instead of
     /// `&mut self`, together with usage of interior mutability.
     _phantom: PhantomData<Cc<()>>,
 }
 
+/// Identifies a generation of an [`ObjectSpace`](struct.ObjectSpace.html).
+/// See [`ObjectSpace::generation_bump`](struct.ObjectSpace.html#method.generation_bump).
+///
+/// Ids are compared for equality only. They wrap around after 256
+/// generations, so a generation id can be reused by a later generation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct GenerationId(pub(crate) u8);
+
+/// Progress of [`ObjectSpace::collect_cycles_step`](struct.ObjectSpace.html#method.collect_cycles_step).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StepResult {
+    /// More objects need to be considered. Contains an estimate of how many.
+    Partial(usize),
+    /// All objects have been considered. Contains the number of objects
+    /// collected during the pass.
+    Complete(usize),
+}
+
+/// Tunes how an [`ObjectSpace`](struct.ObjectSpace.html) collects cycles.
+///
+/// Pass it to
+/// [`ObjectSpace::with_collection_policy`](struct.ObjectSpace.html#method.with_collection_policy).
+/// The default policy always collects, and drops objects in arbitrary order.
+#[derive(Clone, Copy, Debug)]
+pub struct CollectionPolicy {
+    /// Collecting does nothing if fewer objects are tracked.
+    pub min_tracked_to_collect: usize,
+
+    /// Maximum number of scans per collection, at least 1. Objects are
+    /// scanned again after running finalizers, since they might resurrect
+    /// objects. Objects left unscanned are collected by the next collection.
+    pub max_passes: usize,
+
+    /// Order of dropping collected objects.
+    pub drop_order: DropOrder,
+
+    /// [`ObjectSpace::collect_cycles_heuristic`](struct.ObjectSpace.html#method.collect_cycles_heuristic)
+    /// collects once the objects tracked since the last collection exceed
+    /// this fraction of all tracked objects.
+    pub allocation_threshold: f64,
+
+    /// Make [`ObjectSpace::create`](struct.ObjectSpace.html#method.create)
+    /// call [`collect_cycles`](struct.ObjectSpace.html#method.collect_cycles)
+    /// before constructing the new object, once this many objects are
+    /// tracked. `None` never collects automatically.
+    ///
+    /// To keep `create` cheap, the tracked objects are not counted. Like
+    /// `collect_cycles_heuristic`, the count is estimated as the objects left
+    /// by the last collection plus the newly tracked ones. If this many
+    /// objects stay alive, every `create` collects, so the threshold should
+    /// be well above the expected live objects.
+    ///
+    /// ```
+    /// use jrsonnet_gcmodule::{Cc, CollectionPolicy, ObjectSpace, Trace};
+    /// use std::cell::RefCell;
+    ///
+    /// let space = ObjectSpace::with_collection_policy(CollectionPolicy {
+    ///     auto_collect_threshold: Some(1),
+    ///     ..Default::default()
+    /// });
+    /// let a: Cc<RefCell<Option<Box<dyn Trace>>>> = space.create(RefCell::new(None));
+    /// *a.borrow_mut() = Some(Box::new(a.clone()));
+    /// drop(a);
+    /// let _b = space.create(RefCell::new(None::<Box<dyn Trace>>));
+    /// // `a` was collected before creating `b`.
+    /// assert_eq!(space.count_tracked(), 1);
+    /// ```
+    pub auto_collect_threshold: Option<usize>,
+}
+
+impl Default for CollectionPolicy {
+    fn default() -> Self {
+        Self {
+            min_tracked_to_collect: 0,
+            max_passes: usize::MAX,
+            drop_order: DropOrder::Arbitrary,
+            allocation_threshold: 0.25,
+            auto_collect_threshold: None,
+        }
+    }
+}
+
+/// Order of dropping collected objects. See
+/// [`CollectionPolicy`](struct.CollectionPolicy.html).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DropOrder {
+    /// Whatever order is the cheapest.
+    #[default]
+    Arbitrary,
+
+    /// Drop objects before the objects referring to them. Objects in the
+    /// same cycle are still dropped in arbitrary order.
+    TopologicalChildrenFirst,
+}
+
+/// What [`ObjectSpace::collect_cycles_verbose`](struct.ObjectSpace.html#method.collect_cycles_verbose)
+/// and [`ThreadedObjectSpace::collect_cycles_verbose`](struct.ThreadedObjectSpace.html#method.collect_cycles_verbose)
+/// collected.
+#[derive(Clone, Debug, Default)]
+pub struct CollectionReport {
+    /// Number of collected objects. Same as the return value of
+    /// `collect_cycles`.
+    pub total_collected: usize,
+
+    /// Number of objects considered. `total_scanned - total_collected`
+    /// objects survived.
+    pub total_scanned: usize,
+
+    /// Time spent collecting.
+    pub duration: Duration,
+
+    /// Number of collected objects by `Trace::type_name`.
+    pub by_type: HashMap<&'static str, usize>,
+
+    /// Sizes of groups of collected objects connected by references, largest
+    /// first. Each group includes at least one cycle, and the objects only
+    /// reachable from it.
+    pub cycle_sizes: Vec<usize>,
+}
+
+/// Collects the details of a `CollectionReport` from the objects released
+/// by `collect_list`.
+#[derive(Default)]
+pub(crate) struct ReportBuilder {
+    by_type: HashMap<&'static str, usize>,
+    nodes: Vec<ObjectId>,
+    edges: Vec<Vec<ObjectId>>,
+}
+
+impl ReportBuilder {
+    /// Record an object released from a list of `L` headers.
+    pub(crate) fn record<L: Linked>(&mut self, id: ObjectId, value: &dyn CcDyn) {
+        *self.by_type.entry(value.gc_type_name()).or_insert(0) += 1;
+        let mut referents = Vec::new();
+        let mut visit = |ptr: *const ()| referents.push(ObjectId::from_ptr(ptr));
+        value.gc_traverse(&mut graph::inspect_tracer::<L>(&mut visit));
+        self.nodes.push(id);
+        self.edges.push(referents);
+    }
+
+    pub(crate) fn finish(self, stats: CollectionStats, start: Instant) -> CollectionReport {
+        let mut cycle_sizes = graph::component_sizes(&self.nodes, &self.edges);
+        cycle_sizes.sort_unstable_by(|a, b| b.cmp(a));
+        CollectionReport {
+            total_collected: stats.collected,
+            total_scanned: stats.scanned,
+            duration: start.elapsed(),
+            by_type: self.by_type,
+            cycle_sizes,
+        }
+    }
+}
+
+/// Registered by `ObjectSpace::on_collect`.
+type CollectHook = Box<dyn Fn(CollectionEvent)>;
+
+/// Passed to hooks registered by
+/// [`ObjectSpace::on_collect`](struct.ObjectSpace.html#method.on_collect) and
+/// [`ThreadedObjectSpace::on_collect`](struct.ThreadedObjectSpace.html#method.on_collect).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CollectionEvent {
+    /// Collecting is about to start.
+    BeforeCollect,
+
+    /// Collecting has finished.
+    AfterCollect(CollectionStats),
+}
+
+/// Summary of a collection. See
+/// [`CollectionEvent::AfterCollect`](enum.CollectionEvent.html#variant.AfterCollect).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CollectionStats {
+    /// Number of collected objects.
+    pub collected: usize,
+
+    /// Number of objects considered.
+    pub scanned: usize,
+}
+
+/// Receives events from an [`ObjectSpace`](struct.ObjectSpace.html).
+///
+/// Register it by
+/// [`ObjectSpace::set_listener`](struct.ObjectSpace.html#method.set_listener).
+/// This can be used to feed metrics to external monitoring systems.
+pub trait GcListener {
+    /// Called before collecting, with the number of tracked objects.
+    fn before_collect(&self, tracked: usize);
+
+    /// Called after collecting, with the number of collected objects.
+    fn after_collect(&self, collected: usize);
+
+    /// Called when a new object is created in the space.
+    fn on_alloc(&self, type_name: &str);
+
+    /// Called when the memory of an object in the space is released, by the
+    /// collector or by running out of references.
+    ///
+    /// Objects that are not tracked (see
+    /// [`Trace::is_type_tracked`](trait.Trace.html#method.is_type_tracked))
+    /// are not reported, since they are not linked to the space.
+    fn on_dealloc(&self, type_name: &str);
+}
+
 /// This is a private type.
 pub trait AbstractObjectSpace: 'static + Sized {
     type RefCount: RefCount;
-    type Header;
+    type Header: 'static;
 
     /// Insert "header" and "value" to the linked list.
     fn insert(&self, header: &mut Self::Header, value: &dyn CcDyn);
@@ -80,6 +346,11 @@ pub trait AbstractObjectSpace: 'static + Sized {
     fn new_ref_count(&self, tracked: bool) -> Self::RefCount;
 
     fn empty_header(&self) -> Self::Header;
+
+    /// Called after a new object is created in this space.
+    fn on_alloc(&self, type_name: &'static str) {
+        let _ = type_name;
+    }
 }
 
 impl AbstractObjectSpace for ObjectSpace {
@@ -87,24 +358,19 @@ impl AbstractObjectSpace for ObjectSpace {
     type Header = GcHeader;
 
     fn insert(&self, header: &mut Self::Header, value: &dyn CcDyn) {
-        let prev: &GcHeader = &self.list.borrow();
         debug_assert!(header.next.get().is_null());
-        let next = prev.next.get();
-        header.prev.set(prev);
-        header.next.set(next);
-        unsafe {
-            // safety: The linked list is maintained, and pointers are valid.
-            (*next).prev.set(header);
-            // safety: To access vtable pointer. Test by test_gc_header_value.
-            let fat_ptr: [*mut (); 2] = mem::transmute(value);
-            header.ccdyn_vptr = fat_ptr[1];
-        }
-        prev.next.set(header);
+        self.init_header(header, value);
+        self.link_new(header, header, 1);
     }
 
     #[inline]
     fn remove(header: &Self::Header) {
         let header: &GcHeader = header;
+        #[cfg(debug_assertions)]
+        assert!(
+            header.list_token.is_head_alive(),
+            "bug: a Cc outlived its ObjectSpace (was it moved out of ObjectSpace::scope?)"
+        );
         debug_assert!(!header.next.get().is_null());
         debug_assert!(!header.prev.get().is_null());
         let next = unmask_ptr(header.next.get());
@@ -115,6 +381,9 @@ impl AbstractObjectSpace for ObjectSpace {
             (*next).prev.set(prev);
         }
         header.next.set(std::ptr::null_mut());
+        if let Some(listener) = listener_of(header.listener.get()) {
+            listener.on_dealloc(header.value().gc_type_name());
+        }
     }
 
     #[inline]
@@ -126,6 +395,12 @@ impl AbstractObjectSpace for ObjectSpace {
     fn empty_header(&self) -> Self::Header {
         GcHeader::empty()
     }
+
+    fn on_alloc(&self, type_name: &'static str) {
+        if let Some(listener) = listener_of(self.listener_slot.get()) {
+            listener.on_alloc(type_name);
+        }
+    }
 }
 
 impl Default for ObjectSpace {
@@ -134,12 +409,52 @@ impl Default for ObjectSpace {
         let header = new_gc_list();
         Self {
             list: RefCell::new(header),
+            listener_slot: Cell::new(0),
+            hooks: Default::default(),
+            is_collecting: Cell::new(false),
+            generation: Cell::new(0),
+            step_marker: Box::pin(GcHeader::marker()),
+            step_collected: Cell::new(0),
+            step_scanned: Cell::new(0),
+            allocations: Cell::new(0),
+            survivors: Cell::new(0),
+            interned: Default::default(),
+            policy: Default::default(),
             _phantom: PhantomData,
         }
     }
 }
 
 impl ObjectSpace {
+    /// Constructs an empty [`ObjectSpace`](struct.ObjectSpace.html) that
+    /// collects cycles following `policy`.
+    ///
+    /// ```
+    /// use jrsonnet_gcmodule::{CollectionPolicy, ObjectSpace};
+    ///
+    /// let space = ObjectSpace::with_collection_policy(CollectionPolicy {
+    ///     min_tracked_to_collect: 1000,
+    ///     ..Default::default()
+    /// });
+    /// ```
+    pub fn with_collection_policy(policy: CollectionPolicy) -> Self {
+        let mut space = Self::default();
+        space.policy = policy;
+        space
+    }
+
+    /// Collect cycles if
+    /// [`CollectionPolicy::auto_collect_threshold`](struct.CollectionPolicy.html#structfield.auto_collect_threshold)
+    /// is reached.
+    fn auto_collect(&self) {
+        if let Some(threshold) = self.policy.auto_collect_threshold {
+            let tracked = self.survivors.get().saturating_add(self.allocations.get());
+            if tracked >= threshold {
+                let _ = self.collect_cycles();
+            }
+        }
+    }
+
     /// Count objects tracked by this [`ObjectSpace`](struct.ObjectSpace.html).
     pub fn count_tracked(&self) -> usize {
         let list: &GcHeader = &self.list.borrow();
@@ -148,11 +463,647 @@ impl ObjectSpace {
         count
     }
 
+    /// Estimate the memory used by objects tracked by this
+    /// [`ObjectSpace`](struct.ObjectSpace.html), in bytes.
+    ///
+    /// This is the size of each object including the bookkeeping of the
+    /// collector. Memory owned by the objects, like the buffer of a `Vec`,
+    /// and the overhead of the allocator are not included.
+    pub fn live_bytes(&self) -> usize {
+        let list: &GcHeader = &self.list.borrow();
+        live_bytes_list(list)
+    }
+
+    /// Count objects tracked by this [`ObjectSpace`](struct.ObjectSpace.html)
+    /// by `Trace::type_name`, most common types first.
+    ///
+    /// This is intended for feeding metrics to external monitoring systems.
+    pub fn count_tracked_snapshot(&self) -> Vec<(String, usize)> {
+        let list: &GcHeader = &self.list.borrow();
+        count_tracked_by_type(list)
+    }
+
+    /// Call `f` with each object tracked by this
+    /// [`ObjectSpace`](struct.ObjectSpace.html). Use
+    /// [`downcast_ref`](https://doc.rust-lang.org/std/any/trait.Any.html#method.downcast_ref)
+    /// to inspect objects of known types.
+    ///
+    /// This is intended for debugging and profiling tools. Each object is
+    /// kept alive while `f` runs, so `f` can drop or create other objects.
+    /// Objects created by `f` are not visited.
+    ///
+    /// Calling this from a `Drop` implementation run by the collector does
+    /// nothing.
+    ///
+    /// ```
+    /// use jrsonnet_gcmodule::{Cc, ObjectSpace, Trace};
+    /// use std::cell::RefCell;
+    ///
+    /// let space = ObjectSpace::default();
+    /// let a: Cc<RefCell<Vec<Box<dyn Trace>>>> = space.create(Default::default());
+    /// let mut count = 0;
+    /// space.for_each_tracked(|value| {
+    ///     if let Some(value) = value.downcast_ref::<RefCell<Vec<Box<dyn Trace>>>>() {
+    ///         count += value.borrow().len() + 1;
+    ///     }
+    /// });
+    /// assert_eq!(count, 1);
+    /// ```
+    pub fn for_each_tracked(&self, mut f: impl FnMut(&dyn Any)) {
+        if self.is_collecting.get() {
+            return;
+        }
+        // Take references first. `f` might change the list.
+        let mut objects = Vec::new();
+        {
+            let list: &GcHeader = &self.list.borrow();
+            visit_list(list, |header| {
+                if header.value().gc_as_any().is_some() {
+                    objects.push((header as *const GcHeader, header.value().gc_clone()));
+                }
+            });
+        }
+        for (header, _keep_alive) in &objects {
+            // safety: `_keep_alive` keeps the object, including the header,
+            // alive. The value is not dropped since it has strong references.
+            let header = unsafe { &**header };
+            if let Some(value) = header.value().gc_as_any() {
+                f(value);
+            }
+        }
+    }
+
+    /// Whether this [`ObjectSpace`](struct.ObjectSpace.html) is collecting
+    /// garbage.
+    ///
+    /// This is `true` while [`collect_cycles`](#method.collect_cycles) (or a
+    /// variant of it) or [`drain_unreachable`](#method.drain_unreachable)
+    /// runs, which `Trace::trace`, `Finalize::finalize` and `Drop`
+    /// implementations can observe. It is `false` during
+    /// [`count_cycles_estimate`](#method.count_cycles_estimate), which does
+    /// not change anything.
+    ///
+    /// While collecting:
+    /// - `Trace::trace` should visit the same values every time, and avoid
+    ///   heavy work like populating caches.
+    /// - `Drop` implementations should not dereference other `Cc<T>`
+    ///   objects, which might have been dropped already.
+    /// - Calling `collect_cycles` again does nothing and returns 0.
+    pub fn is_collecting(&self) -> bool {
+        self.is_collecting.get()
+    }
+
     /// Collect cyclic garbage tracked by this [`ObjectSpace`](struct.ObjectSpace.html).
     /// Return the number of objects collected.
+    ///
+    /// Only objects created since the last
+    /// [`generation_bump`](#method.generation_bump) are considered. Use
+    /// [`collect_cycles_full`](#method.collect_cycles_full) to also consider
+    /// older objects.
+    ///
+    /// Calling this from a `Drop` implementation run by the collector does
+    /// nothing and returns 0.
+    #[must_use = "check whether any objects were collected; call in a loop for complete collection"]
     pub fn collect_cycles(&self) -> usize {
+        self.collect_cycles_with(self.young_candidates(), |_, _| {})
+            .collected
+    }
+
+    /// Like [`collect_cycles`](#method.collect_cycles), but only collect if
+    /// enough objects were tracked since the last collection. Otherwise
+    /// return 0 without scanning anything.
+    ///
+    /// Collection happens once the newly tracked objects exceed
+    /// [`CollectionPolicy::allocation_threshold`](struct.CollectionPolicy.html#structfield.allocation_threshold)
+    /// of all tracked objects, similar to the thresholds of CPython's
+    /// collector. This is cheap enough to call after every allocation, and
+    /// keeps the amortized collection cost proportional to allocations.
+    ///
+    /// The total is estimated as the objects left by the last collection
+    /// plus the newly tracked ones. Objects released by reference counting
+    /// are not subtracted.
+    ///
+    /// ```
+    /// use jrsonnet_gcmodule::{Cc, ObjectSpace, Trace};
+    /// use std::cell::RefCell;
+    ///
+    /// let space = ObjectSpace::default();
+    /// let a: Cc<RefCell<Option<Box<dyn Trace>>>> = space.create(RefCell::new(None));
+    /// *a.borrow_mut() = Some(Box::new(a.clone()));
+    /// drop(a);
+    /// assert_eq!(space.collect_cycles_heuristic(), 1);
+    /// // Nothing was tracked since.
+    /// assert_eq!(space.collect_cycles_heuristic(), 0);
+    /// ```
+    pub fn collect_cycles_heuristic(&self) -> usize {
+        let allocations = self.allocations.get();
+        let total = self.survivors.get().saturating_add(allocations);
+        if total < self.policy.min_tracked_to_collect
+            || (allocations as f64) <= self.policy.allocation_threshold * total.max(1) as f64
+        {
+            return 0;
+        }
+        self.collect_cycles()
+    }
+
+    /// Like [`collect_cycles`](#method.collect_cycles), but consider objects
+    /// of all generations.
+    pub fn collect_cycles_full(&self) -> usize {
+        self.collect_cycles_with(Candidates::ALL, |_, _| {})
+            .collected
+    }
+
+    /// Drop tracked objects that are only referred by themselves, like a
+    /// `Cc` storing a clone of itself. Return the number of objects dropped.
+    ///
+    /// This is a single pass over the tracked objects without cycle
+    /// detection, so it is cheaper than
+    /// [`collect_cycles`](#method.collect_cycles). Cycles of more than one
+    /// object, and objects with finalizers, are left for `collect_cycles`.
+    ///
+    /// Objects that are only referred by dropped objects are released by
+    /// reference counting as usual. Hooks registered by
+    /// [`on_collect`](#method.on_collect) are called like for
+    /// `collect_cycles`.
+    ///
+    /// ```
+    /// use jrsonnet_gcmodule::{Cc, ObjectSpace, Trace};
+    /// use std::cell::RefCell;
+    ///
+    /// let space = ObjectSpace::default();
+    /// let a: Cc<RefCell<Option<Box<dyn Trace>>>> = space.create(RefCell::new(None));
+    /// *a.borrow_mut() = Some(Box::new(a.clone()));
+    /// assert_eq!(space.drain_unreachable(), 0);
+    /// drop(a);
+    /// assert_eq!(space.drain_unreachable(), 1);
+    /// assert_eq!(space.count_tracked(), 0);
+    /// ```
+    pub fn drain_unreachable(&self) -> usize {
+        if self.is_collecting.get() {
+            debug::log(|| ("collect", "skip reentrant drain_unreachable"));
+            return 0;
+        }
+        if self.is_empty() {
+            return 0;
+        }
+        let collected = self
+            .collecting(
+                || self.count_tracked(),
+                |list, _| {
+                    let (marked, scanned) = mark_self_referred(list);
+                    let collected = sweep_phase(marked, ());
+                    CollectionStats { collected, scanned }
+                },
+            )
+            .collected;
+        self.update_survivors(collected);
+        collected
+    }
+
+    /// Start a new generation. Objects created before this call become "old"
+    /// and are skipped by [`collect_cycles`](#method.collect_cycles).
+    ///
+    /// Returns the id of the new generation, which can be compared with
+    /// [`Cc::generation`](type.Cc.html#method.generation). Ids wrap around
+    /// after 256 calls. Objects whose generation id is reused are considered
+    /// young again, which makes `collect_cycles` scan more objects but does
+    /// not affect what is collected from the young ones.
+    ///
+    /// References from old objects are treated like references from outside
+    /// the space. So old objects keep the new objects they refer to alive
+    /// until the next `collect_cycles_full`.
+    pub fn generation_bump(&self) -> GenerationId {
+        let generation = self.generation.get().wrapping_add(1);
+        self.generation.set(generation);
+        GenerationId(generation)
+    }
+
+    /// Like [`collect_cycles`](#method.collect_cycles), and describe what
+    /// was collected.
+    ///
+    /// This is slower than `collect_cycles` since it also visits the
+    /// references between collected objects.
+    pub fn collect_cycles_verbose(&self) -> CollectionReport {
+        let start = Instant::now();
+        let mut builder = ReportBuilder::default();
+        let stats = self.collect_cycles_with(self.young_candidates(), |id, value| {
+            builder.record::<GcHeader>(id, value)
+        });
+        builder.finish(stats, start)
+    }
+
+    /// Collect cycles formed by a part of the tracked objects. Returns
+    /// [`StepResult::Complete`](enum.StepResult.html#variant.Complete) after
+    /// all objects have been considered since the last completed pass.
+    ///
+    /// Each call takes objects in the current generation, continuing from
+    /// where the previous call stopped, until `budget` objects were scanned.
+    /// The objects referred by taken objects are scanned with them, so a
+    /// cycle is considered as a whole no matter where the calls stop. That
+    /// adds up to `budget` more objects. This bounds the time spent per
+    /// call, so collection can be spread across frames, for example.
+    ///
+    /// References from objects outside the considered part are treated like
+    /// references from outside the space. A cycle is collected if it, with
+    /// the objects it refers to, fits within `budget` objects. So a complete
+    /// pass collects the same objects as
+    /// [`collect_cycles`](#method.collect_cycles) only if every garbage cycle
+    /// fits. Larger cycles are left alive by every pass, so call
+    /// `collect_cycles` occasionally to collect them.
+    ///
+    /// Each call scans its part completely. No scan state is kept between
+    /// calls, since the program can change references between them, which
+    /// would invalidate the reference counts computed by an interrupted scan.
+    /// Only the position in the list is kept, and objects created or
+    /// released between calls do not move it.
+    ///
+    /// Calling this from a `Drop` implementation run by the collector does
+    /// nothing and returns `StepResult::Complete(0)`.
+    pub fn collect_cycles_step(&self, budget: usize) -> StepResult {
+        if self.is_collecting.get() {
+            debug::log(|| ("collect", "skip reentrant collect_cycles_step"));
+            return StepResult::Complete(0);
+        }
+        let marker: &GcHeader = &self.step_marker;
+        let generation = self.generation.get();
+        let estimate = self.survivors.get().saturating_add(self.allocations.get());
+        let mut scanned = 0;
+        let mut complete = false;
+        let stats = self.collecting(
+            || estimate,
+            |list, tables| {
+                if marker.next.get().is_null() {
+                    marker.link_after(list);
+                }
+                let mut passes = 0;
+                loop {
+                    passes += 1;
+                    let step = take_step(list, marker, generation, budget);
+                    let marked = mark_scope(
+                        Scope::Objects(&step.objects),
+                        self.policy.drop_order,
+                        tables,
+                        |_, _| {},
+                    );
+                    let needs_rescan = marked.needs_rescan();
+                    if !needs_rescan {
+                        // Move on while the list is intact. Objects that are
+                        // going to be dropped unlink themselves around the
+                        // marker.
+                        scanned = step.scanned;
+                        complete = step.complete;
+                        marker.unlink();
+                        if !complete {
+                            marker.link_after(step.end);
+                        }
+                    }
+                    let count = sweep_phase(marked, ());
+                    if !needs_rescan || passes >= self.policy.max_passes {
+                        return CollectionStats {
+                            collected: count,
+                            scanned: step.scanned,
+                        };
+                    }
+                }
+            },
+        );
+        let total = self.step_collected.get().saturating_add(stats.collected);
+        let scanned = self.step_scanned.get().saturating_add(scanned);
+        if complete {
+            self.step_collected.set(0);
+            self.step_scanned.set(0);
+            self.allocations.set(0);
+            self.survivors.set(scanned.saturating_sub(total));
+            StepResult::Complete(total)
+        } else {
+            self.step_collected.set(total);
+            self.step_scanned.set(scanned);
+            StepResult::Partial(estimate.saturating_sub(scanned).max(1))
+        }
+    }
+
+    /// Forget the position of `collect_cycles_step`, so the next call starts
+    /// a new pass.
+    pub(crate) fn reset_step(&self) {
+        self.step_marker.unlink();
+        self.step_collected.set(0);
+        self.step_scanned.set(0);
+    }
+
+    /// Whether the tracked object of `header` is in this space.
+    #[cfg(debug_assertions)]
+    fn owns(&self, header: &GcHeader) -> bool {
+        header.list_token.is_in_list(&self.list.borrow().list_token)
+    }
+
+    /// Panic if `value` refers to tracked objects of a different space.
+    /// The collector cannot see such references, so cycles going through
+    /// them are never collected.
+    ///
+    /// Objects of other space types, like `ThreadedCc<T>`, have a different
+    /// header and are skipped. The value is traced as a dry run, so `Trace`
+    /// implementations can skip expensive side effects.
+    #[cfg(debug_assertions)]
+    fn check_same_space(&self, value: &dyn CcDyn) {
+        let list = self.list.borrow();
+        let mut visit = |ptr: *const ()| {
+            // safety: The tracer only visits GcHeaders.
+            let header = unsafe { &*(ptr as *const GcHeader) };
+            if !header.list_token.is_in_list(&list.list_token) {
+                panic!(
+                    "bug: {} refers to a {} in a different ObjectSpace",
+                    value.gc_type_name(),
+                    header.value().gc_type_name()
+                );
+            }
+        };
+        debug::without_log(|| {
+            let mut tracer =
+                Tracer::with_mode(&mut visit, TracerMode::DryRun).with_header_type::<GcHeader>();
+            value.gc_traverse(&mut tracer)
+        });
+    }
+
+    /// Fill in the header of a new object of this space, before linking it
+    /// by `link_new`.
+    fn init_header(&self, header: &mut GcHeader, value: &dyn CcDyn) {
+        #[cfg(debug_assertions)]
+        self.check_same_space(value);
+        header.ccdyn_vptr = ccdyn_vptr_of(value);
+        header.generation = self.generation.get();
+        header.listener.set(self.listener_slot.get());
+        #[cfg(debug_assertions)]
+        {
+            header.list_token = self.list.borrow().list_token.for_object();
+        }
+    }
+
+    /// Link `count` new objects, chained from `first` to `last`, right after
+    /// the list head.
+    fn link_new(&self, first: &GcHeader, last: &GcHeader, count: usize) {
+        let head: &GcHeader = &self.list.borrow();
+        let next = head.next.get();
+        first.prev.set(head);
+        last.next.set(next);
+        // safety: The linked list is maintained, and pointers are valid.
+        unsafe { (*next).prev.set(last) };
+        head.next.set(first);
+        self.allocations
+            .set(self.allocations.get().wrapping_add(count));
+    }
+
+    /// Objects considered by `collect_cycles`.
+    fn young_candidates(&self) -> Candidates {
+        Candidates {
+            generation: Some(self.generation.get()),
+            ..Candidates::ALL
+        }
+    }
+
+    /// Whether `collect_cycles_with` would scan anything.
+    fn should_collect(&self) -> bool {
+        if self.is_empty() {
+            return false;
+        }
+        if self.is_collecting.get() {
+            debug::log(|| ("collect", "skip reentrant collect_cycles"));
+            return false;
+        }
+        self.has_tracked(self.policy.min_tracked_to_collect)
+    }
+
+    /// Whether at least `count` objects are tracked. Unlike `count_tracked`,
+    /// this stops at `count`.
+    fn has_tracked(&self, count: usize) -> bool {
+        let list: &GcHeader = &self.list.borrow();
+        let mut found = 0;
+        let mut ptr = list.next();
+        while found < count && !std::ptr::eq(ptr, list) {
+            // safety: The linked list is maintained. Pointers in it are valid.
+            let header = unsafe { &*ptr };
+            if !header.is_marker() {
+                found += 1;
+            }
+            ptr = header.next();
+        }
+        found >= count
+    }
+
+    /// Collect cycles among `candidates`, calling `on_release` for each
+    /// unreachable object before dropping.
+    fn collect_cycles_with(
+        &self,
+        candidates: Candidates,
+        on_release: impl FnMut(ObjectId, &dyn CcDyn),
+    ) -> CollectionStats {
+        if !self.should_collect() {
+            return CollectionStats::default();
+        }
+        let stats = self.collecting(
+            || self.count_tracked(),
+            |list, tables| collect_list(list, candidates, &self.policy, tables, || (), on_release),
+        );
+        self.update_survivors(stats.collected);
+        stats
+    }
+
+    /// Estimate the objects left after collecting, without counting them.
+    /// Like `collect_cycles_heuristic`, objects released by reference
+    /// counting are not subtracted.
+    fn update_survivors(&self, collected: usize) {
+        let total = self.survivors.get().saturating_add(self.allocations.get());
+        self.allocations.set(0);
+        self.survivors.set(total.saturating_sub(collected));
+    }
+
+    /// Run `collect` on the list, with hooks and the listener notified
+    /// around it. `tracked` is reported to the listener.
+    fn collecting(
+        &self,
+        tracked: impl FnOnce() -> usize,
+        collect: impl FnOnce(&GcHeader, &[Rc<dyn EphemeronEntries>]) -> CollectionStats,
+    ) -> CollectionStats {
+        self.is_collecting.set(true);
+        let _reset = ResetOnDrop(&self.is_collecting);
+        self.run_hooks(CollectionEvent::BeforeCollect);
+        // Not borrowed from `self`, so `set_listener` can be called while
+        // collecting. Released objects are reported by `remove`.
+        let listener = listener_of(self.listener_slot.get());
+        if let Some(listener) = &listener {
+            listener.before_collect(tracked());
+        }
+        let stats = {
+            let list: &GcHeader = &self.list.borrow();
+            collect(list, &ephemeron::thread_tables())
+        };
+        if let Some(listener) = &listener {
+            listener.after_collect(stats.collected);
+        }
+        self.run_hooks(CollectionEvent::AfterCollect(stats));
+        stats
+    }
+
+    fn run_hooks(&self, event: CollectionEvent) {
+        for hook in self.hooks.borrow().iter() {
+            hook(event);
+        }
+    }
+
+    /// Register `hook` to be called before and after each collection, for
+    /// example, to pause other work or to emit metrics. Hooks run in the
+    /// order they were registered.
+    ///
+    /// Hooks run while [`is_collecting`](#method.is_collecting) is `true`,
+    /// so collecting from a hook does nothing. Registering hooks from a hook
+    /// panics. Hooks are not called if nothing is tracked, or if
+    /// [`CollectionPolicy::min_tracked_to_collect`](struct.CollectionPolicy.html#structfield.min_tracked_to_collect)
+    /// skips the collection.
+    ///
+    /// ```
+    /// use jrsonnet_gcmodule::{Cc, CollectionEvent, ObjectSpace, Trace};
+    /// use std::cell::{Cell, RefCell};
+    /// use std::rc::Rc;
+    ///
+    /// let space = ObjectSpace::default();
+    /// let collections = Rc::new(Cell::new(0));
+    /// let counter = collections.clone();
+    /// space.on_collect(move |event| {
+    ///     if let CollectionEvent::AfterCollect(_) = event {
+    ///         counter.set(counter.get() + 1);
+    ///     }
+    /// });
+    /// let _a: Cc<RefCell<Vec<Box<dyn Trace>>>> = space.create(Default::default());
+    /// let _ = space.collect_cycles();
+    /// assert_eq!(collections.get(), 1);
+    /// ```
+    pub fn on_collect(&self, hook: impl Fn(CollectionEvent) + 'static) {
+        self.hooks.borrow_mut().push(Box::new(hook));
+    }
+
+    /// Register a [`GcListener`](trait.GcListener.html) to receive events of
+    /// this space. Replaces the previously registered listener.
+    ///
+    /// This can be called from the listener, or from `Drop` implementations
+    /// run by the collector.
+    pub fn set_listener(&self, listener: Box<dyn GcListener>) {
+        let listener: Rc<dyn GcListener> = Rc::from(listener);
+        let slot = self.listener_slot.get();
+        if slot != 0 {
+            let old = LISTENERS.with(|l| l.borrow_mut()[slot as usize - 1].replace(listener));
+            drop(old);
+            return;
+        }
+        let index = LISTENERS.with(|l| {
+            let mut listeners = l.borrow_mut();
+            match listeners.iter().position(|l| l.is_none()) {
+                Some(index) => {
+                    listeners[index] = Some(listener);
+                    index
+                }
+                None => {
+                    listeners.push(Some(listener));
+                    listeners.len() - 1
+                }
+            }
+        });
+        let slot = u16::try_from(index + 1).expect("too many ObjectSpaces with listeners");
+        self.listener_slot.set(slot);
+        // Existing objects report to the listener too.
         let list: &GcHeader = &self.list.borrow();
-        collect_list(list, ())
+        visit_list(list, |header| header.listener.set(slot));
+    }
+
+    /// Count objects that [`collect_cycles`](#method.collect_cycles) would
+    /// collect, without collecting them.
+    ///
+    /// `Trace` implementations see
+    /// [`Tracer::is_dry_run`](struct.Tracer.html#method.is_dry_run) returning
+    /// `true` during this pass.
+    pub fn count_cycles_estimate(&self) -> usize {
+        if self.is_empty() || self.is_collecting.get() {
+            return 0;
+        }
+        let list: &GcHeader = &self.list.borrow();
+        count_unreachable_list(list, self.young_candidates(), &ephemeron::thread_tables())
+    }
+
+    /// Whether no objects are tracked. Cheaper than `count_tracked() == 0`.
+    fn is_empty(&self) -> bool {
+        let list: &GcHeader = &self.list.borrow();
+        let mut next = list.next();
+        if std::ptr::eq(next, &*self.step_marker) {
+            next = self.step_marker.next();
+        }
+        std::ptr::eq(next, list)
+    }
+
+    /// Find groups of objects referring to each other in cycles, without
+    /// collecting them.
+    ///
+    /// Return strongly connected components of the object graph that have
+    /// more than one member. Objects that are reachable from outside are
+    /// included too, since this does not check ref counts.
+    pub fn find_strongly_connected_components(&self) -> Vec<Vec<ObjectId>> {
+        let list: &GcHeader = &self.list.borrow();
+        graph::build_cycle_graph(list).strongly_connected_components()
+    }
+
+    /// Find tracked objects that are referred from outside the tracked
+    /// objects, for example, by a `Cc<T>` on the stack. Such objects, and
+    /// objects they refer to, are not collected.
+    ///
+    /// This helps to find out why objects are not collected. The returned
+    /// ids match [`Cc::object_id`](type.Cc.html#method.object_id).
+    ///
+    /// Calling this from a `Drop` implementation run by the collector
+    /// returns an empty list.
+    pub fn find_root_objects(&self) -> Vec<ObjectId> {
+        if self.is_collecting.get() {
+            return Vec::new();
+        }
+        let list: &GcHeader = &self.list.borrow();
+        find_roots_list(list)
+    }
+
+    /// Walk the tracked objects and check that the list linking them is
+    /// consistent. Return the number of problems found. Each problem is
+    /// logged with the `debug` feature.
+    ///
+    /// Objects are not moved, since they are allocated individually. This
+    /// is the place to compact them if they are allocated from an arena in
+    /// the future.
+    ///
+    /// Calling this from a `Drop` implementation run by the collector does
+    /// nothing and returns 0.
+    pub fn shrink_tracked_list(&self) -> usize {
+        if self.is_collecting.get() {
+            return 0;
+        }
+        let list: &GcHeader = &self.list.borrow();
+        check_list(list)
+    }
+
+    /// Write tracked objects to `writer` as a JSON array, for analyzing the
+    /// heap with external tools.
+    ///
+    /// Each object is written as
+    /// `{"id": "0x...", "type": "...", "ref_count": N, "references": ["0x...", ...]}`,
+    /// where `id` is the address of the object, and `references` lists the
+    /// `id`s of tracked objects it refers to.
+    pub fn dump_heap(&self, writer: &mut impl Write) -> io::Result<()> {
+        let list: &GcHeader = &self.list.borrow();
+        graph::dump_heap(list, writer)
+    }
+
+    /// Describe tracked objects and the references between them in the
+    /// Graphviz DOT language, for visualizing with `dot -Tsvg`, for example.
+    ///
+    /// Each object is a node labeled with `Trace::type_name` and its
+    /// address, which matches `dump_heap`. Each reference visited by
+    /// `Trace::trace` is an edge.
+    pub fn to_dot(&self) -> String {
+        let list: &GcHeader = &self.list.borrow();
+        graph::to_dot(list)
     }
 
     /// Constructs a new [`Cc<T>`](type.Cc.html) in this
@@ -160,14 +1111,215 @@ impl ObjectSpace {
     ///
     /// The returned object should only refer to objects in the same space.
     /// Otherwise the collector might fail to collect cycles.
+    ///
+    /// Collects cycles first if the
+    /// [threshold](struct.CollectionPolicy.html#structfield.auto_collect_threshold)
+    /// is reached.
     pub fn create<T: Trace>(&self, value: T) -> Cc<T> {
+        self.auto_collect();
         // `&mut self` ensures thread-exclusive access.
         Cc::new_in_space(value, self)
     }
 
+    /// Constructs a new [`Cc<T>`](type.Cc.html) in this
+    /// [`ObjectSpace`](struct.ObjectSpace.html), with a clone of the value
+    /// of `other`, which should be an object of this space.
+    ///
+    /// Unlike `other.clone()`, which shares the value, changing the value
+    /// of the new `Cc<T>` does not affect `other`. Like objects created by
+    /// [`create`](#method.create), the new object is in the current
+    /// generation.
+    ///
+    /// ```
+    /// use jrsonnet_gcmodule::ObjectSpace;
+    /// use std::cell::Cell;
+    ///
+    /// let space = ObjectSpace::default();
+    /// let a = space.create(Cell::new(1));
+    /// let b = space.create_copy(&a);
+    /// b.set(2);
+    /// assert_eq!(a.get(), 1);
+    /// ```
+    pub fn create_copy<T: Trace + Clone>(&self, other: &Cc<T>) -> Cc<T> {
+        #[cfg(debug_assertions)]
+        if let Some(header) = other.gc_header() {
+            assert!(
+                self.owns(header),
+                "create_copy requires an object of this ObjectSpace"
+            );
+        }
+        self.create(other.deref().clone())
+    }
+
+    /// Move `cc` from the space it was created in to this
+    /// [`ObjectSpace`](struct.ObjectSpace.html), without reallocating it.
+    ///
+    /// Like objects created by [`create`](#method.create), the object should
+    /// only refer to objects in this space.
+    ///
+    /// Panics if `cc` is not the only strong reference to the object.
+    pub fn adopt<T: Trace>(&self, cc: Cc<T>) -> Cc<T> {
+        assert_eq!(
+            cc.strong_count(),
+            1,
+            "adopt requires the only strong reference to the object"
+        );
+        cc.relink(self);
+        cc
+    }
+
+    /// Stop tracking `cc` and move its value out, so the value can be
+    /// managed outside the collector, for example, by foreign code.
+    ///
+    /// The returned value might still refer to objects in this space.
+    /// Cycles going through it are no longer detected, and the objects in
+    /// them are leaked unless the value is dropped.
+    ///
+    /// Like [`Cc::try_unwrap`](type.Cc.html#method.try_unwrap), weak
+    /// references can no longer be upgraded afterwards, and the memory of
+    /// the object is released once they are gone.
+    ///
+    /// Panics if `cc` is not the only strong reference to the object. In
+    /// debug builds, also panics if `cc` is tracked by a different space.
+    ///
+    /// ```
+    /// use jrsonnet_gcmodule::{ObjectSpace, Trace};
+    /// use std::cell::RefCell;
+    ///
+    /// let space = ObjectSpace::default();
+    /// let a = space.create(RefCell::new(Vec::<Box<dyn Trace>>::new()));
+    /// assert_eq!(space.count_tracked(), 1);
+    /// let value = space.forget(a);
+    /// assert_eq!(space.count_tracked(), 0);
+    /// assert!(value.borrow().is_empty());
+    /// ```
+    pub fn forget<T: Trace>(&self, cc: Cc<T>) -> T {
+        assert_eq!(
+            cc.strong_count(),
+            1,
+            "forget requires the only strong reference to the object"
+        );
+        #[cfg(debug_assertions)]
+        if let Some(header) = cc.gc_header() {
+            assert!(
+                self.owns(header),
+                "forget requires an object of this ObjectSpace"
+            );
+        }
+        match Cc::try_unwrap(cc) {
+            Ok(value) => value,
+            Err(_) => panic!("forget requires an object that is not dropped"),
+        }
+    }
+
+    /// Constructs a new [`Cc<T, A>`](type.Cc.html) in this
+    /// [`ObjectSpace`](struct.ObjectSpace.html), allocated by `allocator`.
+    ///
+    /// The allocator is stored in the object, and used to release its
+    /// memory. It takes no space if it is zero-sized, like `Global`.
+    #[cfg(feature = "allocator-api")]
+    pub fn create_in<T: Trace, A: std::alloc::Allocator + 'static>(
+        &self,
+        value: T,
+        allocator: A,
+    ) -> Cc<T, A> {
+        self.auto_collect();
+        Cc::new_in_space_in(value, self, allocator)
+    }
+
+    /// Constructs multiple [`Cc<T>`](type.Cc.html) in this
+    /// [`ObjectSpace`](struct.ObjectSpace.html).
+    ///
+    /// This is equivalent to calling [`create`](#method.create) for each
+    /// value, but links the new objects into the space in one step.
+    pub fn create_many<T: Trace>(&self, values: Vec<T>) -> Vec<Cc<T>> {
+        if !T::is_type_tracked() {
+            return values.into_iter().map(|value| self.create(value)).collect();
+        }
+        self.auto_collect();
+
+        // Chain new headers into a detached list first, then splice it after
+        // the list head.
+        let mut first: *const GcHeader = std::ptr::null();
+        let mut last: *const GcHeader = std::ptr::null();
+        let result: Vec<Cc<T>> = values
+            .into_iter()
+            .map(|value| {
+                Cc::new_in_space_with(value, self, |header: &mut GcHeader, value| {
+                    self.init_header(header, value);
+                    header.prev.set(last);
+                    if last.is_null() {
+                        first = header;
+                    } else {
+                        // safety: `last` points to a header created above.
+                        unsafe { (*last).next.set(header) };
+                    }
+                    last = header;
+                })
+            })
+            .collect();
+
+        if !first.is_null() {
+            // safety: `first` and `last` point to headers created above.
+            unsafe { self.link_new(&*first, &*last, result.len()) };
+        }
+
+        for _ in 0..result.len() {
+            self.on_alloc(T::type_name());
+        }
+        result
+    }
+
+    /// Run `f` with a temporary [`ObjectSpace`](struct.ObjectSpace.html),
+    /// then collect cycles in it.
+    ///
+    /// The result of `f` should not contain objects created in the space.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use jrsonnet_gcmodule::{Cc, ObjectSpace, Trace};
+    /// use std::cell::RefCell;
+    ///
+    /// let tracked = ObjectSpace::scope(|space| {
+    ///     type List = Cc<RefCell<Vec<Box<dyn Trace>>>>;
+    ///     let a: List = space.create(Default::default());
+    ///     a.borrow_mut().push(Box::new(a.clone()));
+    ///     space.count_tracked()
+    /// });
+    /// assert_eq!(tracked, 1);
+    /// ```
+    pub fn scope<R>(f: impl FnOnce(&ObjectSpace) -> R) -> R {
+        let space = ObjectSpace::default();
+        let result = f(&space);
+        let _ = space.collect_cycles();
+        result
+    }
+
     /// Leak all objects allocated in this space
+    ///
+    /// This is the same as [`leak_all`](#method.leak_all).
     pub fn leak(&self) {
-        *self.list.borrow_mut() = new_gc_list();
+        self.leak_all();
+    }
+
+    /// Detach all tracked objects from this space without dropping them.
+    ///
+    /// Every tracked object gets an extra reference that is never released,
+    /// so all of them, and everything they refer to, become permanent leaks.
+    /// Afterwards [`count_tracked`](#method.count_tracked) returns 0.
+    ///
+    /// This is useful to exit fast and let the OS reclaim the memory.
+    pub fn leak_all(&self) {
+        self.reset_step();
+        let list: &GcHeader = &self.list.borrow();
+        visit_list(list, |header| {
+            mem::forget(header.value().gc_clone());
+            header.next.set(std::ptr::null());
+            header.prev.set(std::ptr::null());
+        });
+        list.next.set(list);
+        list.prev.set(list);
     }
 
     // TODO: Consider implementing "merge" or method to collect multiple spaces
@@ -176,20 +1328,62 @@ impl ObjectSpace {
 
 impl Drop for ObjectSpace {
     fn drop(&mut self) {
-        self.collect_cycles();
+        // Collect everything, ignoring `min_tracked_to_collect` and
+        // `max_passes`. Nothing else will.
+        if !self.is_empty() {
+            let policy = CollectionPolicy {
+                min_tracked_to_collect: 0,
+                max_passes: usize::MAX,
+                ..self.policy
+            };
+            self.collecting(
+                || self.count_tracked(),
+                |list, tables| {
+                    collect_list(list, Candidates::ALL, &policy, tables, || (), |_, _| {})
+                },
+            );
+        }
+        self.reset_step();
+        let slot = self.listener_slot.get();
+        if slot != 0 {
+            // Objects outliving the space no longer report to its listener,
+            // and the slot can be reused.
+            let list: &GcHeader = &self.list.borrow();
+            visit_list(list, |header| header.listener.set(0));
+            let old = LISTENERS.try_with(|l| l.borrow_mut()[slot as usize - 1].take());
+            drop(old);
+        }
     }
 }
 
-pub trait Linked {
+pub trait Linked: 'static {
     fn next(&self) -> *const Self;
     fn prev(&self) -> *const Self;
     fn set_prev(&self, other: *const Self);
 
     /// Get the trait object to operate on the actual `CcBox`.
     fn value(&self) -> &dyn CcDyn;
+
+    /// Generation of the object. `collect_list` can be restricted to objects
+    /// of a single generation.
+    fn generation(&self) -> u8 {
+        0
+    }
+
+    /// Whether this is a position marker in the list instead of an object.
+    /// See `ObjectSpace::step_marker`.
+    fn is_marker(&self) -> bool {
+        false
+    }
 }
 
 /// Internal metadata used by the cycle collector.
+///
+/// Tracked objects are allocated as `CcBoxWithGcHeader`, a `repr(C)` pair of
+/// `GcHeader` and `CcBox<T>`. `Linked::value` finds the `CcBox<T>` right
+/// after the header, so the size of `GcHeader` must be a multiple of the
+/// alignment of `CcBox<T>`. That is checked below for common types, and in
+/// `Cc::new` for all types.
 #[cfg_attr(target_pointer_width = "32", repr(C, align(8)))]
 #[cfg_attr(not(target_pointer_width = "32"), repr(C))]
 pub struct GcHeader {
@@ -198,6 +1392,60 @@ pub struct GcHeader {
 
     /// Vtable of (`&CcBox<T> as &dyn CcDyn`)
     pub(crate) ccdyn_vptr: *const (),
+
+    /// `ObjectSpace` generation when the object was created.
+    pub(crate) generation: u8,
+
+    /// `ObjectSpace::listener_slot` of the space, to report deallocation.
+    pub(crate) listener: Cell<u16>,
+
+    /// Detects objects outliving the list head on debug builds.
+    #[cfg(debug_assertions)]
+    pub(crate) list_token: ListToken,
+}
+
+const _: () = assert!(
+    mem::size_of::<GcHeader>() % mem::align_of::<crate::cc::RawCcBox<u64, ObjectSpace>>() == 0
+);
+
+/// Liveness of a linked list head, for debug builds.
+///
+/// The head owns the token. Objects refer to it weakly, so dropping an
+/// object after its list head was freed (usually with the `ObjectSpace`)
+/// can panic instead of writing to freed memory.
+#[cfg(debug_assertions)]
+pub(crate) enum ListToken {
+    None,
+    Head(std::sync::Arc<()>),
+    Object(std::sync::Weak<()>),
+}
+
+#[cfg(debug_assertions)]
+impl ListToken {
+    /// Token for a new object in the list of this head.
+    fn for_object(&self) -> Self {
+        match self {
+            ListToken::Head(arc) => ListToken::Object(std::sync::Arc::downgrade(arc)),
+            _ => ListToken::None,
+        }
+    }
+
+    /// Whether this object token belongs to the list of `head`.
+    fn is_in_list(&self, head: &Self) -> bool {
+        match (self, head) {
+            (ListToken::Object(weak), ListToken::Head(arc)) => {
+                std::ptr::eq(weak.as_ptr(), std::sync::Arc::as_ptr(arc))
+            }
+            _ => true,
+        }
+    }
+
+    fn is_head_alive(&self) -> bool {
+        match self {
+            ListToken::Object(weak) => weak.strong_count() > 0,
+            _ => true,
+        }
+    }
 }
 
 impl Linked for GcHeader {
@@ -223,6 +1471,14 @@ impl Linked for GcHeader {
             mem::transmute(fat_ptr)
         }
     }
+    #[inline]
+    fn generation(&self) -> u8 {
+        self.generation
+    }
+    #[inline]
+    fn is_marker(&self) -> bool {
+        self.ccdyn_vptr.is_null()
+    }
 }
 
 impl GcHeader {
@@ -232,7 +1488,47 @@ impl GcHeader {
             next: Cell::new(std::ptr::null()),
             prev: Cell::new(std::ptr::null()),
             ccdyn_vptr: CcDummy::ccdyn_vptr(),
+            generation: 0,
+            listener: Cell::new(0),
+            #[cfg(debug_assertions)]
+            list_token: ListToken::None,
+        }
+    }
+
+    /// Create a header marking a position in a list. It is linked like an
+    /// object, but skipped by `visit_list`.
+    fn marker() -> Self {
+        Self {
+            ccdyn_vptr: std::ptr::null(),
+            ..Self::empty()
+        }
+    }
+
+    /// Insert this unlinked header right after `anchor`.
+    fn link_after(&self, anchor: &GcHeader) {
+        debug_assert!(self.next.get().is_null());
+        let next = anchor.next.get();
+        self.prev.set(anchor);
+        self.next.set(next);
+        // safety: The linked list is maintained, and pointers are valid.
+        unsafe { (*next).prev.set(self) };
+        anchor.next.set(self);
+    }
+
+    /// Remove this header from its list, if it is linked.
+    fn unlink(&self) {
+        let next = self.next.get();
+        if next.is_null() {
+            return;
+        }
+        let prev = self.prev.get();
+        // safety: The linked list is maintained, and pointers are valid.
+        unsafe {
+            (*prev).next.set(next);
+            (*next).prev.set(prev);
         }
+        self.next.set(std::ptr::null());
+        self.prev.set(std::ptr::null());
     }
 }
 
@@ -253,29 +1549,289 @@ pub fn count_thread_tracked() -> usize {
 
 thread_local!(pub(crate) static THREAD_OBJECT_SPACE: ObjectSpace = ObjectSpace::default());
 
+// Listeners registered by `ObjectSpace::set_listener`, indexed by
+// `listener_slot - 1`. Objects find the listener of their space here, since
+// they do not refer to the space.
+thread_local!(static LISTENERS: RefCell<Vec<Option<Rc<dyn GcListener>>>> = Default::default());
+
+/// The listener registered at `slot`, if any.
+fn listener_of(slot: u16) -> Option<Rc<dyn GcListener>> {
+    if slot == 0 {
+        return None;
+    }
+    LISTENERS
+        .try_with(|l| l.borrow()[slot as usize - 1].clone())
+        .ok()
+        .flatten()
+}
+
 /// Acquire reference to thread-local global object space
 pub fn with_thread_object_space<R>(handler: impl FnOnce(&ObjectSpace) -> R) -> R {
     THREAD_OBJECT_SPACE.with(handler)
 }
 
+/// Extract the vtable pointer of a `CcDyn` trait object.
+#[inline]
+pub(crate) fn ccdyn_vptr_of(value: &dyn CcDyn) -> *const () {
+    // safety: To access vtable pointer. Test by test_gc_header_value.
+    let fat_ptr: [*const (); 2] = unsafe { mem::transmute(value) };
+    fat_ptr[1]
+}
+
 /// Create an empty linked list with a dummy GcHeader.
 pub(crate) fn new_gc_list() -> Pin<Box<GcHeader>> {
-    let pinned = Box::pin(GcHeader::empty());
+    #[allow(unused_mut)]
+    let mut head = GcHeader::empty();
+    #[cfg(debug_assertions)]
+    {
+        head.list_token = ListToken::Head(Default::default());
+    }
+    let pinned = Box::pin(head);
     let header: &GcHeader = pinned.deref();
     header.prev.set(header);
     header.next.set(header);
     pinned
 }
 
+/// Which objects in a linked list are considered by `collect_list`.
+#[derive(Clone, Copy)]
+pub(crate) struct Candidates {
+    /// Only consider objects of this generation.
+    pub(crate) generation: Option<u8>,
+
+    /// Skip this many objects from the start of the list.
+    pub(crate) skip: usize,
+
+    /// Consider at most this many objects after `skip`.
+    pub(crate) take: usize,
+}
+
+impl Candidates {
+    /// All objects in the list.
+    pub(crate) const ALL: Self = Self {
+        generation: None,
+        skip: 0,
+        take: usize::MAX,
+    };
+
+    fn contains<L: Linked>(&self, index: usize, header: &L) -> bool {
+        index >= self.skip
+            && index - self.skip < self.take
+            && self.generation.map_or(true, |g| header.generation() == g)
+    }
+}
+
 /// Scan the specified linked list. Collect cycles.
-pub(crate) fn collect_list<L: Linked, K>(list: &L, lock: K) -> usize {
-    update_refs(list);
-    subtract_refs(list);
-    release_unreachable(list, lock)
+///
+/// Only `candidates` are considered. References from other objects are
+/// treated as references from outside the list.
+///
+/// `lock` is called to block changes to the linked list before each scan.
+/// The list is scanned again after running finalizers, since they might
+/// resurrect objects, up to `policy.max_passes` times.
+///
+/// `on_release` is called for each unreachable object before dropping.
+/// It must not access the linked list.
+///
+/// `scanned` in the result is the number of objects considered by the first
+/// pass.
+pub(crate) fn collect_list<L: Linked, K>(
+    list: &L,
+    candidates: Candidates,
+    policy: &CollectionPolicy,
+    ephemerons: &[Rc<dyn EphemeronEntries>],
+    mut lock: impl FnMut() -> K,
+    mut on_release: impl FnMut(ObjectId, &dyn CcDyn),
+) -> CollectionStats {
+    let mut passes = 0;
+    let mut scanned = 0;
+    loop {
+        passes += 1;
+        let lock = lock();
+        let (marked, count) = mark_phase(
+            list,
+            candidates,
+            policy.drop_order,
+            ephemerons,
+            &mut on_release,
+        );
+        if passes == 1 {
+            scanned = count;
+        }
+        let needs_rescan = marked.needs_rescan();
+        let count = sweep_phase(marked, lock);
+        if !needs_rescan || passes >= policy.max_passes {
+            return CollectionStats {
+                collected: count,
+                scanned,
+            };
+        }
+    }
+}
+
+/// Unreachable objects found by `mark_phase`, to be handled by `sweep_phase`.
+///
+/// Extra references to the `CcBox<T>`s are kept, so metadata like
+/// `ref_count` stays available after the linked list changes.
+pub(crate) enum MarkedSet {
+    /// Objects to finalize. Finalizers might resurrect objects, so the list
+    /// needs to be scanned again afterwards.
+    Finalize(Vec<Box<dyn GcClone>>),
+
+    /// Objects to drop, and values removed from ephemerons since their
+    /// keys are dropped.
+    Drop(Vec<Box<dyn GcClone>>, Vec<Box<dyn Any>>),
 }
 
-/// Visit the linked list.
+impl MarkedSet {
+    /// Whether the list needs to be scanned again after `sweep_phase`.
+    pub(crate) fn needs_rescan(&self) -> bool {
+        matches!(self, MarkedSet::Finalize(_))
+    }
+}
+
+/// Find objects in the linked list whose references all come from
+/// themselves, to be dropped by `sweep_phase`.
+pub(crate) fn mark_self_referred<L: Linked>(list: &L) -> (MarkedSet, usize) {
+    let mut to_drop: Vec<Box<dyn GcClone>> = Vec::new();
+    let mut count = 0;
+    visit_list(list, |header| {
+        count += 1;
+        let value = header.value();
+        if value.gc_needs_finalize() {
+            return;
+        }
+        let id = ObjectId::from_header(header);
+        let mut self_refs = 0;
+        let mut visit = |ptr: *const ()| {
+            if ObjectId::from_ptr(ptr) == id {
+                self_refs += 1;
+            }
+        };
+        value.gc_traverse(&mut Tracer::with_mode(&mut visit, TracerMode::Inspect));
+        if self_refs > 0 && self_refs == value.gc_ref_count() {
+            to_drop.push(value.gc_clone());
+        }
+    });
+    debug::log(|| {
+        (
+            "collect",
+            format!("{} self-referred objects", to_drop.len()),
+        )
+    });
+    (MarkedSet::Drop(to_drop, Vec::new()), count)
+}
+
+/// Scan the specified linked list. Count objects that `collect_list` would
+/// release, without releasing them.
+pub(crate) fn count_unreachable_list<L: Linked>(
+    list: &L,
+    candidates: Candidates,
+    ephemerons: &[Rc<dyn EphemeronEntries>],
+) -> usize {
+    update_refs(list, candidates);
+    subtract_refs(&Scope::List(list), TracerMode::DryRun);
+    subtract_ephemeron_refs::<L>(ephemerons, TracerMode::DryRun);
+    mark_reachable(&Scope::List(list), ephemerons, TracerMode::DryRun);
+    let mut count = 0;
+    visit_list(list, |header| {
+        if is_unreachable(header) {
+            count += 1;
+        }
+    });
+    restore_prev(list);
+    count
+}
+
+/// Find objects in the linked list that are referred from outside the
+/// list, without changing anything.
+pub(crate) fn find_roots_list<L: Linked>(list: &L) -> Vec<ObjectId> {
+    update_refs(list, Candidates::ALL);
+    subtract_refs(&Scope::List(list), TracerMode::Inspect);
+    let mut roots = Vec::new();
+    visit_list(list, |header| {
+        if is_collecting(header) && !is_unreachable(header) {
+            roots.push(ObjectId::from_header(header));
+        }
+    });
+    restore_prev(list);
+    roots
+}
+
+/// Check that `prev` and `next` pointers of the linked list agree, and
+/// tracked objects are alive. Return the number of problems found.
+pub(crate) fn check_list<L: Linked>(list: &L) -> usize {
+    let mut problems = 0;
+    let mut report = |message: String| {
+        debug::log(|| ("check_list", message.clone()));
+        problems += 1;
+    };
+    let mut prev: *const L = list;
+    visit_links(list, |header| {
+        if header.is_marker() {
+            prev = header;
+            return;
+        }
+        if header.prev() != prev {
+            report(format!("{} has inconsistent prev", debug_name(header)));
+        }
+        if header.next().is_null() {
+            report(format!("{} is unlinked", debug_name(header)));
+        }
+        if header.value().gc_ref_count() == 0 {
+            report(format!("{} has no references", debug_name(header)));
+        }
+        prev = header;
+    });
+    if list.prev() != prev {
+        report("list head has inconsistent prev".to_string());
+    }
+    problems
+}
+
+/// Count objects in the linked list by type name, most common types first.
+pub(crate) fn count_tracked_by_type<L: Linked>(list: &L) -> Vec<(String, usize)> {
+    let mut by_type: HashMap<&'static str, usize> = HashMap::new();
+    visit_list(list, |header| {
+        *by_type.entry(header.value().gc_type_name()).or_insert(0) += 1;
+    });
+    let mut result: Vec<(String, usize)> = by_type
+        .into_iter()
+        .map(|(name, count)| (name.to_string(), count))
+        .collect();
+    result.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    result
+}
+
+/// Sum the sizes of objects in the linked list, including their headers.
+pub(crate) fn live_bytes_list<L: Linked>(list: &L) -> usize {
+    let mut total = 0;
+    visit_list(list, |header| {
+        total += mem::size_of::<L>() + header.value().gc_size();
+    });
+    total
+}
+
+/// Reset the `is_collecting` flag even if a `Drop` implementation panics.
+struct ResetOnDrop<'a>(&'a Cell<bool>);
+
+impl Drop for ResetOnDrop<'_> {
+    fn drop(&mut self) {
+        self.0.set(false);
+    }
+}
+
+/// Visit objects in the linked list.
 pub(crate) fn visit_list<'a, L: Linked>(list: &'a L, mut func: impl FnMut(&'a L)) {
+    visit_links(list, |header| {
+        if !header.is_marker() {
+            func(header)
+        }
+    });
+}
+
+/// Visit the linked list, including markers.
+fn visit_links<'a, L: Linked>(list: &'a L, mut func: impl FnMut(&'a L)) {
     // Skip the first dummy entry.
     let mut ptr = list.next();
     while ptr as *const _ != list as *const _ {
@@ -296,10 +1852,21 @@ fn unmask_ptr<T>(ptr: *const T) -> *const T {
     ((ptr as usize) & PTR_MASK) as *const T
 }
 
-/// Temporarily use `GcHeader.prev` as `gc_ref_count`.
+/// Temporarily use `GcHeader.prev` as `gc_ref_count`. Return the number of
+/// candidates.
 /// Idea comes from https://bugs.python.org/issue33597.
-fn update_refs<L: Linked>(list: &L) {
+fn update_refs<L: Linked>(list: &L, candidates: Candidates) -> usize {
+    let mut index = 0;
+    let mut count = 0;
     visit_list(list, |header| {
+        // Objects that are not candidates are not collected. Not marking them
+        // as COLLECTING also skips them in the following steps.
+        let is_candidate = candidates.contains(index, header);
+        index += 1;
+        if !is_candidate {
+            return;
+        }
+        count += 1;
         let ref_count = header.value().gc_ref_count();
         // It's possible that the ref_count becomes 0 in a multi-thread context:
         //  thread 1> drop()
@@ -315,6 +1882,114 @@ fn update_refs<L: Linked>(list: &L) {
             debug_assert!(header.prev() as usize & PREV_MASK_COLLECTING == 0);
         }
     });
+    count
+}
+
+/// Objects flagged by `take_step`.
+struct Step<'a, L> {
+    /// Flagged objects, with their original `prev`.
+    objects: Vec<(&'a L, *const L)>,
+
+    /// The last object taken from the list. The next step continues after it.
+    end: &'a L,
+
+    /// Number of objects scanned.
+    scanned: usize,
+
+    /// Whether the end of the list was reached.
+    complete: bool,
+}
+
+/// Flag objects for `collect_cycles_step`, like `update_refs` does: objects
+/// of `generation` after `marker`, and the objects they refer to, until
+/// `budget` objects were scanned. The objects referred by each taken object
+/// are limited to `budget`.
+fn take_step<'a, L: Linked>(
+    list: &'a L,
+    marker: &'a L,
+    generation: u8,
+    budget: usize,
+) -> Step<'a, L> {
+    fn flag<'a, L: Linked>(header: &'a L, generation: u8, objects: &mut Vec<(&'a L, *const L)>) {
+        if header.is_marker() || is_collecting(header) || header.generation() != generation {
+            return;
+        }
+        // See update_refs about ref_count being 0.
+        let ref_count = header.value().gc_ref_count();
+        if ref_count > 0 {
+            objects.push((header, header.prev()));
+            let shifted = (ref_count << PREV_SHIFT) | PREV_MASK_COLLECTING;
+            header.set_prev(shifted as _);
+        }
+    }
+
+    let budget = budget.max(1);
+    let mut step = Step {
+        objects: Vec::new(),
+        end: marker,
+        scanned: 0,
+        complete: false,
+    };
+    let mut ptr = marker.next();
+    while step.scanned < budget && !std::ptr::eq(ptr, list) {
+        // The linked list is maintained so the pointer is valid.
+        let header: &'a L = unsafe { &*ptr };
+        ptr = header.next();
+        step.end = header;
+        step.scanned += 1;
+
+        // Take referred objects too, so cycles through `header` are not
+        // split between steps.
+        let first = step.objects.len();
+        flag(header, generation, &mut step.objects);
+        let limit = first.saturating_add(budget);
+        let mut index = first;
+        while index < step.objects.len() {
+            let (header, _) = step.objects[index];
+            index += 1;
+            let objects = &mut step.objects;
+            let mut visit = |ptr: *const ()| {
+                if objects.len() < limit {
+                    // safety: The tracer only visits headers of type L.
+                    flag(unsafe { &*(ptr as *const L) }, generation, objects);
+                }
+            };
+            header
+                .value()
+                .gc_traverse(&mut collector_tracer::<L>(&mut visit, TracerMode::Collect));
+        }
+        step.scanned += step.objects.len().saturating_sub(first + 1);
+    }
+    step.complete = std::ptr::eq(ptr, list);
+    step
+}
+
+/// Nesting of `Tracer::trace_nested` followed within a single object when
+/// counting references. Values nested deeper are not traced, so the objects
+/// they refer to stay alive and cycles going through them are not collected.
+const TRACE_DEPTH_LIMIT: usize = 1000;
+
+/// Tracer used by `subtract_refs` and `mark_reachable`. Both passes must
+/// skip the same edges, so they share the depth limit. Only objects with
+/// headers of type `L` are visited.
+fn collector_tracer<L: Linked>(visit: &mut dyn FnMut(*const ()), mode: TracerMode) -> Tracer<'_> {
+    Tracer::with_mode(visit, mode)
+        .with_depth_limit(TRACE_DEPTH_LIMIT)
+        .with_header_type::<L>()
+}
+
+/// Subtract a reference visited from a collecting object.
+fn subtract_ref<L: Linked>(header: *const ()) {
+    // safety: The type is known to be GcHeader.
+    let header = unsafe { &*(header as *const L) };
+    if is_collecting(header) {
+        debug_assert!(
+            !is_unreachable(header),
+            "bug: object {} becomes unreachable while trying to dec_ref (is Trace impl correct?)",
+            debug_name(header)
+        );
+        edit_gc_ref_count(header, -1);
+    }
 }
 
 /// Subtract ref counts in `GcHeader.prev` by calling the non-recursive
@@ -323,30 +1998,63 @@ fn update_refs<L: Linked>(list: &L) {
 /// After this, potential unreachable objects will have ref count down
 /// to 0. If vertexes in a connected component _all_ have ref count 0,
 /// they are unreachable and can be released.
-fn subtract_refs<L: Linked>(list: &L) {
-    let mut tracer = |header: *const ()| {
-        // safety: The type is known to be GcHeader.
-        let header = unsafe { &*(header as *const L) };
-        if is_collecting(header) {
-            debug_assert!(
-                !is_unreachable(header),
-                "bug: object {} becomes unreachable while trying to dec_ref (is Trace impl correct?)",
-                debug_name(header)
-            );
-            edit_gc_ref_count(header, -1);
+fn subtract_refs<L: Linked>(scope: &Scope<L>, mode: TracerMode) {
+    let mut tracer = subtract_ref::<L>;
+    scope.visit(|header| {
+        // References from objects that are not collected are external.
+        if !is_collecting(header) {
+            return;
         }
-    };
-    visit_list(list, |header| {
         set_visited(header);
-        header.value().gc_traverse(&mut tracer);
+        header
+            .value()
+            .gc_traverse(&mut collector_tracer::<L>(&mut tracer, mode));
     });
 }
 
+/// Subtract references from ephemeron values, like references between
+/// collecting objects. `mark_reachable` revives the values of reachable keys.
+fn subtract_ephemeron_refs<L: Linked>(ephemerons: &[Rc<dyn EphemeronEntries>], mode: TracerMode) {
+    let mut tracer = subtract_ref::<L>;
+    for table in ephemerons {
+        table.visit(&mut |_, value| value.trace(&mut collector_tracer::<L>(&mut tracer, mode)));
+    }
+}
+
+/// Whether the key of an ephemeron entry is reachable, according to the
+/// COLLECTING flags set by `mark_reachable`. Objects that are not collected
+/// are reachable.
+fn is_key_reachable<L: Linked>(key: EphemeronKey) -> bool {
+    match key {
+        EphemeronKey::Dropped => false,
+        EphemeronKey::Untracked => true,
+        // safety: Keys are `Cc<T>` of `ObjectSpace`s, which only pass
+        // ephemerons if `L` is `GcHeader`.
+        EphemeronKey::Tracked(header) => !is_collecting(unsafe { &*(header as *const L) }),
+    }
+}
+
 /// Mark objects as reachable recursively. So ref count 0 means unreachable
 /// values. This also removes the COLLECTING flag for reachable objects so
 /// unreachable objects all have the COLLECTING flag set.
-fn mark_reachable<L: Linked>(list: &L) {
-    fn revive<L: Linked>(header: *const ()) {
+///
+/// Values of `ephemerons` are reachable if their keys are.
+fn mark_reachable<L: Linked>(
+    scope: &Scope<L>,
+    ephemerons: &[Rc<dyn EphemeronEntries>],
+    mode: TracerMode,
+) {
+    // Traversing a long chain of objects recursively can overflow the stack.
+    // Objects deeper than this are revived, but their referents are visited
+    // later from `pending`.
+    const MAX_DEPTH: usize = 1000;
+
+    fn revive<L: Linked>(
+        header: *const (),
+        mode: TracerMode,
+        depth: usize,
+        pending: &mut Vec<*const L>,
+    ) {
         // safety: The type is known to be GcHeader.
         let header = unsafe { &*(header as *const L) };
         // hasn't visited?
@@ -355,28 +2063,154 @@ fn mark_reachable<L: Linked>(list: &L) {
             if is_unreachable(header) {
                 edit_gc_ref_count(header, 1); // revive
             }
-            header.value().gc_traverse(&mut revive::<L>); // revive recursively
+            if depth >= MAX_DEPTH {
+                pending.push(header);
+            } else {
+                traverse(header, mode, depth + 1, pending); // revive recursively
+            }
         }
     }
-    visit_list(list, |header| {
+    fn traverse<L: Linked>(
+        header: &L,
+        mode: TracerMode,
+        depth: usize,
+        pending: &mut Vec<*const L>,
+    ) {
+        let mut visit = |header| revive::<L>(header, mode, depth, pending);
+        header
+            .value()
+            .gc_traverse(&mut collector_tracer::<L>(&mut visit, mode));
+    }
+    fn drain<L: Linked>(mode: TracerMode, pending: &mut Vec<*const L>) {
+        while let Some(header) = pending.pop() {
+            // safety: `pending` only contains headers in the list.
+            traverse(unsafe { &*header }, mode, 0, pending);
+        }
+    }
+    let mut pending = Vec::new();
+    scope.visit(|header| {
         if is_collecting(header) && !is_unreachable(header) {
             unset_collecting(header);
-            header.value().gc_traverse(&mut revive::<L>)
+            traverse(header, mode, 0, &mut pending);
+            drain(mode, &mut pending);
         }
     });
+
+    // Reviving a value can make more keys reachable. Repeat until nothing
+    // changes.
+    let mut revived: Vec<Vec<bool>> = vec![Vec::new(); ephemerons.len()];
+    loop {
+        let mut changed = false;
+        for (table, revived) in ephemerons.iter().zip(revived.iter_mut()) {
+            let mut index = 0;
+            table.visit(&mut |key, value| {
+                if revived.len() <= index {
+                    revived.push(false);
+                }
+                if !revived[index] && is_key_reachable::<L>(key) {
+                    revived[index] = true;
+                    changed = true;
+                    let mut visit = |header| revive::<L>(header, mode, 0, &mut pending);
+                    value.trace(&mut collector_tracer::<L>(&mut visit, mode));
+                    drain(mode, &mut pending);
+                }
+                index += 1;
+            });
+        }
+        if !changed {
+            break;
+        }
+    }
 }
 
-/// Release unreachable objects in the linked list.
-fn release_unreachable<L: Linked, K>(list: &L, lock: K) -> usize {
+/// Find unreachable objects among `candidates` in the linked list. Also
+/// return the number of candidates.
+///
+/// The linked list must not change until this returns. It is left intact.
+/// `on_release` is called for each object that is going to be dropped.
+pub(crate) fn mark_phase<L: Linked>(
+    list: &L,
+    candidates: Candidates,
+    drop_order: DropOrder,
+    ephemerons: &[Rc<dyn EphemeronEntries>],
+    on_release: impl FnMut(ObjectId, &dyn CcDyn),
+) -> (MarkedSet, usize) {
+    let count = update_refs(list, candidates);
+    let marked = mark_scope(Scope::List(list), drop_order, ephemerons, on_release);
+    (marked, count)
+}
+
+/// Objects considered by `mark_scope`. They have the COLLECTING flag set,
+/// with their reference counts stored in `prev`.
+pub(crate) enum Scope<'a, L> {
+    /// Objects in the list flagged by `update_refs`.
+    List(&'a L),
+
+    /// Objects flagged by `take_step`, with their original `prev`.
+    Objects(&'a [(&'a L, *const L)]),
+}
+
+impl<'a, L: Linked> Scope<'a, L> {
+    fn visit(&self, mut func: impl FnMut(&'a L)) {
+        match *self {
+            Scope::List(list) => visit_list(list, func),
+            Scope::Objects(objects) => objects.iter().for_each(|&(header, _)| func(header)),
+        }
+    }
+
+    /// Restore `GcHeader.prev` as a pointer used in the linked list.
+    fn restore_prev(&self) {
+        match *self {
+            Scope::List(list) => restore_prev(list),
+            Scope::Objects(objects) => {
+                for &(header, prev) in objects {
+                    header.set_prev(prev);
+                }
+            }
+        }
+    }
+}
+
+/// Find unreachable objects in `scope`.
+fn mark_scope<L: Linked>(
+    scope: Scope<L>,
+    drop_order: DropOrder,
+    ephemerons: &[Rc<dyn EphemeronEntries>],
+    mut on_release: impl FnMut(ObjectId, &dyn CcDyn),
+) -> MarkedSet {
+    subtract_refs(&scope, TracerMode::Collect);
+    subtract_ephemeron_refs::<L>(ephemerons, TracerMode::Collect);
+
     // Mark reachable objects. For example, A refers B. A's gc_ref_count
     // is 1 while B's gc_ref_count is 0. In this case B should be revived
     // by A's non-zero gc_ref_count.
-    mark_reachable(list);
+    mark_reachable(&scope, ephemerons, TracerMode::Collect);
+
+    // Run finalizers while the unreachable objects are still intact.
+    // Finalizers might resurrect objects so what is unreachable needs to be
+    // recalculated afterwards. Objects are only finalized once so this does
+    // not loop forever.
+    let mut to_finalize: Vec<Box<dyn GcClone>> = Vec::new();
+    scope.visit(|header| {
+        if is_unreachable(header) && header.value().gc_needs_finalize() {
+            to_finalize.push(header.value().gc_clone());
+        }
+    });
+    if !to_finalize.is_empty() {
+        debug::log(|| {
+            (
+                "collect",
+                format!("{} objects to finalize", to_finalize.len()),
+            )
+        });
+        scope.restore_prev();
+        return MarkedSet::Finalize(to_finalize);
+    }
 
     let mut count = 0;
 
     // Count unreachable objects. This is an optimization to avoid realloc.
-    visit_list(list, |header| {
+    scope.visit(|header| {
         if is_unreachable(header) {
             count += 1;
         }
@@ -386,27 +2220,70 @@ fn release_unreachable<L: Linked, K>(list: &L, lock: K) -> usize {
 
     // Build a list of what to drop. The collecting steps change the linked list
     // so `visit_list` cannot be used.
-    //
-    // Here we keep extra references to the `CcBox<T>` to keep them alive. This
-    // ensures metadata fields like `ref_count` is available.
     let mut to_drop: Vec<Box<dyn GcClone>> = Vec::with_capacity(count);
-    visit_list(list, |header| {
+    scope.visit(|header| {
         if is_unreachable(header) {
+            on_release(ObjectId::from_header(header), header.value());
             to_drop.push(header.value().gc_clone());
         }
     });
 
+    if drop_order == DropOrder::TopologicalChildrenFirst {
+        let mut nodes = Vec::with_capacity(count);
+        let mut edges = Vec::with_capacity(count);
+        scope.visit(|header| {
+            if is_unreachable(header) {
+                let mut referents = Vec::new();
+                let mut visit = |ptr: *const ()| referents.push(ObjectId::from_ptr(ptr));
+                header
+                    .value()
+                    .gc_traverse(&mut Tracer::with_mode(&mut visit, TracerMode::Inspect));
+                nodes.push(ObjectId::from_header(header));
+                edges.push(referents);
+            }
+        });
+        let mut slots: Vec<Option<Box<dyn GcClone>>> = to_drop.into_iter().map(Some).collect();
+        to_drop = graph::children_first(&nodes, &edges)
+            .into_iter()
+            .filter_map(|i| slots[i].take())
+            .collect();
+    }
+
+    // Values of unreachable keys were not revived. They might refer to the
+    // objects to drop, so they are dropped together.
+    let pruned = ephemerons
+        .iter()
+        .map(|table| table.remove_dead(&mut |key| !is_key_reachable::<L>(key)))
+        .collect();
+
     // Restore "prev" so deleting nodes from the linked list can work.
-    restore_prev(list);
+    scope.restore_prev();
+
+    MarkedSet::Drop(to_drop, pruned)
+}
 
+/// Finalize or drop objects found by `mark_phase`. Return the number of
+/// dropped objects.
+///
+/// `lock` is released first, so the dropped objects can change reference
+/// counts and the linked list.
+pub(crate) fn sweep_phase<K>(marked: MarkedSet, lock: K) -> usize {
     // Drop the lock so deref() can work, reference counts and the linked list
     // can be changed. This is needed because gc_drop_t might change the ref
     // counts. This is okay for linked list because objects has been cloned
-    // to a separate `to_drop` list and the original linked list is no longer
-    // used.
+    // to a separate list and the original linked list is no longer used.
     drop(lock);
-    // Drop the reference to the list so we don't reuse it.
-    // drop(list);
+
+    let (to_drop, pruned) = match marked {
+        MarkedSet::Finalize(to_finalize) => {
+            for value in to_finalize.iter() {
+                value.gc_finalize();
+            }
+            return 0;
+        }
+        MarkedSet::Drop(to_drop, pruned) => (to_drop, pruned),
+    };
+    let count = to_drop.len();
 
     #[cfg(feature = "debug")]
     {
@@ -416,13 +2293,34 @@ fn release_unreachable<L: Linked, K>(list: &L, lock: K) -> usize {
     // Drop `T` without releasing memory of `CcBox<T>`. This might trigger some
     // recursive drops of other `Cc<T>`. `CcBox<T>` need to stay alive so
     // `Cc<T>::drop` can read the ref count metadata.
-    for value in to_drop.iter() {
-        value.gc_drop_t();
+    //
+    // If a `Drop` implementation panics, the `CcBox<T>` is leaked since its
+    // ref count can no longer be trusted. Other objects are still dropped,
+    // then the first panic is resumed.
+    let mut panics = Vec::new();
+
+    // Values removed from ephemerons go first, since they might refer to
+    // the objects being dropped.
+    for values in pruned {
+        if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| drop(values))) {
+            panics.push(payload);
+        }
+    }
+
+    let mut dropped = Vec::with_capacity(to_drop.len());
+    for value in to_drop {
+        match panic::catch_unwind(AssertUnwindSafe(|| value.gc_drop_t())) {
+            Ok(()) => dropped.push(value),
+            Err(payload) => {
+                panics.push(payload);
+                mem::forget(value);
+            }
+        }
     }
 
     // At this point the only references to the `CcBox<T>`s are inside the
-    // `to_drop` list. Dropping `to_drop` would release the memory.
-    for value in to_drop.iter() {
+    // `dropped` list. Dropping `dropped` would release the memory.
+    for value in dropped.iter() {
         let ref_count = value.gc_ref_count();
         assert_eq!(
             ref_count, 1,
@@ -438,13 +2336,18 @@ fn release_unreachable<L: Linked, K>(list: &L, lock: K) -> usize {
         crate::debug::GC_DROPPING.with(|d| d.set(false));
     }
 
+    if !panics.is_empty() {
+        drop(dropped);
+        panic::resume_unwind(panics.swap_remove(0));
+    }
+
     count
 }
 
 /// Restore `GcHeader.prev` as a pointer used in the linked list.
 fn restore_prev<L: Linked>(list: &L) {
     let mut prev = list;
-    visit_list(list, |header| {
+    visit_links(list, |header| {
         header.set_prev(prev);
         prev = header;
     });
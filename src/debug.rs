@@ -24,6 +24,15 @@ pub(crate) fn capture_log(mut func: impl FnMut()) -> String {
     })
 }
 
+/// Run `func` without recording debug logs.
+#[cfg(debug_assertions)]
+pub(crate) fn without_log<R>(func: impl FnOnce() -> R) -> R {
+    let enabled = ENABLED.with(|e| e.replace(false));
+    let result = func();
+    ENABLED.with(|e| e.set(enabled));
+    result
+}
+
 pub(crate) fn log<S1: ToString, S2: ToString>(func: impl Fn() -> (S1, S2)) {
     let enabled = ENABLED.with(|e| e.get());
     if enabled {
@@ -38,7 +47,7 @@ pub(crate) fn log<S1: ToString, S2: ToString>(func: impl Fn() -> (S1, S2)) {
                     log.push_str(", ");
                     log.push_str(&message);
                 } else {
-                    log.push_str("\n");
+                    log.push('\n');
                     log.push_str(&name);
                     log.push_str(": ");
                     log.push_str(&message);
@@ -50,6 +59,6 @@ pub(crate) fn log<S1: ToString, S2: ToString>(func: impl Fn() -> (S1, S2)) {
         let (name, message) = func();
         let t = std::thread::current().id();
         let name = format!("{:?}-{}", t, name.to_string());
-        eprintln!("debug::log {} {}", name.to_string(), message.to_string());
+        eprintln!("debug::log {} {}", name, message.to_string());
     }
 }
@@ -0,0 +1,226 @@
+//! `Ephemeron<K, V>`: association table that does not keep its keys alive.
+
+use crate::Cc;
+use crate::Trace;
+use crate::Tracer;
+use crate::Weak;
+use std::any::Any;
+use std::cell::{Ref, RefCell};
+use std::collections::HashMap;
+use std::rc;
+use std::rc::Rc;
+
+/// Maps `Cc<K>` keys to `V` values without keeping the keys alive.
+///
+/// Keys are stored as [`Weak<K>`](type.Weak.html). Once a key is dropped
+/// its entry becomes dead. Dead entries are invisible to lookups.
+///
+/// The collector treats a value as reachable only if its key is reachable
+/// through other references. So a value referring to its own key does not
+/// keep the key alive. [`collect_cycles`](struct.ObjectSpace.html#method.collect_cycles)
+/// removes the entries of the keys it collects, and the entries of keys
+/// dropped earlier. [`prune`](#method.prune), or inserting new entries,
+/// removes dead entries without collecting.
+///
+/// ```
+/// use jrsonnet_gcmodule::{Cc, Ephemeron};
+///
+/// let cache = Ephemeron::new();
+/// let key = Cc::new(1);
+/// cache.insert(&key, "one");
+/// assert_eq!(cache.get(&key).as_deref(), Some(&"one"));
+/// drop(key);
+/// assert!(cache.is_empty());
+/// ```
+pub struct Ephemeron<K: ?Sized, V> {
+    // Shared with `TABLES`, so the collector can find the entries.
+    entries: Rc<Entries<K, V>>,
+}
+
+struct Entries<K: ?Sized, V>(RefCell<HashMap<*const (), (Weak<K>, V)>>);
+
+impl<K: ?Sized + 'static, V: Trace> Default for Ephemeron<K, V> {
+    fn default() -> Self {
+        let entries = Rc::new(Entries(RefCell::new(HashMap::new())));
+        let table: Rc<dyn EphemeronEntries> = entries.clone();
+        TABLES.with(|tables| {
+            let mut tables = tables.borrow_mut();
+            tables.retain(|table| table.strong_count() > 0);
+            tables.push(Rc::downgrade(&table));
+        });
+        Self { entries }
+    }
+}
+
+impl<K: ?Sized + 'static, V: Trace> Ephemeron<K, V> {
+    /// Constructs an empty `Ephemeron`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<K: ?Sized, V> Ephemeron<K, V> {
+    /// Insert a value for `key`. Returns the previous value of a live entry.
+    ///
+    /// Dead entries are pruned first.
+    pub fn insert(&self, key: &Cc<K>, value: V) -> Option<V> {
+        // Drop values outside the borrow, since their `Drop` might access
+        // this table.
+        let pruned = self.take_dead();
+        let old = self
+            .entries
+            .0
+            .borrow_mut()
+            .insert(key_of(key), (key.downgrade(), value));
+        drop(pruned);
+        old.map(|(_, value)| value)
+    }
+
+    /// Get the value for `key`.
+    pub fn get(&self, key: &Cc<K>) -> Option<Ref<'_, V>> {
+        Ref::filter_map(self.entries.0.borrow(), |entries| {
+            entries
+                .get(&key_of(key))
+                .filter(|(weak, _)| weak.is_alive())
+                .map(|(_, value)| value)
+        })
+        .ok()
+    }
+
+    /// Remove the entry for `key`. Returns its value.
+    pub fn remove(&self, key: &Cc<K>) -> Option<V> {
+        let removed = self.entries.0.borrow_mut().remove(&key_of(key));
+        removed
+            .filter(|(weak, _)| weak.is_alive())
+            .map(|(_, value)| value)
+    }
+
+    /// Count live entries.
+    pub fn len(&self) -> usize {
+        let entries = self.entries.0.borrow();
+        entries.values().filter(|(weak, _)| weak.is_alive()).count()
+    }
+
+    /// Whether there are no live entries.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Remove entries whose keys have been dropped. Returns the number of
+    /// removed entries.
+    pub fn prune(&self) -> usize {
+        self.take_dead().len()
+    }
+
+    fn take_dead(&self) -> Vec<V> {
+        let mut entries = self.entries.0.borrow_mut();
+        let dead: Vec<*const ()> = entries
+            .iter()
+            .filter(|(_, (weak, _))| !weak.is_alive())
+            .map(|(&ptr, _)| ptr)
+            .collect();
+        dead.into_iter()
+            .filter_map(|ptr| entries.remove(&ptr))
+            .map(|(_, value)| value)
+            .collect()
+    }
+}
+
+impl<K: ?Sized + 'static, V: Trace> Trace for Ephemeron<K, V> {
+    fn trace(&self, tracer: &mut Tracer) {
+        // Keys are weak references. Values are visited by the collector
+        // through `TABLES`, depending on whether their keys are reachable.
+        let _ = tracer;
+    }
+
+    #[inline]
+    fn is_type_tracked() -> bool {
+        false
+    }
+}
+
+/// Key of an ephemeron entry, as seen by the collector.
+#[derive(Clone, Copy)]
+pub(crate) enum EphemeronKey {
+    /// The key was dropped. The entry is dead.
+    Dropped,
+    /// The key is not tracked, so it is not collected by the collector.
+    Untracked,
+    /// The key is tracked. Contains the address visited by `Tracer`.
+    Tracked(*const ()),
+}
+
+/// Entries of an `Ephemeron`, with the types erased for the collector.
+pub(crate) trait EphemeronEntries {
+    /// Whether the entries can be visited and removed. The collector skips
+    /// tables that are borrowed, which keeps their values alive.
+    fn is_available(&self) -> bool;
+
+    /// Call `f` with each entry, in the same order each time, as long as
+    /// the table is not changed.
+    fn visit(&self, f: &mut dyn FnMut(EphemeronKey, &dyn Trace));
+
+    /// Remove entries whose keys are dead according to `is_dead`. Return
+    /// the removed values, so the caller decides when to drop them.
+    fn remove_dead(&self, is_dead: &mut dyn FnMut(EphemeronKey) -> bool) -> Box<dyn Any>;
+}
+
+impl<K: ?Sized + 'static, V: Trace> EphemeronEntries for Entries<K, V> {
+    fn is_available(&self) -> bool {
+        self.0.try_borrow_mut().is_ok()
+    }
+
+    fn visit(&self, f: &mut dyn FnMut(EphemeronKey, &dyn Trace)) {
+        for (weak, value) in self.0.borrow().values() {
+            f(key_state(weak), value);
+        }
+    }
+
+    fn remove_dead(&self, is_dead: &mut dyn FnMut(EphemeronKey) -> bool) -> Box<dyn Any> {
+        let mut entries = self.0.borrow_mut();
+        let dead: Vec<*const ()> = entries
+            .iter()
+            .filter(|(_, (weak, _))| is_dead(key_state(weak)))
+            .map(|(&ptr, _)| ptr)
+            .collect();
+        let values: Vec<V> = dead
+            .into_iter()
+            .filter_map(|ptr| entries.remove(&ptr))
+            .map(|(_, value)| value)
+            .collect();
+        Box::new(values)
+    }
+}
+
+fn key_state<K: ?Sized>(weak: &Weak<K>) -> EphemeronKey {
+    if !weak.is_alive() {
+        EphemeronKey::Dropped
+    } else {
+        match weak.tracked_header() {
+            Some(header) => EphemeronKey::Tracked(header),
+            None => EphemeronKey::Untracked,
+        }
+    }
+}
+
+// Ephemerons created in this thread. Their keys are `Cc<T>`, which cannot
+// be sent to other threads.
+thread_local!(static TABLES: RefCell<Vec<rc::Weak<dyn EphemeronEntries>>> = Default::default());
+
+/// Ephemerons of this thread that the collector can visit.
+pub(crate) fn thread_tables() -> Vec<Rc<dyn EphemeronEntries>> {
+    TABLES
+        .try_with(|tables| {
+            tables
+                .borrow()
+                .iter()
+                .filter_map(|table| table.upgrade())
+                .filter(|table| table.is_available())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn key_of<K: ?Sized>(key: &Cc<K>) -> *const () {
+    key.inner() as *const _ as *const ()
+}
@@ -0,0 +1,307 @@
+// Read-only views of the object graph formed by tracked objects.
+//
+// Unlike `collect.rs`, nothing here touches `GcHeader.prev`, so the linked
+// list stays intact and no objects are dropped.
+
+use crate::cc::CcDyn;
+use crate::collect::visit_list;
+use crate::collect::Linked;
+use crate::Tracer;
+use crate::TracerMode;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io;
+use std::io::Write;
+
+/// Opaque handle to a tracked object.
+///
+/// The handle is derived from the address of the object's `GcHeader`. It is
+/// stable while the object is alive, and might be reused by a different
+/// object after the original one gets dropped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ObjectId(usize);
+
+impl ObjectId {
+    pub(crate) fn from_header<L>(header: &L) -> Self {
+        Self(header as *const L as usize)
+    }
+
+    /// From a header address reported to a [`Tracer`].
+    pub(crate) fn from_ptr(ptr: *const ()) -> Self {
+        Self(ptr as usize)
+    }
+}
+
+/// Snapshot of tracked objects and the edges between them.
+pub(crate) struct CycleGraph {
+    /// Tracked objects, in linked list order.
+    pub(crate) nodes: Vec<ObjectId>,
+
+    /// `edges[i]` lists indexes into `nodes` referred by `nodes[i]`.
+    /// References to objects outside the list are ignored.
+    pub(crate) edges: Vec<Vec<usize>>,
+}
+
+/// Build a [`CycleGraph`] by calling `gc_traverse` on every object in `list`.
+pub(crate) fn build_cycle_graph<L: Linked>(list: &L) -> CycleGraph {
+    let mut nodes = Vec::new();
+    let mut index = HashMap::new();
+    visit_list(list, |header| {
+        let id = ObjectId::from_header(header);
+        index.insert(id, nodes.len());
+        nodes.push(id);
+    });
+
+    let mut edges = Vec::with_capacity(nodes.len());
+    visit_list(list, |header| {
+        let mut referents = Vec::new();
+        let mut visit = |ptr: *const ()| {
+            if let Some(&i) = index.get(&ObjectId::from_ptr(ptr)) {
+                referents.push(i);
+            }
+        };
+        header
+            .value()
+            .gc_traverse(&mut Tracer::with_mode(&mut visit, TracerMode::Inspect));
+        edges.push(referents);
+    });
+
+    CycleGraph { nodes, edges }
+}
+
+impl CycleGraph {
+    /// Strongly connected components with more than one member, found by
+    /// Tarjan's algorithm. Self-referencing objects are not included.
+    pub(crate) fn strongly_connected_components(&self) -> Vec<Vec<ObjectId>> {
+        const UNVISITED: usize = usize::MAX;
+
+        let n = self.nodes.len();
+        let mut next_index = 0;
+        let mut indexes = vec![UNVISITED; n];
+        let mut low_links = vec![0; n];
+        let mut on_stack = vec![false; n];
+        let mut stack = Vec::new();
+        let mut result = Vec::new();
+
+        // Iterative to avoid overflowing the native stack on long chains.
+        // Each frame is (node, position of the next edge to visit).
+        let mut frames: Vec<(usize, usize)> = Vec::new();
+        for root in 0..n {
+            if indexes[root] != UNVISITED {
+                continue;
+            }
+            frames.push((root, 0));
+            while let Some(&mut (v, ref mut edge_pos)) = frames.last_mut() {
+                if *edge_pos == 0 && indexes[v] == UNVISITED {
+                    indexes[v] = next_index;
+                    low_links[v] = next_index;
+                    next_index += 1;
+                    stack.push(v);
+                    on_stack[v] = true;
+                }
+                if let Some(&w) = self.edges[v].get(*edge_pos) {
+                    *edge_pos += 1;
+                    if indexes[w] == UNVISITED {
+                        frames.push((w, 0));
+                    } else if on_stack[w] {
+                        low_links[v] = low_links[v].min(indexes[w]);
+                    }
+                    continue;
+                }
+                frames.pop();
+                if let Some(&(parent, _)) = frames.last() {
+                    low_links[parent] = low_links[parent].min(low_links[v]);
+                }
+                if low_links[v] == indexes[v] {
+                    let mut component = Vec::new();
+                    loop {
+                        let w = stack.pop().unwrap();
+                        on_stack[w] = false;
+                        component.push(self.nodes[w]);
+                        if w == v {
+                            break;
+                        }
+                    }
+                    if component.len() > 1 {
+                        result.push(component);
+                    }
+                }
+            }
+        }
+        result
+    }
+}
+
+/// Sizes of weakly connected components of `nodes`. `edges[i]` lists objects
+/// referred by `nodes[i]`. Edges to objects outside `nodes` are ignored.
+pub(crate) fn component_sizes(nodes: &[ObjectId], edges: &[Vec<ObjectId>]) -> Vec<usize> {
+    fn find(parents: &mut [usize], mut i: usize) -> usize {
+        while parents[i] != i {
+            parents[i] = parents[parents[i]];
+            i = parents[i];
+        }
+        i
+    }
+
+    let index: HashMap<ObjectId, usize> =
+        nodes.iter().enumerate().map(|(i, &id)| (id, i)).collect();
+    let mut parents: Vec<usize> = (0..nodes.len()).collect();
+    for (i, referents) in edges.iter().enumerate() {
+        for id in referents {
+            if let Some(&j) = index.get(id) {
+                let (a, b) = (find(&mut parents, i), find(&mut parents, j));
+                parents[a] = b;
+            }
+        }
+    }
+
+    let mut sizes = HashMap::new();
+    for i in 0..nodes.len() {
+        *sizes.entry(find(&mut parents, i)).or_insert(0) += 1;
+    }
+    sizes.into_values().collect()
+}
+
+/// Order `nodes` so each object comes before the objects referring to it.
+/// Objects in the same cycle are ordered arbitrarily. `edges[i]` lists
+/// objects referred by `nodes[i]`. Return indexes into `nodes`.
+pub(crate) fn children_first(nodes: &[ObjectId], edges: &[Vec<ObjectId>]) -> Vec<usize> {
+    let index: HashMap<ObjectId, usize> =
+        nodes.iter().enumerate().map(|(i, &id)| (id, i)).collect();
+    let mut visited = vec![false; nodes.len()];
+    let mut result = Vec::with_capacity(nodes.len());
+
+    // Iterative post-order walk. Each frame is (node, position of the next
+    // edge to visit).
+    let mut frames: Vec<(usize, usize)> = Vec::new();
+    for root in 0..nodes.len() {
+        if visited[root] {
+            continue;
+        }
+        visited[root] = true;
+        frames.push((root, 0));
+        while let Some(&mut (v, ref mut edge_pos)) = frames.last_mut() {
+            if let Some(id) = edges[v].get(*edge_pos) {
+                *edge_pos += 1;
+                if let Some(&w) = index.get(id) {
+                    if !visited[w] {
+                        visited[w] = true;
+                        frames.push((w, 0));
+                    }
+                }
+                continue;
+            }
+            frames.pop();
+            result.push(v);
+        }
+    }
+    result
+}
+
+/// Address of the `CcBox` of the object with the header at `header`, as
+/// reported by `dump_heap` and `to_dot`.
+fn box_address<L: Linked>(header: *const ()) -> usize {
+    // safety: Headers come from the list, or from a tracer restricted to `L`.
+    let value = unsafe { &*(header as *const L) }.value();
+    value as *const dyn CcDyn as *const () as usize
+}
+
+/// Tracer reporting headers of type `L`. Objects of other object space
+/// types cannot be found in the list.
+pub(crate) fn inspect_tracer<L: Linked>(visit: &mut dyn FnMut(*const ())) -> Tracer<'_> {
+    Tracer::with_mode(visit, TracerMode::Inspect).with_header_type::<L>()
+}
+
+/// Write tracked objects in `list` as a JSON array. Each object is described
+/// by the address of its `CcBox`, its type name, ref count, and the addresses
+/// of the `CcBox`es it refers to.
+pub(crate) fn dump_heap<L: Linked>(list: &L, writer: &mut dyn Write) -> io::Result<()> {
+    let mut result = Ok(());
+    let mut first = true;
+    writer.write_all(b"[")?;
+    visit_list(list, |header| {
+        if result.is_err() {
+            return;
+        }
+        let value = header.value();
+        let mut references = Vec::new();
+        let mut visit = |ptr: *const ()| references.push(box_address::<L>(ptr));
+        value.gc_traverse(&mut inspect_tracer::<L>(&mut visit));
+        let references: Vec<String> = references
+            .into_iter()
+            .map(|address| format!("\"{:#x}\"", address))
+            .collect();
+        result = write!(
+            writer,
+            "{}\n{{\"id\":\"{:#x}\",\"type\":\"{}\",\"ref_count\":{},\"references\":[{}]}}",
+            if first { "" } else { "," },
+            box_address::<L>(header as *const L as _),
+            JsonStr(value.gc_type_name()),
+            value.gc_ref_count(),
+            references.join(","),
+        );
+        first = false;
+    });
+    result?;
+    writer.write_all(b"\n]\n")
+}
+
+/// Describe tracked objects in `list` and the references between them in the
+/// Graphviz DOT language. Nodes are named by the addresses of `CcBox`es, like
+/// `dump_heap`, and labeled by type names.
+pub(crate) fn to_dot<L: Linked>(list: &L) -> String {
+    let mut out = String::from("digraph {\n");
+    visit_list(list, |header| {
+        let value = header.value();
+        let id = box_address::<L>(header as *const L as _);
+        let _ = writeln!(
+            out,
+            "  \"{:#x}\" [label=\"{}\\n{:#x}\"];",
+            id,
+            DotStr(value.gc_type_name()),
+            id
+        );
+        let mut references = Vec::new();
+        let mut visit = |ptr: *const ()| references.push(box_address::<L>(ptr));
+        value.gc_traverse(&mut inspect_tracer::<L>(&mut visit));
+        for reference in references {
+            let _ = writeln!(out, "  \"{:#x}\" -> \"{:#x}\";", id, reference);
+        }
+    });
+    out.push_str("}\n");
+    out
+}
+
+/// Formats a string as the content of a DOT string literal.
+struct DotStr<'a>(&'a str);
+
+impl std::fmt::Display for DotStr<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '"' => f.write_str("\\\"")?,
+                '\\' => f.write_str("\\\\")?,
+                '\n' => f.write_str("\\n")?,
+                c => write!(f, "{}", c)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Formats a string as the content of a JSON string literal.
+struct JsonStr<'a>(&'a str);
+
+impl std::fmt::Display for JsonStr<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '"' => f.write_str("\\\"")?,
+                '\\' => f.write_str("\\\\")?,
+                c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+                c => write!(f, "{}", c)?,
+            }
+        }
+        Ok(())
+    }
+}
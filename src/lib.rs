@@ -1,6 +1,7 @@
 #![deny(missing_docs)]
 #![cfg_attr(feature = "nightly", feature(coerce_unsized), feature(unsize))]
 #![cfg_attr(all(feature = "debug", feature = "nightly"), feature(specialization))]
+#![cfg_attr(feature = "allocator-api", feature(allocator_api))]
 
 //! Reference cycle garbage collection inspired by
 //! [cpython](https://github.com/python/cpython/).
@@ -79,6 +80,25 @@
 //! assert_eq!(space.count_tracked(), 0);
 //! ```
 //!
+//! ## Feature flags
+//!
+//! - `sync` (default): [`ThreadedObjectSpace`](struct.ThreadedObjectSpace.html)
+//!   and [`ThreadedCc`](type.ThreadedCc.html). This is the only feature that
+//!   depends on `parking_lot`. Without it, [`Cc`](type.Cc.html) and
+//!   [`ObjectSpace`](struct.ObjectSpace.html) only need `std`.
+//! - `derive` (default): `#[derive(Trace)]`.
+//! - `parking_lot`: `Trace` for `parking_lot` locks. Enabled by `sync`.
+//! - `nightly`: unsizing coercion like `Cc<T>` to `Cc<dyn Trace>`. Requires a
+//!   nightly compiler.
+//! - `debug`: log internal operations to stderr.
+//! - `tag`: a `u32` tag in every object, see
+//!   [`Cc::set_tag`](type.Cc.html#method.set_tag).
+//! - `testutil`: utilities for testing the collector.
+//! - `track-rc`: track `std::rc::Rc<T>` if `T` is tracked, so cycles through
+//!   uniquely owned `Rc`s can be collected. This makes such `Rc`s take the
+//!   slower tracked path of [`Cc`](type.Cc.html), even if they never form
+//!   cycles.
+//!
 //! ## Defining new types
 //!
 //! [`Cc<T>`](type.Cc.html) requires [`Trace`](trait.Trace.html) implemented
@@ -263,10 +283,15 @@ extern crate self as jrsonnet_gcmodule;
 
 mod cc;
 mod cc_impls;
+mod cell;
 mod collect;
 #[cfg(test)]
 mod debug;
+mod ephemeron;
+mod graph;
 mod ref_count;
+mod slice;
+mod string;
 #[cfg(feature = "sync")]
 mod sync;
 #[cfg(test)]
@@ -275,15 +300,27 @@ mod tests;
 pub mod testutil;
 mod trace;
 mod trace_impls;
+mod vec;
 
 pub use cc::{Cc, RawCc, RawWeak, Weak};
+pub use cell::{GcCell, TraceUnsafeCell};
 pub use collect::{
-    collect_thread_cycles, count_thread_tracked, with_thread_object_space, ObjectSpace,
+    collect_thread_cycles, count_thread_tracked, with_thread_object_space, CollectionEvent,
+    CollectionPolicy, CollectionReport, CollectionStats, DropOrder, GcListener, GenerationId,
+    ObjectSpace, StepResult,
 };
-pub use trace::{Trace, Tracer};
+pub use ephemeron::Ephemeron;
+pub use graph::ObjectId;
+pub use slice::CcSlice;
+pub use string::CcString;
+pub use trace::{Finalize, Trace, Tracer, TracerMode, TypeTracked};
+pub use vec::{GcVec, GcVecIter};
 
 #[cfg(feature = "sync")]
-pub use sync::{collect::ThreadedObjectSpace, ThreadedCc, ThreadedCcRef};
+pub use sync::{
+    collect::{LockedTrackedIter, ThreadedObjectSpace, TrackedObject},
+    ThreadedCc, ThreadedCcRef, ThreadedCcWeak,
+};
 
 /// Derive [`Trace`](trait.Trace.html) implementation for a structure.
 ///
@@ -314,7 +351,7 @@ pub use jrsonnet_gcmodule_derive::Trace;
 
 #[cfg(not(test))]
 mod debug {
-    #[cfg(any(feature = "debug", test))]
+    #[cfg(any(feature = "debug", feature = "testutil", test))]
     thread_local!(pub(crate) static NEXT_DEBUG_NAME: std::cell::Cell<usize> = Default::default());
     #[cfg(any(feature = "debug", test))]
     thread_local!(pub(crate) static GC_DROPPING: std::cell::Cell<bool> = Cell::new(false));
@@ -324,6 +361,10 @@ mod debug {
             eprintln!("[gc] {} {}", name.to_string(), message.to_string());
         }
     }
+    #[cfg(debug_assertions)]
+    pub(crate) fn without_log<R>(func: impl FnOnce() -> R) -> R {
+        func()
+    }
 }
 
 /// Whether the `debug` feature is enabled.
@@ -334,17 +375,26 @@ pub const DEBUG_ENABLED: bool = cfg!(feature = "debug");
 /// `thread_local`. Instead, there is two methods added, one should be
 /// called at the end of current thread work, and one that should be
 /// used when using other thread.
-// It won't be able to preserve debug state.
-#[cfg(not(any(test, feature = "debug")))]
+///
+/// It also has low-level helpers for crates building on the collector
+/// metadata.
 pub mod interop {
+    #[cfg(not(any(test, feature = "debug")))]
     use std::mem;
+    #[cfg(not(any(test, feature = "debug")))]
     use std::pin::Pin;
 
+    use crate::cc::{CcDummy, RawCcBox};
+    #[cfg(not(any(test, feature = "debug")))]
     use crate::collect::{new_gc_list, GcHeader, THREAD_OBJECT_SPACE};
+    use crate::{ObjectSpace, Trace};
 
     /// Type-erased gc object list
+    // It won't be able to preserve debug state.
+    #[cfg(not(any(test, feature = "debug")))]
     pub enum GcState {}
 
+    #[cfg(not(any(test, feature = "debug")))]
     type UnerasedState = Pin<Box<GcHeader>>;
 
     /// Dump current interned string pool, to be restored by
@@ -354,24 +404,45 @@ pub mod interop {
     ///
     /// Current thread gc becomes broken after this call, you should not use gc after this
     /// call, and before `reenter_thread` call.
+    #[cfg(not(any(test, feature = "debug")))]
     pub unsafe fn exit_thread() -> *mut GcState {
-        let object_list: UnerasedState = THREAD_OBJECT_SPACE
-            .with(|space| mem::replace(&mut *space.list.borrow_mut(), new_gc_list()));
+        let object_list: UnerasedState = THREAD_OBJECT_SPACE.with(|space| {
+            space.reset_step();
+            mem::replace(&mut *space.list.borrow_mut(), new_gc_list())
+        });
         Box::into_raw(Box::new(object_list)).cast()
     }
 
+    /// Vtable pointer of the collector metadata of `Cc<T>` objects, as
+    /// stored in the headers of tracked objects.
+    ///
+    /// This is useful to build a header for a `Cc<T>` allocation without an
+    /// existing object. The pointer is only meaningful together with a
+    /// `Cc<T>` allocation of an [`ObjectSpace`](../struct.ObjectSpace.html)
+    /// laid out right after the header. Using it with a different `T`, or
+    /// with a different object space type, is undefined behavior once the
+    /// collector visits the header.
+    ///
+    /// The same vtable might exist at different addresses, so the result
+    /// should not be compared with other vtable pointers.
+    pub fn ccdyn_vptr_for<T: Trace>() -> *const () {
+        CcDummy::ccdyn_vptr_for::<RawCcBox<T, ObjectSpace>>()
+    }
+
     /// Reenter thread, using state dumped by `exit_thread`.
     ///
     /// # Safety
     ///
     /// `state` should be acquired from `exit_thread`, it is not allowed
     /// to reuse state to reenter multiple threads.
+    #[cfg(not(any(test, feature = "debug")))]
     pub unsafe fn reenter_thread(state: *mut GcState) {
         let ptr: *mut UnerasedState = state.cast();
         // SAFETY: ptr is an unique state per method safety requirements.
         let ptr: Box<UnerasedState> = unsafe { Box::from_raw(ptr) };
         let ptr: UnerasedState = *ptr;
         THREAD_OBJECT_SPACE.with(|space| {
+            space.reset_step();
             let _ = mem::replace(&mut *space.list.borrow_mut(), ptr);
         });
     }
@@ -380,10 +451,9 @@ pub mod interop {
 #[cfg(test)]
 mod dyn_cc {
     use crate::Trace;
-    use std::fmt::Debug;
-    use std::ops::Deref;
+    use std::fmt::{self, Debug};
 
-    use crate::{cc_dyn, Cc};
+    use crate::cc_dyn;
 
     #[derive(Debug, Trace)]
     struct Test {
@@ -394,6 +464,12 @@ mod dyn_cc {
     impl<T> DebugAndTrace for T where T: Debug + Trace {}
     cc_dyn!(CcDebugAndTrace, DebugAndTrace);
 
+    impl Debug for CcDebugAndTrace {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            self.0.fmt(f)
+        }
+    }
+
     #[test]
     fn test_dyn() {
         let test = Test {
@@ -403,14 +479,60 @@ mod dyn_cc {
         let dyncc = CcDebugAndTrace::new(test);
         assert_eq!(format!("{dyncc:?}"), "Cc(Test { a: \"hello\" })");
         let dyncc_is_trace = dyncc;
-        assert_eq!(
-            format!("{dyncc_is_trace:?}"),
-            "Cc(Cc(Test { a: \"hello\" }))"
-        );
+        assert_eq!(format!("{dyncc_is_trace:?}"), "Cc(Test { a: \"hello\" })");
         let dyncc_is_trace_as_dyn = CcDebugAndTrace::new(dyncc_is_trace);
         assert_eq!(
             format!("{dyncc_is_trace_as_dyn:?}"),
             "Cc(Cc(Test { a: \"hello\" }))"
         );
     }
+
+    trait Animal: Trace {
+        fn befriend(&self, _other: CcAnimal) {}
+    }
+    cc_dyn!(CcAnimal, Animal);
+
+    struct Dog;
+    impl Trace for Dog {
+        fn is_type_tracked() -> bool {
+            false
+        }
+    }
+    impl Animal for Dog {}
+
+    struct Cat {
+        friends: std::cell::RefCell<Vec<CcAnimal>>,
+    }
+    impl Trace for Cat {
+        fn trace(&self, tracer: &mut crate::Tracer) {
+            self.friends.trace(tracer);
+        }
+        fn is_type_tracked() -> bool {
+            true
+        }
+    }
+    impl Animal for Cat {
+        fn befriend(&self, other: CcAnimal) {
+            self.friends.borrow_mut().push(other);
+        }
+    }
+
+    #[test]
+    fn test_dyn_collect() {
+        let new_cat = || {
+            CcAnimal::new(Cat {
+                friends: Default::default(),
+            })
+        };
+        let animals = vec![CcAnimal::new(Dog), new_cat(), new_cat()];
+        for a in &animals {
+            for b in &animals {
+                b.0.befriend(CcAnimal(a.0.clone()));
+            }
+        }
+        drop(animals);
+        // `Dog` is not tracked. It is released with the cats.
+        assert_eq!(crate::collect_thread_cycles(), 2);
+        assert_eq!(crate::count_thread_tracked(), 0);
+    }
 }
@@ -6,16 +6,21 @@ pub(crate) const REF_COUNT_MASK_TRACKED: usize = 0b1;
 /// Whether `T` in the `CcBox<T>` has been dropped.
 pub(crate) const REF_COUNT_MASK_DROPPED: usize = 0b10;
 
+/// Whether `Finalize::finalize` has been called for the `CcBox<T>`.
+pub(crate) const REF_COUNT_MASK_FINALIZED: usize = 0b100;
+
 /// Number of bits used for metadata.
-pub(crate) const REF_COUNT_SHIFT: i32 = 2;
+pub(crate) const REF_COUNT_SHIFT: i32 = 3;
 
 pub trait RefCount: 'static {
     fn is_tracked(&self) -> bool;
     fn is_dropped(&self) -> bool;
+    fn is_finalized(&self) -> bool;
     fn inc_ref(&self) -> usize;
     fn dec_ref(&self) -> usize;
     fn ref_count(&self) -> usize;
     fn set_dropped(&self) -> bool;
+    fn set_finalized(&self) -> bool;
 
     // Ideally this can be "type Locked<'a> = ..." so there is no need to
     // duplicate the function to make parking_lot optional. However it's not in
@@ -60,6 +65,11 @@ impl RefCount for SingleThreadRefCount {
         Cell::get(&self.0) & REF_COUNT_MASK_DROPPED != 0
     }
 
+    #[inline]
+    fn is_finalized(&self) -> bool {
+        Cell::get(&self.0) & REF_COUNT_MASK_FINALIZED != 0
+    }
+
     #[inline]
     fn set_dropped(&self) -> bool {
         let value = Cell::get(&self.0);
@@ -67,6 +77,13 @@ impl RefCount for SingleThreadRefCount {
         value & REF_COUNT_MASK_DROPPED != 0
     }
 
+    #[inline]
+    fn set_finalized(&self) -> bool {
+        let value = Cell::get(&self.0);
+        self.0.set(value | REF_COUNT_MASK_FINALIZED);
+        value & REF_COUNT_MASK_FINALIZED != 0
+    }
+
     #[inline]
     fn ref_count(&self) -> usize {
         self.0.get() >> REF_COUNT_SHIFT
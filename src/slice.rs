@@ -0,0 +1,110 @@
+//! `CcSlice<T>`: immutable shared slices.
+
+use crate::cc::SliceValue;
+use crate::collect;
+use crate::collect::ObjectSpace;
+use crate::Cc;
+use crate::Trace;
+use crate::Tracer;
+use std::fmt;
+use std::hash;
+use std::iter::FromIterator;
+use std::ops::Deref;
+
+/// An immutable slice shared by reference counting.
+///
+/// Compared to `Cc<Vec<T>>`, there is no spare capacity. The elements are
+/// stored inline in the `Cc` allocation, after the reference count, so
+/// accessing them goes through one pointer.
+///
+/// Cloning a `CcSlice<T>` clones the reference, not the elements.
+///
+/// ```
+/// use jrsonnet_gcmodule::CcSlice;
+///
+/// let s: CcSlice<u32> = (1..4).collect();
+/// assert_eq!(&*s, &[1, 2, 3]);
+/// assert_eq!(s.clone(), CcSlice::from_vec(vec![1, 2, 3]));
+/// ```
+pub struct CcSlice<T>(Cc<SliceValue<T>>);
+
+impl<T: Trace> CcSlice<T> {
+    /// Constructs a `CcSlice<T>` in a thread-local storage.
+    pub fn from_vec(vec: Vec<T>) -> Self {
+        collect::with_thread_object_space(|space| Self::from_vec_with_space(space, vec))
+    }
+
+    /// Constructs a `CcSlice<T>` in the given
+    /// [`ObjectSpace`](struct.ObjectSpace.html).
+    pub fn from_vec_with_space(space: &ObjectSpace, vec: Vec<T>) -> Self {
+        Self(Cc::new_slice_in_space(vec, space))
+    }
+}
+
+impl<T: Trace> FromIterator<T> for CcSlice<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self::from_vec(iter.into_iter().collect())
+    }
+}
+
+impl<T: Trace> From<Vec<T>> for CcSlice<T> {
+    fn from(vec: Vec<T>) -> Self {
+        Self::from_vec(vec)
+    }
+}
+
+impl<T> CcSlice<T> {
+    /// Returns `true` if the two `CcSlice`s point to the same allocation.
+    #[inline]
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        Cc::ptr_eq(&this.0, &other.0)
+    }
+}
+
+impl<T> Deref for CcSlice<T> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &[T] {
+        &self.0.items
+    }
+}
+
+impl<T> Clone for CcSlice<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T: PartialEq> PartialEq for CcSlice<T> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl<T: Eq> Eq for CcSlice<T> {}
+
+impl<T: hash::Hash> hash::Hash for CcSlice<T> {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        (**self).hash(state)
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for CcSlice<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl<T: Trace> Trace for CcSlice<T> {
+    fn trace(&self, tracer: &mut Tracer) {
+        tracer.trace_ptr(&self.0)
+    }
+
+    #[inline]
+    fn is_type_tracked() -> bool {
+        T::is_type_tracked()
+    }
+}
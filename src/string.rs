@@ -0,0 +1,147 @@
+//! `CcString`: interned strings.
+
+use crate::collect::ObjectSpace;
+use crate::Cc;
+use crate::Trace;
+use crate::Tracer;
+use crate::Weak;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash;
+use std::hash::BuildHasher;
+use std::ops::Deref;
+
+/// An immutable string interned per [`ObjectSpace`](struct.ObjectSpace.html).
+///
+/// Interning the same content in the same space returns the same
+/// allocation, as long as a previous `CcString` of that content is still
+/// alive. Comparing `CcString`s from the same space is a pointer comparison.
+///
+/// ```
+/// use jrsonnet_gcmodule::{CcString, ObjectSpace};
+///
+/// let space = ObjectSpace::default();
+/// let a = CcString::intern(&space, "abc");
+/// let b = CcString::intern(&space, "abc");
+/// assert!(CcString::ptr_eq(&a, &b));
+/// ```
+#[derive(Clone)]
+pub struct CcString(Cc<Box<str>>);
+
+/// Interned strings of an `ObjectSpace`.
+///
+/// Entries are keyed by the hash of their content, so the content itself is
+/// only stored once, in the `Cc` allocation.
+#[derive(Default)]
+pub(crate) struct InternTable {
+    strings: HashMap<u64, Vec<Weak<Box<str>>>>,
+    hasher: RandomState,
+
+    /// Number of weak references in `strings`.
+    pub(crate) len: usize,
+
+    /// Prune dead entries when `len` grows to this size.
+    prune_at: usize,
+}
+
+impl InternTable {
+    fn get(&self, hash: u64, s: &str) -> Option<Cc<Box<str>>> {
+        self.strings
+            .get(&hash)?
+            .iter()
+            .filter_map(|weak| weak.upgrade())
+            .find(|cc| ***cc == *s)
+    }
+
+    fn insert(&mut self, hash: u64, cc: &Cc<Box<str>>) {
+        if self.len >= self.prune_at {
+            self.strings.retain(|_, bucket| {
+                bucket.retain(Weak::is_alive);
+                !bucket.is_empty()
+            });
+            self.len = self.strings.values().map(Vec::len).sum();
+            self.prune_at = (self.len * 2).max(16);
+        }
+        self.strings.entry(hash).or_default().push(cc.downgrade());
+        self.len += 1;
+    }
+}
+
+impl CcString {
+    /// Get the interned string of `s` in `space`, or allocate a new one.
+    pub fn intern(space: &ObjectSpace, s: &str) -> Self {
+        let hash = {
+            let table = space.interned.borrow();
+            let hash = table.hasher.hash_one(s);
+            if let Some(cc) = table.get(hash, s) {
+                return Self(cc);
+            }
+            hash
+        };
+        // `create` may run a collection, whose `Drop`s may intern strings
+        // too, so the table must not be borrowed here.
+        let cc = space.create(Box::<str>::from(s));
+        space.interned.borrow_mut().insert(hash, &cc);
+        Self(cc)
+    }
+
+    /// The string content.
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns `true` if the two `CcString`s point to the same allocation.
+    #[inline]
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        Cc::ptr_eq(&this.0, &other.0)
+    }
+}
+
+impl Deref for CcString {
+    type Target = str;
+
+    #[inline]
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl PartialEq for CcString {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        // Strings from different spaces can have the same content.
+        Self::ptr_eq(self, other) || self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for CcString {}
+
+impl hash::Hash for CcString {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
+    }
+}
+
+impl fmt::Debug for CcString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.as_str().fmt(f)
+    }
+}
+
+impl fmt::Display for CcString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.as_str().fmt(f)
+    }
+}
+
+impl Trace for CcString {
+    #[inline(always)]
+    fn trace(&self, _tracer: &mut Tracer) {}
+
+    #[inline(always)]
+    fn is_type_tracked() -> bool {
+        false
+    }
+}
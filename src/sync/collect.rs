@@ -1,19 +1,36 @@
 use super::ref_count::ThreadedRefCount;
 use super::ThreadedCc;
+use super::ThreadedCcWeak;
 use crate::cc::CcDummy;
 use crate::cc::CcDyn;
 use crate::collect;
 use crate::collect::AbstractObjectSpace;
+use crate::collect::CollectionEvent;
+use crate::collect::CollectionReport;
+use crate::collect::CollectionStats;
 use crate::collect::Linked;
+use crate::collect::ReportBuilder;
 use crate::debug;
+use crate::graph;
+use crate::graph::ObjectId;
 use crate::Trace;
 use parking_lot::Mutex;
+use parking_lot::MutexGuard;
 use parking_lot::RwLock;
+use parking_lot::RwLockReadGuard;
+use std::any::Any;
 use std::cell::Cell;
+use std::io;
+use std::io::Write;
 use std::mem;
 use std::pin::Pin;
 use std::sync::Arc;
+use std::time::Instant;
 
+/// Internal metadata used by the cycle collector.
+///
+/// Like `GcHeader`, the size must be a multiple of the alignment of
+/// `CcBox<T>`, since `Linked::value` finds it right after the header.
 #[repr(C)]
 pub struct Header {
     next: Cell<*const Header>,
@@ -26,6 +43,14 @@ pub struct Header {
     linked_list_lock: Arc<Mutex<()>>,
 }
 
+const _: () = assert!(
+    mem::size_of::<Header>() % mem::align_of::<crate::cc::RawCcBox<u64, ThreadedObjectSpace>>()
+        == 0
+);
+
+/// Registered by `ThreadedObjectSpace::on_collect`.
+type CollectHook = Box<dyn Fn(CollectionEvent) + Send + Sync>;
+
 /// A collection of tracked [`ThreadedCc`](type.ThreadedCc.html) objects
 /// that can be garbage collected.
 ///
@@ -37,6 +62,9 @@ pub struct ThreadedObjectSpace {
 
     /// Whether the collector is running.
     collector_lock: Arc<RwLock<()>>,
+
+    /// Called before and after collecting. See `on_collect`.
+    hooks: RwLock<Vec<CollectHook>>,
 }
 
 // safety: accesses are protected by mutex
@@ -119,6 +147,7 @@ impl Default for ThreadedObjectSpace {
         ThreadedObjectSpace {
             list: pinned,
             collector_lock: Default::default(),
+            hooks: Default::default(),
         }
     }
 }
@@ -134,19 +163,138 @@ impl ThreadedObjectSpace {
         count
     }
 
+    /// Estimate the memory used by objects tracked by this
+    /// [`ThreadedObjectSpace`](struct.ThreadedObjectSpace.html), in bytes.
+    /// See [`ObjectSpace::live_bytes`](struct.ObjectSpace.html#method.live_bytes).
+    pub fn live_bytes(&self) -> usize {
+        let _linked_list_lock = self.list.linked_list_lock.lock();
+        let list: &Header = &self.list;
+        collect::live_bytes_list(list)
+    }
+
+    /// Count objects tracked by this
+    /// [`ThreadedObjectSpace`](struct.ThreadedObjectSpace.html) by
+    /// `Trace::type_name`, most common types first.
+    ///
+    /// Other threads cannot create or drop objects in this space while
+    /// counting.
+    pub fn count_tracked_snapshot(&self) -> Vec<(String, usize)> {
+        let _linked_list_lock = self.list.linked_list_lock.lock();
+        let list: &Header = &self.list;
+        collect::count_tracked_by_type(list)
+    }
+
+    /// Iterate through tracked objects.
+    ///
+    /// The iterator holds the locks that block collecting, creating and
+    /// dropping objects in this space until it is dropped or
+    /// [`release`](struct.LockedTrackedIter.html#method.release)d. Other
+    /// threads doing so will wait. Doing so, or borrowing objects of this
+    /// space, on the current thread while holding the iterator deadlocks.
+    ///
+    /// Iterate through `&mut` of the iterator, so the objects cannot be
+    /// used after the locks are released:
+    ///
+    /// ```
+    /// use jrsonnet_gcmodule::{ThreadedCc, ThreadedObjectSpace, Trace};
+    /// use std::sync::Mutex;
+    ///
+    /// let space = ThreadedObjectSpace::default();
+    /// let _a: ThreadedCc<Mutex<Vec<Box<dyn Trace + Send + Sync>>>> =
+    ///     space.create(Mutex::new(Vec::new()));
+    /// let mut iter = space.iter_tracked_locked();
+    /// assert_eq!((&mut iter).count(), 1);
+    /// iter.release();
+    /// ```
+    pub fn iter_tracked_locked(&self) -> LockedTrackedIter<'_> {
+        // Same order as `collect_cycles`.
+        let collector_lock = self.collector_lock.read();
+        let linked_list_lock = self.list.linked_list_lock.lock();
+        let list: &Header = &self.list;
+        LockedTrackedIter {
+            list,
+            next: list.next(),
+            locks: Some((linked_list_lock, collector_lock)),
+        }
+    }
+
+    /// Write tracked objects to `writer` as a JSON array. See
+    /// [`ObjectSpace::dump_heap`](struct.ObjectSpace.html#method.dump_heap)
+    /// for the format.
+    ///
+    /// Other threads cannot create or drop objects in this space while
+    /// writing.
+    pub fn dump_heap(&self, writer: &mut impl Write) -> io::Result<()> {
+        let _linked_list_lock = self.list.linked_list_lock.lock();
+        let list: &Header = &self.list;
+        graph::dump_heap(list, writer)
+    }
+
     /// Collect cyclic garbage tracked by this
     /// [`ThreadedObjectSpace`](struct.ThreadedObjectSpace.html).
     /// Return the number of objects collected.
+    ///
+    /// This blocks other threads from dereferencing, creating and dropping
+    /// objects in this space while scanning.
+    #[must_use = "check whether any objects were collected; call in a loop for complete collection"]
     pub fn collect_cycles(&self) -> usize {
-        // Wait for complex operations (drop). Block operations (drop, deref).
-        let collector_lock = self.collector_lock.write();
-        // Block linked list changes (create, remove).
-        let linked_list_lock = self.list.linked_list_lock.lock();
+        self.collect_cycles_with(|_, _| {}).collected
+    }
+
+    /// Like [`collect_cycles`](#method.collect_cycles), and describe what
+    /// was collected. See
+    /// [`ObjectSpace::collect_cycles_verbose`](struct.ObjectSpace.html#method.collect_cycles_verbose).
+    pub fn collect_cycles_verbose(&self) -> CollectionReport {
+        let start = Instant::now();
+        let mut builder = ReportBuilder::default();
+        let stats = self.collect_cycles_with(|id, value| builder.record::<Header>(id, value));
+        builder.finish(stats, start)
+    }
+
+    /// Collect cycles, calling `on_release` for each unreachable object
+    /// before dropping.
+    fn collect_cycles_with(&self, on_release: impl FnMut(ObjectId, &dyn CcDyn)) -> CollectionStats {
+        // The scan is stop-the-world. Scanning concurrently with mutators
+        // (like Bacon and Rajan's concurrent cycle collector) would need a
+        // buffer of candidate roots filled by `drop`, and an atomic color per
+        // object, instead of the counts stored in `Header.prev`.
         debug::log(|| ("ThreadedObjectSpace", "start collect_cycles"));
+        self.run_hooks(CollectionEvent::BeforeCollect);
         let list: &Header = &self.list;
-        let result = collect::collect_list(list, (linked_list_lock, collector_lock));
+        let lock = || {
+            // Wait for complex operations (drop). Block operations (drop, deref).
+            let collector_lock = self.collector_lock.write();
+            // Block linked list changes (create, remove).
+            let linked_list_lock = self.list.linked_list_lock.lock();
+            (linked_list_lock, collector_lock)
+        };
+        let stats = collect::collect_list(
+            list,
+            collect::Candidates::ALL,
+            &Default::default(),
+            &[],
+            lock,
+            on_release,
+        );
         debug::log(|| ("ThreadedObjectSpace", "end collect_cycles"));
-        result
+        self.run_hooks(CollectionEvent::AfterCollect(stats));
+        stats
+    }
+
+    fn run_hooks(&self, event: CollectionEvent) {
+        for hook in self.hooks.read().iter() {
+            hook(event);
+        }
+    }
+
+    /// Register `hook` to be called before and after each collection. See
+    /// [`ObjectSpace::on_collect`](struct.ObjectSpace.html#method.on_collect).
+    ///
+    /// Hooks run on the thread calling
+    /// [`collect_cycles`](#method.collect_cycles), without blocking other
+    /// threads. Registering hooks from a hook deadlocks.
+    pub fn on_collect(&self, hook: impl Fn(CollectionEvent) + Send + Sync + 'static) {
+        self.hooks.write().push(Box::new(hook));
     }
 
     /// Constructs a new [`ThreadedCc<T>`](type.ThreadedCc.html) in this
@@ -174,6 +322,106 @@ impl ThreadedObjectSpace {
         let _linked_list_lock = self.list.linked_list_lock.lock();
         ThreadedCc::new_in_space(value, self)
     }
+
+    /// Like [`create`](#method.create), and also return a
+    /// [`ThreadedCcWeak<T>`](type.ThreadedCcWeak.html) to the new object.
+    ///
+    /// Both are created while holding the lock that blocks other threads
+    /// from changing the space, so no collection can happen in between.
+    ///
+    /// ```
+    /// use jrsonnet_gcmodule::ThreadedObjectSpace;
+    ///
+    /// let space = ThreadedObjectSpace::default();
+    /// let (cc, weak) = space.create_with_weak(1u32);
+    /// assert_eq!(weak.weak_count(), 1);
+    /// drop(cc);
+    /// assert!(weak.upgrade().is_none());
+    /// ```
+    pub fn create_with_weak<T: Trace + Send + Sync>(
+        &self,
+        value: T,
+    ) -> (ThreadedCc<T>, ThreadedCcWeak<T>) {
+        let _linked_list_lock = self.list.linked_list_lock.lock();
+        let cc = ThreadedCc::new_in_space(value, self);
+        let weak = cc.downgrade();
+        (cc, weak)
+    }
+
+    /// Run `f` with a temporary
+    /// [`ThreadedObjectSpace`](struct.ThreadedObjectSpace.html), then collect
+    /// cycles in it.
+    ///
+    /// The result of `f` should not contain objects created in the space.
+    pub fn scope<R>(f: impl FnOnce(&ThreadedObjectSpace) -> R) -> R {
+        let space = ThreadedObjectSpace::default();
+        let result = f(&space);
+        let _ = space.collect_cycles();
+        result
+    }
+}
+
+/// Iterator of objects tracked by a
+/// [`ThreadedObjectSpace`](struct.ThreadedObjectSpace.html), returned by
+/// [`ThreadedObjectSpace::iter_tracked_locked`](struct.ThreadedObjectSpace.html#method.iter_tracked_locked).
+pub struct LockedTrackedIter<'a> {
+    list: &'a Header,
+    next: *const Header,
+    locks: Option<(MutexGuard<'a, ()>, RwLockReadGuard<'a, ()>)>,
+}
+
+impl LockedTrackedIter<'_> {
+    /// Release the locks before dropping the iterator. The iterator yields
+    /// nothing afterwards.
+    pub fn release(&mut self) {
+        self.locks = None;
+    }
+}
+
+impl<'b> Iterator for &'b mut LockedTrackedIter<'_> {
+    type Item = TrackedObject<'b>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.locks.is_none() || std::ptr::eq(self.next, self.list) {
+            return None;
+        }
+        // safety: The linked list cannot change while the locks are held.
+        // Yielded references borrow the iterator, so they cannot outlive
+        // the locks.
+        let header: &'b Header = unsafe { &*self.next };
+        self.next = header.next();
+        Some(TrackedObject(header))
+    }
+}
+
+/// A tracked object yielded by
+/// [`LockedTrackedIter`](struct.LockedTrackedIter.html).
+///
+/// This is a read-only view. It cannot keep the object alive or drop it.
+#[derive(Clone, Copy)]
+pub struct TrackedObject<'a>(&'a Header);
+
+impl<'a> TrackedObject<'a> {
+    /// Name of the type of the object. See `Trace::type_name`.
+    pub fn type_name(&self) -> &'static str {
+        self.0.value().gc_type_name()
+    }
+
+    /// Identity of the object. See [`ObjectId`](struct.ObjectId.html).
+    pub fn object_id(&self) -> ObjectId {
+        ObjectId::from_header(self.0)
+    }
+
+    /// Number of strong references to the object.
+    pub fn ref_count(&self) -> usize {
+        self.0.value().gc_ref_count()
+    }
+
+    /// The value of the object, to downcast to its concrete type. `None` if
+    /// it was dropped already.
+    pub fn as_any(&self) -> Option<&'a dyn Any> {
+        self.0.value().gc_as_any()
+    }
 }
 
 impl Linked for Header {
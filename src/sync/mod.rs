@@ -5,7 +5,10 @@ mod ref_count;
 mod tests;
 
 use crate::cc::RawCc;
+use crate::cc::RawWeak;
+use crate::collect::ObjectSpace;
 use crate::ref_count::RefCount;
+use crate::Cc;
 use crate::Trace;
 use crate::Tracer;
 use collect::ThreadedObjectSpace;
@@ -22,9 +25,13 @@ use std::ops::Deref;
 /// [`Cc`](type.Cc.html).
 ///
 /// To construct a [`ThreadedCc`](type.ThreadedCc.html), use
-/// [`ThreadedObjectSpace::create`](struct.ThreadedObjectSpace.html#method.create).
+/// [`ThreadedObjectSpace::create`](struct.ThreadedObjectSpace.html#method.create)
+/// or `ThreadedCc::new_with_space`.
 pub type ThreadedCc<T> = RawCc<T, ThreadedObjectSpace>;
 
+/// Weak reference of [`ThreadedCc`](type.ThreadedCc.html).
+pub type ThreadedCcWeak<T> = RawWeak<T, ThreadedObjectSpace>;
+
 /// Wraps a borrowed reference to [`ThreadedCc`](type.ThreadedCc.html).
 ///
 /// The wrapper automatically takes a lock that prevents the collector from
@@ -47,6 +54,65 @@ pub struct ThreadedCcRef<'a, T: ?Sized> {
 // safety: similar to `std::sync::Arc`
 unsafe impl<T: Send + Sync + ?Sized> Send for ThreadedCc<T> {}
 unsafe impl<T: Send + Sync + ?Sized> Sync for ThreadedCc<T> {}
+unsafe impl<T: Send + Sync + ?Sized> Send for ThreadedCcWeak<T> {}
+unsafe impl<T: Send + Sync + ?Sized> Sync for ThreadedCcWeak<T> {}
+
+impl<T: Trace + Send + Sync> ThreadedCc<T> {
+    /// Constructs a new [`ThreadedCc<T>`](type.ThreadedCc.html) in the given
+    /// [`ThreadedObjectSpace`](struct.ThreadedObjectSpace.html).
+    ///
+    /// This is the same as
+    /// [`ThreadedObjectSpace::create`](struct.ThreadedObjectSpace.html#method.create).
+    pub fn new_with_space(space: &ThreadedObjectSpace, value: T) -> ThreadedCc<T> {
+        space.create(value)
+    }
+
+    /// Moves the value into a single-threaded [`Cc<T>`](type.Cc.html) in the
+    /// given [`ObjectSpace`](struct.ObjectSpace.html), if `this` is the only
+    /// strong reference. Otherwise returns `this` unchanged.
+    ///
+    /// Weak references to `this` can no longer be upgraded afterwards.
+    ///
+    /// This briefly blocks the collector and weak upgrades of the
+    /// [`ThreadedObjectSpace`](struct.ThreadedObjectSpace.html). It
+    /// deadlocks if the current thread holds a
+    /// [`ThreadedCcRef`](struct.ThreadedCcRef.html) from the same space.
+    ///
+    /// ```
+    /// use jrsonnet_gcmodule::{ObjectSpace, ThreadedObjectSpace};
+    ///
+    /// let threaded = ThreadedObjectSpace::default();
+    /// let space = ObjectSpace::default();
+    /// let a = threaded.create(3);
+    /// let b = a.clone();
+    /// let a = a.into_single_threaded(&space).err().unwrap();
+    /// drop(b);
+    /// let a = a.into_single_threaded(&space).ok().unwrap();
+    /// assert_eq!(*a, 3);
+    /// ```
+    pub fn into_single_threaded(self, space: &ObjectSpace) -> Result<Cc<T>, Self> {
+        let inner = self.inner();
+        let value = {
+            // Exclusive: weak upgrades and the collector hold the read lock.
+            let _locked = inner.ref_count.collector_lock.write();
+            if inner.ref_count.ref_count() != 1 || inner.ref_count.is_dropped() {
+                None
+            } else {
+                // safety: This is the only strong reference, and the value
+                // is not dropped. Nothing else can access it while the lock
+                // is held, and `take_t` marks it as dropped.
+                Some(unsafe { inner.take_t() })
+            }
+        };
+        match value {
+            None => Err(self),
+            Some(value) => {
+                drop(self);
+                Ok(space.create(value))
+            }
+        }
+    }
+}
 
 impl<T: ?Sized> ThreadedCc<T> {
     /// Immutably borrows the wrapped value.
@@ -61,7 +127,7 @@ impl<T: ?Sized> ThreadedCc<T> {
     }
 }
 
-impl<'a, T: ?Sized> Deref for ThreadedCcRef<'a, T> {
+impl<T: ?Sized> Deref for ThreadedCcRef<'_, T> {
     type Target = T;
 
     fn deref(&self) -> &Self::Target {
@@ -74,7 +140,7 @@ impl<'a, T: ?Sized> Deref for ThreadedCcRef<'a, T> {
 
 impl<T: Trace> Trace for ThreadedCc<T> {
     fn trace(&self, tracer: &mut Tracer) {
-        self.inner().trace_t(tracer)
+        tracer.trace_ptr(self)
     }
 
     #[inline]
@@ -85,7 +151,7 @@ impl<T: Trace> Trace for ThreadedCc<T> {
 
 impl Trace for ThreadedCc<dyn Trace> {
     fn trace(&self, tracer: &mut Tracer) {
-        self.inner().trace_t(tracer)
+        tracer.trace_ptr(self)
     }
 
     #[inline]
@@ -97,7 +163,7 @@ impl Trace for ThreadedCc<dyn Trace> {
 
 impl Trace for ThreadedCc<dyn Trace + Send> {
     fn trace(&self, tracer: &mut Tracer) {
-        self.inner().trace_t(tracer)
+        tracer.trace_ptr(self)
     }
 
     #[inline]
@@ -109,7 +175,7 @@ impl Trace for ThreadedCc<dyn Trace + Send> {
 
 impl Trace for ThreadedCc<dyn Trace + Send + Sync> {
     fn trace(&self, tracer: &mut Tracer) {
-        self.inner().trace_t(tracer)
+        tracer.trace_ptr(self)
     }
 
     #[inline]
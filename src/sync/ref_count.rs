@@ -1,4 +1,7 @@
-use crate::ref_count::{RefCount, REF_COUNT_MASK_DROPPED, REF_COUNT_MASK_TRACKED, REF_COUNT_SHIFT};
+use crate::ref_count::{
+    RefCount, REF_COUNT_MASK_DROPPED, REF_COUNT_MASK_FINALIZED, REF_COUNT_MASK_TRACKED,
+    REF_COUNT_SHIFT,
+};
 use parking_lot::lock_api::RwLockReadGuard;
 use parking_lot::RawRwLock;
 use parking_lot::RwLock;
@@ -36,12 +39,23 @@ impl RefCount for ThreadedRefCount {
         self.ref_count.load(Acquire) & REF_COUNT_MASK_DROPPED != 0
     }
 
+    #[inline]
+    fn is_finalized(&self) -> bool {
+        self.ref_count.load(Acquire) & REF_COUNT_MASK_FINALIZED != 0
+    }
+
     #[inline]
     fn set_dropped(&self) -> bool {
         let old_value = self.ref_count.fetch_or(REF_COUNT_MASK_DROPPED, AcqRel);
         old_value & REF_COUNT_MASK_DROPPED != 0
     }
 
+    #[inline]
+    fn set_finalized(&self) -> bool {
+        let old_value = self.ref_count.fetch_or(REF_COUNT_MASK_FINALIZED, AcqRel);
+        old_value & REF_COUNT_MASK_FINALIZED != 0
+    }
+
     #[inline]
     fn ref_count(&self) -> usize {
         self.ref_count.load(Acquire) >> REF_COUNT_SHIFT
@@ -33,7 +33,7 @@ fn test_cross_thread_cycle(n: usize) {
         })
     };
 
-    let threads: Vec<_> = (0..n).map(|i| spawn_thread(i)).collect();
+    let threads: Vec<_> = (0..n).map(spawn_thread).collect();
     for thread in threads {
         thread.join().unwrap();
     }
@@ -97,16 +97,16 @@ fn test_racy_threads(
                             }
                         }
                         if (create_cycles_bits >> i) & 1 == 1 {
-                            for j in 0..thread_count {
+                            for (j, tx) in tx_list.iter().enumerate() {
                                 if j % (i + 1) == 0 {
-                                    let _ = tx_list[j].send(Box::new(acc.clone()));
+                                    let _ = tx.send(Box::new(acc.clone()));
                                 }
                             }
                         }
                     }
 
                     if (collect_cycles_bits >> i) & 1 == 1 {
-                        space.collect_cycles();
+                        let _ = space.collect_cycles();
                     }
                 }
             })
@@ -117,7 +117,7 @@ fn test_racy_threads(
         t.join().unwrap();
     }
 
-    space.collect_cycles();
+    let _ = space.collect_cycles();
     assert_eq!(space.count_tracked(), 0);
 }
 
@@ -143,3 +143,159 @@ fn test_racy_threads_collects() {
 fn test_racy_threads_mixed_collects() {
     test_racy_threads(8, 100, 0b11110000, 0b10101010);
 }
+
+#[test]
+fn test_threaded_object_space_scope() {
+    let alive = Arc::new(());
+    let tracked = ThreadedObjectSpace::scope(|space| {
+        let a: List = space.create(Mutex::new(vec![Box::new(alive.clone())]));
+        let b: List = ThreadedCc::new_with_space(space, Mutex::new(Vec::new()));
+        a.borrow().lock().unwrap().push(Box::new(b.clone()));
+        b.borrow().lock().unwrap().push(Box::new(a.clone()));
+        space.count_tracked()
+    });
+    assert_eq!(tracked, 2);
+    assert_eq!(Arc::strong_count(&alive), 1);
+}
+
+#[test]
+fn test_threaded_cc_fmt_pointer() {
+    let space = ThreadedObjectSpace::default();
+    let a: ThreadedCc<u32> = space.create(0);
+    let b = a.clone();
+    assert_eq!(format!("{:p}", a), format!("{:p}", b));
+    assert_eq!(format!("{:p}", a), format!("{:p}", &*a.borrow()));
+}
+
+#[test]
+fn test_threaded_cc_ref_id() {
+    let space = ThreadedObjectSpace::default();
+    let a: ThreadedCc<u32> = space.create(0);
+    let b = a.clone();
+    let id = spawn(move || ThreadedCc::ref_id(&b)).join().unwrap();
+    assert_eq!(ThreadedCc::ref_id(&a), id);
+    assert_ne!(ThreadedCc::ref_id(&a), ThreadedCc::ref_id(&space.create(0)));
+}
+
+#[test]
+fn test_threaded_cc_strong_count() {
+    let space = ThreadedObjectSpace::default();
+    let a: ThreadedCc<u32> = space.create(0);
+    assert_eq!(a.strong_count(), 1);
+    let b = a.clone();
+    assert_eq!(a.strong_count(), 2);
+    spawn(move || drop(b)).join().unwrap();
+    assert_eq!(a.strong_count(), 1);
+}
+
+#[test]
+fn test_iter_tracked_locked() {
+    let space = ThreadedObjectSpace::default();
+    let a: List = space.create(Mutex::new(Vec::new()));
+    let _untracked = space.create(1u32);
+    {
+        let mut iter = space.iter_tracked_locked();
+        let objects: Vec<_> = (&mut iter).collect();
+        assert_eq!(objects.len(), 1);
+        assert_eq!(
+            objects[0].type_name(),
+            std::any::type_name::<Mutex<Vec<Box<dyn Trace + Send + Sync>>>>()
+        );
+        assert_eq!(objects[0].ref_count(), 1);
+        let value = objects[0].as_any().unwrap();
+        assert!(value.is::<Mutex<Vec<Box<dyn Trace + Send + Sync>>>>());
+        iter.release();
+        assert_eq!((&mut iter).count(), 0);
+        // Objects can be dropped after releasing the locks.
+        drop(a);
+    }
+    assert_eq!(space.count_tracked(), 0);
+}
+
+#[test]
+fn test_live_bytes() {
+    let space = ThreadedObjectSpace::default();
+    assert_eq!(space.live_bytes(), 0);
+    let lists: Vec<List> = (0..10)
+        .map(|_| space.create(Mutex::new(Vec::new())))
+        .collect();
+    let bytes = space.live_bytes();
+    let list_size = std::mem::size_of::<Mutex<Vec<Box<dyn Trace + Send + Sync>>>>();
+    assert!(bytes > 10 * list_size, "{}", bytes);
+    drop(lists);
+    assert_eq!(space.live_bytes(), 0);
+}
+
+#[test]
+fn test_on_collect() {
+    use crate::CollectionEvent;
+
+    let space = ThreadedObjectSpace::default();
+    let events = Arc::new(Mutex::new(Vec::new()));
+    {
+        let events = events.clone();
+        space.on_collect(move |event| events.lock().unwrap().push(event));
+    }
+    let a: List = space.create(Mutex::new(Vec::new()));
+    a.borrow().lock().unwrap().push(Box::new(a.clone()));
+    drop(a);
+    assert_eq!(space.collect_cycles(), 1);
+    assert_eq!(
+        *events.lock().unwrap(),
+        [
+            CollectionEvent::BeforeCollect,
+            CollectionEvent::AfterCollect(crate::CollectionStats {
+                collected: 1,
+                scanned: 1,
+            })
+        ]
+    );
+}
+
+#[test]
+fn test_collect_cycles_verbose() {
+    let space = ThreadedObjectSpace::default();
+    let _alive: List = space.create(Mutex::new(Vec::new()));
+    {
+        let a: List = space.create(Mutex::new(Vec::new()));
+        let b: List = space.create(Mutex::new(Vec::new()));
+        a.borrow().lock().unwrap().push(Box::new(b.clone()));
+        b.borrow().lock().unwrap().push(Box::new(a.clone()));
+    }
+    let report = space.collect_cycles_verbose();
+    assert_eq!(report.total_collected, 2);
+    assert_eq!(report.total_scanned, 3);
+    assert_eq!(report.by_type.values().sum::<usize>(), 2);
+    assert_eq!(report.cycle_sizes, [2]);
+}
+
+#[test]
+fn test_create_with_weak() {
+    let space = Arc::new(ThreadedObjectSpace::default());
+    let (a, weak): (List, _) = space.create_with_weak(Mutex::new(Vec::new()));
+    a.borrow().lock().unwrap().push(Box::new(a.clone()));
+    drop(a);
+
+    let upgraded = spawn(move || weak.upgrade().is_some()).join().unwrap();
+    assert!(upgraded);
+    assert_eq!(space.collect_cycles(), 1);
+}
+
+#[test]
+fn test_into_single_threaded() {
+    let threaded = ThreadedObjectSpace::default();
+    let space = crate::ObjectSpace::default();
+    let a: ThreadedCc<Vec<u32>> = threaded.create(vec![1, 2]);
+    let weak = a.downgrade();
+    let b = a.clone();
+    let a = a.into_single_threaded(&space).err().unwrap();
+    drop(b);
+
+    let a = spawn(move || a).join().unwrap();
+    let a = a.into_single_threaded(&space).ok().unwrap();
+    assert_eq!(*a, [1, 2]);
+    assert!(weak.upgrade().is_none());
+    drop(weak);
+    assert_eq!(threaded.count_tracked(), 0);
+    assert_eq!(space.count_tracked(), 0);
+}
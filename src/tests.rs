@@ -5,7 +5,7 @@ use std::cell::Cell;
 use std::cell::RefCell;
 use std::ops::Deref;
 use std::panic;
-use std::sync::atomic::{AtomicBool, Ordering::SeqCst};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering::SeqCst};
 
 #[test]
 fn test_simple_untracked() {
@@ -160,7 +160,11 @@ fn test_weakref_with_cycles() {
         drop(b);
         assert!(wa.upgrade().is_some());
         assert!(wb.upgrade().is_some());
+        assert!(wa.is_alive());
         assert_eq!(collect::collect_thread_cycles(), 2);
+        assert!(!wa.is_alive());
+        assert!(!wb.is_alive());
+        assert_eq!(format!("{:?}", wa), "(Weak)");
         assert!(wa.upgrade().is_none());
         assert!(wa1.upgrade().is_none());
         assert!(wb.upgrade().is_none());
@@ -207,7 +211,7 @@ fn test_drop_by_ref_count() {
 0: drop (0), drop (T), drop (CcBoxWithGcHeader)
 1: drop (0), drop (T), drop (CcBoxWithGcHeader)
 2: drop (0), drop (T), drop (CcBoxWithGcHeader)
-collect: collect_thread_cycles, 0 unreachable objects"#
+collect: collect_thread_cycles"#
     );
 }
 
@@ -390,7 +394,7 @@ collect: 0 unreachable objects
 1: drop (0), drop (T)
 0: drop (0), drop (T), drop (CcBoxWithGcHeader)
 1: drop (CcBoxWithGcHeader)
-collect: collect_thread_cycles, 0 unreachable objects"#
+collect: collect_thread_cycles"#
     );
 }
 
@@ -400,7 +404,7 @@ fn test_update_with() {
     // Update on a unique value.
     let log = debug::capture_log(|| {
         let mut cc = Cc::new(30);
-        cc.update_with(|i| *i = *i + 1);
+        cc.update_with(|i| *i += 1);
         assert_eq!(cc.deref(), &31);
     });
     assert_eq!(log, "\n0: new (CcBox), drop (0), drop (T), drop (CcBox)");
@@ -411,7 +415,7 @@ fn test_update_with() {
         let cc1 = Cc::new(30);
         let mut cc2 = cc1.clone();
         debug::NEXT_DEBUG_NAME.with(|n| n.set(3));
-        cc2.update_with(|i| *i = *i + 1);
+        cc2.update_with(|i| *i += 1);
         assert_eq!(cc1.deref(), &30);
         assert_eq!(cc2.deref(), &31);
     });
@@ -439,7 +443,7 @@ fn test_update_with() {
         let cc1: Cc<V> = Cc::new(V(30));
         let mut cc2 = cc1.clone();
         debug::NEXT_DEBUG_NAME.with(|n| n.set(3));
-        cc2.update_with(|i| i.0 = i.0 + 1);
+        cc2.update_with(|i| i.0 += 1);
         assert_eq!(cc1.deref().0, 30);
         assert_eq!(cc2.deref().0, 31);
     });
@@ -478,9 +482,9 @@ fn capture_panic_message<R, F: Fn() -> R + panic::UnwindSafe>(func: F) -> String
         Ok(_) => "(no panic happened)".to_string(),
         Err(e) => {
             if let Some(s) = e.downcast_ref::<String>() {
-                return s.clone();
+                s.clone()
             } else if let Some(s) = e.downcast_ref::<&'static str>() {
-                return s.to_string();
+                s.to_string()
             } else {
                 "(panic information is not a string)".to_string()
             }
@@ -494,7 +498,7 @@ fn test_trace_impl_double_visits() {
     v.extra_times.set(1);
     *(v.a.borrow_mut()) = Some(Box::new(v.clone()));
 
-    let message = capture_panic_message(|| collect::collect_thread_cycles());
+    let message = capture_panic_message(collect::collect_thread_cycles);
     assert!(message.contains("bug: unexpected ref-count after dropping cycles"));
 
     // The `CcBox<_>` was "forced dropped" as a side effect.
@@ -520,6 +524,31 @@ fn leak() {
     let _ = b;
 }
 
+#[test]
+#[ignore = "causes memory leak, thus causing valgrind to error"]
+fn leak_all() {
+    type List = Cc<RefCell<Vec<Box<dyn Trace>>>>;
+    let space = crate::ObjectSpace::default();
+    let a: List = space.create(Default::default());
+    let b: List = space.create(Default::default());
+    a.borrow_mut().push(Box::new(b.clone()));
+    b.borrow_mut().push(Box::new(a.clone()));
+    let weak = a.downgrade();
+    assert_eq!(space.count_tracked(), 2);
+
+    space.leak_all();
+    assert_eq!(space.count_tracked(), 0);
+    drop((a, b));
+    assert_eq!(space.collect_cycles(), 0);
+    assert!(weak.upgrade().is_some());
+
+    // The space is still usable.
+    let c: List = space.create(Default::default());
+    c.borrow_mut().push(Box::new(c.clone()));
+    drop(c);
+    assert_eq!(space.collect_cycles(), 1);
+}
+
 #[test]
 fn test_cc_ptr_eq() {
     let a = Cc::new(1);
@@ -528,6 +557,75 @@ fn test_cc_ptr_eq() {
 
     assert!(Cc::ptr_eq(&a, &b));
     assert!(!Cc::ptr_eq(&a, &c));
+    // `==` compares values.
+    assert_eq!(a, c);
+    assert_ne!(a, Cc::new(2));
+}
+
+#[test]
+fn test_cc_hash_map_key() {
+    use std::collections::HashMap;
+
+    let a = Cc::new("hello".to_string());
+    let b = Cc::new("hello".to_string());
+    assert!(!Cc::ptr_eq(&a, &b));
+
+    let mut map = HashMap::new();
+    map.insert(a, 1u32);
+    *map.entry(b).or_default() += 1;
+    assert_eq!(map.len(), 1);
+    assert_eq!(map[&Cc::new("hello".to_string())], 2);
+}
+
+#[test]
+fn test_cc_error() {
+    use std::error::Error;
+    use std::fmt;
+
+    #[derive(Debug)]
+    struct Inner;
+    impl fmt::Display for Inner {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("inner")
+        }
+    }
+    impl Error for Inner {}
+
+    #[derive(Debug)]
+    struct Outer(Inner);
+    impl fmt::Display for Outer {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("outer")
+        }
+    }
+    impl Error for Outer {
+        fn source(&self) -> Option<&(dyn Error + 'static)> {
+            Some(&self.0)
+        }
+    }
+    impl Trace for Outer {}
+
+    let e: Box<dyn Error> = Box::new(Cc::new(Outer(Inner)));
+    assert_eq!(e.to_string(), "outer");
+    assert_eq!(e.source().unwrap().to_string(), "inner");
+}
+
+#[test]
+fn test_cc_ord() {
+    use std::collections::BTreeSet;
+
+    let mut v: Vec<Cc<i32>> = [3, 1, 2].into_iter().map(Cc::new).collect();
+    v.sort();
+    assert_eq!(v.iter().map(|x| **x).collect::<Vec<_>>(), [1, 2, 3]);
+    assert!(Cc::new(1.0) < Cc::new(2.0));
+
+    let set: BTreeSet<Cc<String>> = ["b", "a", "b"]
+        .into_iter()
+        .map(|s| Cc::new(s.to_string()))
+        .collect();
+    assert_eq!(set.len(), 2);
+    assert!(set.contains(&Cc::new("a".to_string())));
+    assert_eq!(set.first().map(|s| s.as_str()), Some("a"));
 }
 
 #[test]
@@ -561,7 +659,7 @@ impl<T: ?Sized + Trace> Trace for TraceBox<T> {
     }
 }
 struct Tracked {
-    a: Vec<u32>,
+    _a: Vec<u32>,
 }
 impl Trace for Tracked {
     fn trace(&self, _tracer: &mut Tracer) {}
@@ -579,7 +677,7 @@ fn weak_double_free() {
         Tracked::is_type_tracked(),
         "double free issue was only present for tracked (CcBoxWithGcHeader) objects"
     );
-    let v = Cc::new(TraceBox(Box::new(Tracked { a: vec![1, 2, 3] })));
+    let v = Cc::new(TraceBox(Box::new(Tracked { _a: vec![1, 2, 3] })));
     let w1 = v.clone().downgrade();
     let w2 = v.clone().downgrade();
     drop(v);
@@ -587,3 +685,2187 @@ fn weak_double_free() {
     drop(w1);
     drop(w2);
 }
+
+#[test]
+fn test_find_strongly_connected_components() {
+    type List = Cc<RefCell<Vec<Box<dyn Trace>>>>;
+    let space = crate::ObjectSpace::default();
+    let new_list = || -> List { space.create(Default::default()) };
+    let link = |from: &List, to: &List| from.borrow_mut().push(Box::new(to.clone()));
+
+    // a <-> b, c -> a, d -> d, e -> f -> g -> e
+    let (a, b, c, d) = (new_list(), new_list(), new_list(), new_list());
+    let (e, f, g) = (new_list(), new_list(), new_list());
+    link(&a, &b);
+    link(&b, &a);
+    link(&c, &a);
+    link(&d, &d);
+    link(&e, &f);
+    link(&f, &g);
+    link(&g, &e);
+
+    let mut sizes: Vec<usize> = space
+        .find_strongly_connected_components()
+        .iter()
+        .map(|c| c.len())
+        .collect();
+    sizes.sort_unstable();
+    assert_eq!(sizes, [2, 3]);
+
+    // Nothing is collected.
+    assert_eq!(space.count_tracked(), 7);
+    drop((a, b, c, d, e, f, g));
+    assert_eq!(space.collect_cycles(), 6);
+    assert!(space.find_strongly_connected_components().is_empty());
+}
+
+#[test]
+fn test_type_name() {
+    struct Named;
+    impl Trace for Named {
+        fn type_name() -> &'static str {
+            "Named"
+        }
+    }
+
+    assert_eq!(<Vec<u8>>::type_name(), std::any::type_name::<Vec<u8>>());
+    assert_eq!(Named::type_name(), "Named");
+
+    let space = crate::ObjectSpace::default();
+    let _named = space.create(Named);
+    let _list: Cc<RefCell<Vec<Box<dyn Trace>>>> = space.create(Default::default());
+    let mut expected = [
+        "Named",
+        std::any::type_name::<RefCell<Vec<Box<dyn Trace>>>>(),
+    ];
+    expected.sort_unstable();
+    let list: &collect::GcHeader = &space.list.borrow();
+    let mut names = Vec::new();
+    collect::visit_list(list, |header| {
+        names.push(collect::Linked::value(header).gc_type_name())
+    });
+    names.sort_unstable();
+    assert_eq!(names, expected);
+}
+
+#[test]
+fn test_gc_listener() {
+    use std::rc::Rc;
+
+    struct Recorder(Rc<RefCell<Vec<String>>>);
+    impl crate::GcListener for Recorder {
+        fn before_collect(&self, tracked: usize) {
+            self.0
+                .borrow_mut()
+                .push(format!("before_collect {}", tracked));
+        }
+        fn after_collect(&self, collected: usize) {
+            self.0
+                .borrow_mut()
+                .push(format!("after_collect {}", collected));
+        }
+        fn on_alloc(&self, type_name: &str) {
+            self.0.borrow_mut().push(format!("alloc {}", type_name));
+        }
+        fn on_dealloc(&self, type_name: &str) {
+            self.0.borrow_mut().push(format!("dealloc {}", type_name));
+        }
+    }
+
+    struct Node(RefCell<Option<Box<dyn Trace>>>);
+    impl Trace for Node {
+        fn trace(&self, tracer: &mut Tracer) {
+            self.0.trace(tracer);
+        }
+        fn type_name() -> &'static str {
+            "Node"
+        }
+    }
+
+    let events = Rc::new(RefCell::new(Vec::new()));
+    let space = crate::ObjectSpace::default();
+    space.set_listener(Box::new(Recorder(events.clone())));
+    {
+        let a = space.create(Node(RefCell::new(None)));
+        let b = space.create(Node(RefCell::new(None)));
+        let _c = space.create(1u8);
+        *a.0.borrow_mut() = Some(Box::new(b.clone()));
+        *b.0.borrow_mut() = Some(Box::new(a.clone()));
+    }
+    assert_eq!(space.collect_cycles(), 2);
+    assert_eq!(
+        *events.borrow(),
+        [
+            "alloc Node",
+            "alloc Node",
+            "alloc u8",
+            "before_collect 2",
+            "dealloc Node",
+            "dealloc Node",
+            "after_collect 2",
+        ]
+    );
+
+    // Objects released by running out of references are reported too.
+    events.borrow_mut().clear();
+    drop(space.create(Node(RefCell::new(None))));
+    assert_eq!(*events.borrow(), ["alloc Node", "dealloc Node"]);
+}
+
+#[test]
+fn test_gc_listener_replaced_while_collecting() {
+    use std::rc::Rc;
+
+    struct Counter(Rc<Cell<usize>>);
+    impl crate::GcListener for Counter {
+        fn before_collect(&self, _tracked: usize) {}
+        fn after_collect(&self, _collected: usize) {}
+        fn on_alloc(&self, _type_name: &str) {}
+        fn on_dealloc(&self, _type_name: &str) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    struct Replace(Rc<crate::ObjectSpace>, Rc<Cell<usize>>);
+    impl Trace for Replace {}
+    impl Drop for Replace {
+        fn drop(&mut self) {
+            self.0.set_listener(Box::new(Counter(self.1.clone())));
+        }
+    }
+
+    let space = Rc::new(crate::ObjectSpace::default());
+    let first = Rc::new(Cell::new(0));
+    let second = Rc::new(Cell::new(0));
+    space.set_listener(Box::new(Counter(first.clone())));
+    {
+        type List = Cc<RefCell<Vec<Box<dyn Trace>>>>;
+        let a: List = space.create(RefCell::new(Vec::new()));
+        let r: Cc<Replace> = space.create(Replace(space.clone(), second.clone()));
+        a.borrow_mut().push(Box::new(a.clone()));
+        a.borrow_mut().push(Box::new(r));
+    }
+    assert_eq!(space.collect_cycles(), 2);
+    // The listener was replaced before the memory was released.
+    assert_eq!(first.get(), 0);
+    assert_eq!(second.get(), 2);
+}
+
+#[test]
+fn test_reentrant_collect_cycles() {
+    use std::rc::Rc;
+
+    struct Reentrant {
+        next: RefCell<Option<Box<dyn Trace>>>,
+        space: Rc<crate::ObjectSpace>,
+        inner_collected: Rc<Cell<Option<usize>>>,
+    }
+    impl Trace for Reentrant {
+        fn trace(&self, tracer: &mut Tracer) {
+            self.next.trace(tracer);
+        }
+    }
+    impl Drop for Reentrant {
+        fn drop(&mut self) {
+            self.inner_collected.set(Some(self.space.collect_cycles()));
+        }
+    }
+
+    let space = Rc::new(crate::ObjectSpace::default());
+    let inner_collected = Rc::new(Cell::new(None));
+    {
+        let new_node = || {
+            space.create(Reentrant {
+                next: RefCell::new(None),
+                space: space.clone(),
+                inner_collected: inner_collected.clone(),
+            })
+        };
+        let a = new_node();
+        let b = new_node();
+        *a.next.borrow_mut() = Some(Box::new(b.clone()));
+        *b.next.borrow_mut() = Some(Box::new(a.clone()));
+    }
+    assert_eq!(space.collect_cycles(), 2);
+    assert_eq!(inner_collected.get(), Some(0));
+    assert_eq!(space.count_tracked(), 0);
+}
+
+#[test]
+fn test_collect_cycles_drop_panic() {
+    use std::rc::Rc;
+
+    struct MaybePanic {
+        next: RefCell<Option<Box<dyn Trace>>>,
+        should_panic: bool,
+        dropped: Rc<Cell<usize>>,
+    }
+    impl Trace for MaybePanic {
+        fn trace(&self, tracer: &mut Tracer) {
+            self.next.trace(tracer);
+        }
+    }
+    impl Drop for MaybePanic {
+        fn drop(&mut self) {
+            self.dropped.set(self.dropped.get() + 1);
+            if self.should_panic {
+                panic!("MaybePanic::drop");
+            }
+        }
+    }
+
+    let space = crate::ObjectSpace::default();
+    let dropped = Rc::new(Cell::new(0));
+    let create_cycle = |should_panic: bool| {
+        let new_node = |should_panic| {
+            space.create(MaybePanic {
+                next: RefCell::new(None),
+                should_panic,
+                dropped: dropped.clone(),
+            })
+        };
+        let a = new_node(should_panic);
+        let b = new_node(false);
+        *a.next.borrow_mut() = Some(Box::new(b.clone()));
+        *b.next.borrow_mut() = Some(Box::new(a.clone()));
+    };
+
+    create_cycle(true);
+    let space_ref = panic::AssertUnwindSafe(&space);
+    let message = capture_panic_message(move || space_ref.collect_cycles());
+    assert_eq!(message, "MaybePanic::drop");
+    assert_eq!(dropped.get(), 2);
+
+    // The panicked object is leaked. The collector keeps working.
+    assert_eq!(space.count_tracked(), 1);
+    create_cycle(false);
+    assert_eq!(space.collect_cycles(), 2);
+    assert_eq!(dropped.get(), 4);
+    assert_eq!(space.count_tracked(), 1);
+}
+
+#[test]
+fn test_create_many() {
+    type List = Cc<RefCell<Vec<Box<dyn Trace>>>>;
+    let space = crate::ObjectSpace::default();
+    let _existing: List = space.create(Default::default());
+
+    let lists: Vec<List> = space.create_many((0..3).map(|_| Default::default()).collect());
+    assert_eq!(space.count_tracked(), 4);
+    for (i, list) in lists.iter().enumerate() {
+        let next = &lists[(i + 1) % lists.len()];
+        list.borrow_mut().push(Box::new(next.clone()));
+    }
+
+    let untracked = space.create_many(vec![1u8, 2, 3]);
+    assert_eq!(*untracked[2], 3);
+    assert_eq!(space.count_tracked(), 4);
+    assert!(space.create_many(Vec::<List>::new()).is_empty());
+
+    drop(lists);
+    assert_eq!(space.collect_cycles(), 3);
+    assert_eq!(space.count_tracked(), 1);
+}
+
+#[test]
+fn test_collect_cycles_empty_space() {
+    let space = crate::ObjectSpace::default();
+    assert_eq!(space.collect_cycles(), 0);
+
+    // Untracked objects do not populate the list.
+    let _v = space.create(1u32);
+    assert_eq!(space.collect_cycles(), 0);
+
+    let log = debug::capture_log(|| {
+        assert_eq!(space.collect_cycles(), 0);
+    });
+    assert_eq!(log, "");
+}
+
+#[test]
+fn test_object_space_scope() {
+    use std::rc::Rc;
+
+    struct Node(RefCell<Option<Box<dyn Trace>>>, Rc<Cell<usize>>);
+    impl Trace for Node {
+        fn trace(&self, tracer: &mut Tracer) {
+            self.0.trace(tracer);
+        }
+    }
+    impl Drop for Node {
+        fn drop(&mut self) {
+            self.1.set(self.1.get() + 1);
+        }
+    }
+
+    let dropped = Rc::new(Cell::new(0));
+    let tracked = crate::ObjectSpace::scope(|space| {
+        let a = space.create(Node(RefCell::new(None), dropped.clone()));
+        let b = space.create(Node(RefCell::new(None), dropped.clone()));
+        *a.0.borrow_mut() = Some(Box::new(b.clone()));
+        *b.0.borrow_mut() = Some(Box::new(a.clone()));
+        space.count_tracked()
+    });
+    assert_eq!(tracked, 2);
+    assert_eq!(dropped.get(), 2);
+}
+
+#[test]
+fn test_tracer_trace_ptr() {
+    struct Explicit(Cc<RefCell<Option<Box<dyn Trace>>>>, Cc<u8>);
+    impl Trace for Explicit {
+        fn trace(&self, tracer: &mut Tracer) {
+            tracer.trace_ptr(&self.0);
+            tracer.trace_ptr(&self.1);
+        }
+    }
+
+    let value = Explicit(Cc::new(RefCell::new(None)), Cc::new(1));
+    let mut visited = Vec::new();
+    value.trace(&mut Tracer::new(&mut |header| visited.push(header)));
+    // The untracked `Cc<u8>` is skipped.
+    assert_eq!(visited.len(), 1);
+
+    let mut via_trace = Vec::new();
+    value
+        .0
+        .trace(&mut Tracer::new(&mut |header| via_trace.push(header)));
+    assert_eq!(visited, via_trace);
+}
+
+#[test]
+fn test_tracer_mode() {
+    use crate::TracerMode;
+
+    thread_local!(static MODES: RefCell<Vec<TracerMode>> = const { RefCell::new(Vec::new()) });
+    struct Node(RefCell<Option<Box<dyn Trace>>>);
+    impl Trace for Node {
+        fn trace(&self, tracer: &mut Tracer) {
+            assert_eq!(tracer.is_dry_run(), tracer.mode() == TracerMode::DryRun);
+            assert_eq!(tracer.is_inspecting(), tracer.mode() == TracerMode::Inspect);
+            MODES.with(|m| m.borrow_mut().push(tracer.mode()));
+            self.0.trace(tracer);
+        }
+    }
+    let take_modes = || {
+        let mut modes = MODES.with(|m| m.take());
+        modes.dedup();
+        modes
+    };
+
+    let space = crate::ObjectSpace::default();
+    {
+        let a = space.create(Node(RefCell::new(None)));
+        *a.0.borrow_mut() = Some(Box::new(a.clone()));
+    }
+    take_modes();
+    space.find_strongly_connected_components();
+    assert_eq!(take_modes(), [TracerMode::Inspect]);
+    assert_eq!(space.count_cycles_estimate(), 1);
+    assert_eq!(take_modes(), [TracerMode::DryRun]);
+    assert_eq!(space.collect_cycles(), 1);
+    assert_eq!(take_modes(), [TracerMode::Collect]);
+}
+
+#[test]
+fn test_tracer_is_dry_run() {
+    use std::rc::Rc;
+
+    struct Node {
+        next: RefCell<Option<Box<dyn Trace>>>,
+        side_effects: Rc<Cell<usize>>,
+    }
+    impl Trace for Node {
+        fn trace(&self, tracer: &mut Tracer) {
+            if !tracer.is_dry_run() {
+                self.side_effects.set(self.side_effects.get() + 1);
+            }
+            self.next.trace(tracer);
+        }
+    }
+
+    let space = crate::ObjectSpace::default();
+    let side_effects = Rc::new(Cell::new(0));
+    let new_node = || {
+        space.create(Node {
+            next: RefCell::new(None),
+            side_effects: side_effects.clone(),
+        })
+    };
+    let a = new_node();
+    let b = new_node();
+    *a.next.borrow_mut() = Some(Box::new(b.clone()));
+    *b.next.borrow_mut() = Some(Box::new(a.clone()));
+
+    let kept = new_node();
+    assert_eq!(space.count_cycles_estimate(), 0);
+    drop((a, b));
+    assert_eq!(space.count_cycles_estimate(), 2);
+    assert_eq!(side_effects.get(), 0);
+
+    // The estimate leaves the list usable.
+    assert_eq!(space.count_tracked(), 3);
+    assert_eq!(space.collect_cycles(), 2);
+    assert!(side_effects.get() > 0);
+    drop(kept);
+}
+
+#[test]
+fn test_collect_long_chain() {
+    struct Link(RefCell<Option<Cc<Link>>>);
+    impl Trace for Link {
+        fn trace(&self, tracer: &mut Tracer) {
+            self.0.trace(tracer);
+        }
+        fn is_type_tracked() -> bool {
+            true
+        }
+    }
+
+    // Marking a long chain of reachable objects should not overflow the stack.
+    let space = crate::ObjectSpace::default();
+    let head = space.create(Link(RefCell::new(None)));
+    let mut tail = head.clone();
+    for _ in 0..100_000 {
+        let node = space.create(Link(RefCell::new(None)));
+        *tail.0.borrow_mut() = Some(node.clone());
+        tail = node;
+    }
+    *tail.0.borrow_mut() = Some(head.clone());
+    drop(tail);
+    assert_eq!(space.collect_cycles(), 0);
+    assert_eq!(space.count_tracked(), 100_001);
+
+    // Break the chain to avoid deeply recursive drops.
+    let mut next = head.0.borrow_mut().take();
+    while let Some(node) = next {
+        next = node.0.borrow_mut().take();
+    }
+    drop(head);
+    assert_eq!(space.count_tracked(), 0);
+}
+
+#[test]
+fn test_tracer_depth_limit() {
+    let value: Box<Box<Box<dyn Trace>>> = Box::new(Box::new(Box::new(Cc::new(RefCell::new(
+        None::<Box<dyn Trace>>,
+    )))));
+    let count_edges = |limit: usize| {
+        let mut count = 0;
+        let mut visit = |_| count += 1;
+        let mut tracer = Tracer::new(&mut visit).with_depth_limit(limit);
+        value.trace(&mut tracer);
+        assert_eq!(tracer.depth(), 0);
+        count
+    };
+    assert_eq!(count_edges(2), 0);
+    assert_eq!(count_edges(3), 1);
+}
+
+#[test]
+fn test_collect_depth_limit() {
+    struct Nest(Option<Box<Nest>>, Option<Box<dyn Trace>>);
+    impl Trace for Nest {
+        fn trace(&self, tracer: &mut Tracer) {
+            self.0.trace(tracer);
+            self.1.trace(tracer);
+        }
+        fn is_type_tracked() -> bool {
+            true
+        }
+    }
+
+    // A cycle going through `depth` nested boxes.
+    let space = crate::ObjectSpace::default();
+    let create_cycle = |depth: usize| {
+        let a: Cc<RefCell<Option<Nest>>> = space.create(RefCell::new(None));
+        let mut nest = Nest(None, Some(Box::new(a.clone())));
+        for _ in 0..depth {
+            nest = Nest(Some(Box::new(nest)), None);
+        }
+        *a.borrow_mut() = Some(nest);
+        a
+    };
+
+    drop(create_cycle(10));
+    assert_eq!(space.collect_cycles(), 1);
+
+    // Too deep to trace. The object is treated as alive.
+    let a = create_cycle(2000);
+    let weak = a.downgrade();
+    drop(a);
+    assert_eq!(space.collect_cycles(), 0);
+    assert_eq!(space.count_tracked(), 1);
+
+    // Break the cycle to clean up.
+    let nest = weak.upgrade().unwrap().borrow_mut().take();
+    drop((nest, weak));
+    assert_eq!(space.count_tracked(), 0);
+}
+
+#[test]
+fn test_finalize() {
+    use crate::Finalize;
+
+    thread_local!(static FINALIZED: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) });
+
+    struct Node {
+        name: &'static str,
+        next: RefCell<Option<Cc<Node>>>,
+    }
+    impl Trace for Node {
+        fn trace(&self, tracer: &mut Tracer) {
+            self.next.trace(tracer);
+        }
+        fn as_finalize(&self) -> Option<&dyn Finalize> {
+            Some(self)
+        }
+    }
+    impl Finalize for Node {
+        fn finalize(&self) {
+            // Other objects in the cycle are still accessible.
+            let next = self.next.borrow().as_ref().unwrap().name;
+            FINALIZED.with(|f| f.borrow_mut().push(format!("{} -> {}", self.name, next)));
+        }
+    }
+
+    let space = crate::ObjectSpace::default();
+    {
+        let a = space.create(Node {
+            name: "a",
+            next: RefCell::new(None),
+        });
+        let b = space.create(Node {
+            name: "b",
+            next: RefCell::new(Some(a.clone())),
+        });
+        *a.next.borrow_mut() = Some(b);
+    }
+    assert_eq!(space.collect_cycles(), 2);
+    assert_eq!(space.count_tracked(), 0);
+    let mut finalized = FINALIZED.with(|f| f.take());
+    finalized.sort();
+    assert_eq!(finalized, ["a -> b", "b -> a"]);
+}
+
+#[test]
+fn test_finalize_before_drop() {
+    use crate::Finalize;
+
+    thread_local!(static EVENTS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) });
+
+    struct Node(&'static str, RefCell<Vec<Cc<Node>>>);
+    impl Trace for Node {
+        fn trace(&self, tracer: &mut Tracer) {
+            self.1.trace(tracer);
+        }
+        fn as_finalize(&self) -> Option<&dyn Finalize> {
+            Some(self)
+        }
+    }
+    impl Finalize for Node {
+        fn finalize(&self) {
+            EVENTS.with(|e| e.borrow_mut().push(format!("finalize {}", self.0)));
+        }
+    }
+    impl Drop for Node {
+        fn drop(&mut self) {
+            EVENTS.with(|e| e.borrow_mut().push(format!("drop {}", self.0)));
+        }
+    }
+
+    let space = crate::ObjectSpace::default();
+    {
+        let nodes: Vec<_> = ["a", "b", "c"]
+            .into_iter()
+            .map(|name| space.create(Node(name, RefCell::new(Vec::new()))))
+            .collect();
+        for (i, node) in nodes.iter().enumerate() {
+            node.1.borrow_mut().push(nodes[(i + 1) % 3].clone());
+        }
+    }
+    EVENTS.with(|e| e.borrow_mut().push("collect".to_string()));
+    assert_eq!(space.collect_cycles(), 3);
+
+    let events = EVENTS.with(|e| e.take());
+    assert_eq!(events.len(), 7);
+    assert_eq!(events[0], "collect");
+    // All finalizers run before anything in the cycle is dropped.
+    assert!(events[1..4].iter().all(|e| e.starts_with("finalize ")));
+    assert!(events[4..].iter().all(|e| e.starts_with("drop ")));
+}
+
+#[test]
+fn test_finalize_resurrect() {
+    use crate::Finalize;
+
+    thread_local!(static SAVED: RefCell<Option<Cc<Node>>> = const { RefCell::new(None) });
+    static FINALIZE_COUNT: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+    struct Node(RefCell<Option<Cc<Node>>>);
+    impl Trace for Node {
+        fn trace(&self, tracer: &mut Tracer) {
+            self.0.trace(tracer);
+        }
+        fn as_finalize(&self) -> Option<&dyn Finalize> {
+            Some(self)
+        }
+    }
+    impl Finalize for Node {
+        fn finalize(&self) {
+            FINALIZE_COUNT.fetch_add(1, SeqCst);
+            // Resurrect the other object in the cycle.
+            let other = self.0.borrow().clone();
+            SAVED.with(|s| *s.borrow_mut() = other);
+        }
+    }
+
+    {
+        let a = Cc::new(Node(RefCell::new(None)));
+        let b = Cc::new(Node(RefCell::new(Some(a.clone()))));
+        *a.0.borrow_mut() = Some(b);
+    }
+    assert_eq!(collect_thread_cycles(), 0);
+    assert_eq!(FINALIZE_COUNT.load(SeqCst), 2);
+    assert_eq!(collect::count_thread_tracked(), 2);
+
+    // Finalizers do not run again.
+    SAVED.with(|s| s.borrow_mut().take());
+    assert_eq!(collect_thread_cycles(), 2);
+    assert_eq!(FINALIZE_COUNT.load(SeqCst), 2);
+    assert_eq!(collect::count_thread_tracked(), 0);
+}
+
+#[test]
+fn test_gc_cell() {
+    use crate::GcCell;
+
+    struct Node(Option<GcCell<Node>>);
+    impl Trace for Node {
+        fn trace(&self, tracer: &mut Tracer) {
+            self.0.trace(tracer);
+        }
+    }
+
+    let space = crate::ObjectSpace::default();
+    {
+        let a = GcCell::new_with_space(&space, Node(None));
+        let b = GcCell::new_with_space(&space, Node(Some(a.clone())));
+        a.borrow_mut().0 = Some(b.clone());
+
+        let borrowed = a.borrow();
+        assert!(a.try_borrow().is_ok());
+        assert!(a.try_borrow_mut().is_err());
+        drop(borrowed);
+        assert!(a.try_borrow_mut().is_ok());
+    }
+    assert_eq!(space.count_tracked(), 2);
+    assert_eq!(space.collect_cycles(), 2);
+
+    let v = GcCell::new_with_space(&space, 1);
+    assert_eq!(format!("{:?}", v), "GcCell(1)");
+    let _borrowed = v.borrow_mut();
+    assert_eq!(format!("{:?}", v), "GcCell(<borrowed>)");
+}
+
+#[test]
+fn test_ephemeron() {
+    use crate::Ephemeron;
+
+    struct Node(RefCell<Option<Cc<Node>>>);
+    impl Trace for Node {
+        fn trace(&self, tracer: &mut Tracer) {
+            self.0.trace(tracer);
+        }
+    }
+
+    let space = crate::ObjectSpace::default();
+    let table = Ephemeron::new();
+    let live = space.create(Node(RefCell::new(None)));
+    table.insert(&live, "live");
+    {
+        let a = space.create(Node(RefCell::new(None)));
+        let b = space.create(Node(RefCell::new(Some(a.clone()))));
+        *a.0.borrow_mut() = Some(b);
+        table.insert(&a, "cyclic");
+        assert_eq!(table.get(&a).as_deref(), Some(&"cyclic"));
+    }
+    assert_eq!(table.len(), 2);
+
+    // The table does not keep the cycle alive. Its entry is removed.
+    assert_eq!(space.collect_cycles(), 2);
+    assert_eq!(table.len(), 1);
+    assert_eq!(table.prune(), 0);
+
+    // Keys dropped by reference counting leave dead entries until pruned.
+    let temp = space.create(Node(RefCell::new(None)));
+    table.insert(&temp, "temp");
+    drop(temp);
+    assert_eq!(table.len(), 1);
+    assert_eq!(table.prune(), 1);
+
+    assert_eq!(table.insert(&live, "updated"), Some("live"));
+    assert_eq!(table.remove(&live), Some("updated"));
+    assert!(table.get(&live).is_none());
+    assert!(table.is_empty());
+}
+
+#[test]
+fn test_ephemeron_value_refers_to_key() {
+    use crate::Ephemeron;
+
+    type List = Cc<RefCell<Vec<Box<dyn Trace>>>>;
+    let space = crate::ObjectSpace::default();
+    let table: Ephemeron<RefCell<Vec<Box<dyn Trace>>>, List> = Ephemeron::new();
+
+    // Values referring to their own keys do not keep the keys alive.
+    let key: List = space.create(Default::default());
+    let value: List = space.create(Default::default());
+    value.borrow_mut().push(Box::new(key.clone()));
+    table.insert(&key, value);
+    drop(key);
+    assert_eq!(space.count_tracked(), 2);
+    assert_eq!(space.collect_cycles(), 2);
+    assert!(table.is_empty());
+
+    // Values of reachable keys are kept, including the keys they refer to.
+    let key: List = space.create(Default::default());
+    let other: List = space.create(Default::default());
+    let value: List = space.create(Default::default());
+    value.borrow_mut().push(Box::new(other.clone()));
+    table.insert(&key, value);
+    let value: List = space.create(Default::default());
+    table.insert(&other, value);
+    drop(other);
+    assert_eq!(space.collect_cycles(), 0);
+    assert_eq!(table.len(), 2);
+
+    drop(key);
+    assert_eq!(space.collect_cycles(), 3);
+    assert!(table.is_empty());
+}
+
+#[test]
+fn test_cc_slice() {
+    use crate::CcSlice;
+
+    struct Node(RefCell<Option<CcSlice<Cc<Node>>>>);
+    impl Trace for Node {
+        fn trace(&self, tracer: &mut Tracer) {
+            self.0.trace(tracer);
+        }
+    }
+
+    assert!(!CcSlice::<u8>::is_type_tracked());
+    assert!(CcSlice::<Cc<Node>>::is_type_tracked());
+
+    let space = crate::ObjectSpace::default();
+    {
+        let a = space.create(Node(RefCell::new(None)));
+        let b = space.create(Node(RefCell::new(None)));
+        let slice = CcSlice::from_vec_with_space(&space, vec![a.clone(), b.clone()]);
+        *a.0.borrow_mut() = Some(slice.clone());
+        *b.0.borrow_mut() = Some(slice);
+    }
+    assert_eq!(space.count_tracked(), 3);
+    assert_eq!(space.collect_cycles(), 3);
+
+    let s: CcSlice<u8> = vec![1, 2].into_iter().collect();
+    assert!(CcSlice::ptr_eq(&s, &s.clone()));
+    assert!(!CcSlice::ptr_eq(&s, &CcSlice::from(vec![1, 2])));
+    assert_eq!(s, CcSlice::from(vec![1, 2]));
+    assert_eq!(format!("{:?}", s), "[1, 2]");
+    assert!(CcSlice::<u8>::from(vec![]).is_empty());
+
+    // Elements are moved in, and dropped once.
+    let shared = space.create(1u8);
+    let s = CcSlice::from_vec_with_space(&space, vec![shared.clone(), shared.clone()]);
+    assert_eq!(shared.strong_count(), 3);
+    drop(s);
+    assert_eq!(shared.strong_count(), 1);
+
+    // Elements are aligned.
+    #[repr(align(64))]
+    struct Aligned(u8);
+    impl Trace for Aligned {
+        fn is_type_tracked() -> bool {
+            false
+        }
+    }
+    let s = CcSlice::from_vec(vec![Aligned(1), Aligned(2)]);
+    assert_eq!(&s[0] as *const Aligned as usize % 64, 0);
+    assert_eq!(s[1].0, 2);
+}
+
+#[test]
+fn test_cc_string_intern() {
+    use crate::CcString;
+
+    let space = crate::ObjectSpace::default();
+    let a = CcString::intern(&space, "abc");
+    let b = CcString::intern(&space, "abc");
+    let c = CcString::intern(&space, "abd");
+    assert!(CcString::ptr_eq(&a, &b));
+    assert!(!CcString::ptr_eq(&a, &c));
+    assert_eq!(a, b);
+    assert_ne!(a, c);
+    assert_eq!(&*a, "abc");
+
+    // Same content from another space is equal, but not the same pointer.
+    let other_space = crate::ObjectSpace::default();
+    let d = CcString::intern(&other_space, "abc");
+    assert!(!CcString::ptr_eq(&a, &d));
+    assert_eq!(a, d);
+
+    // Interned strings are not kept alive by the table.
+    let kept = a.clone();
+    drop((a, b));
+    let e = CcString::intern(&space, "abc");
+    assert!(CcString::ptr_eq(&kept, &e));
+    drop((kept, e));
+    for i in 0..100 {
+        CcString::intern(&space, &i.to_string());
+    }
+    assert!(space.interned.borrow().len < 50);
+    assert_eq!(space.count_tracked(), 0);
+}
+
+#[test]
+fn test_cc_string_intern_while_collecting() {
+    use crate::CcString;
+
+    struct Node(std::rc::Rc<crate::ObjectSpace>, RefCell<Option<Cc<Node>>>);
+    impl Trace for Node {
+        fn trace(&self, tracer: &mut Tracer) {
+            self.1.trace(tracer);
+        }
+    }
+    impl Drop for Node {
+        fn drop(&mut self) {
+            CcString::intern(&self.0, "dropped");
+        }
+    }
+
+    let space = std::rc::Rc::new(crate::ObjectSpace::with_collection_policy(
+        crate::CollectionPolicy {
+            auto_collect_threshold: Some(1),
+            ..Default::default()
+        },
+    ));
+    let a = space.create(Node(space.clone(), RefCell::new(None)));
+    *a.1.borrow_mut() = Some(a.clone());
+    drop(a);
+    // Interning allocates, which collects the cycle above.
+    let s = CcString::intern(&space, "abc");
+    assert_eq!(&*s, "abc");
+    assert_eq!(space.count_tracked(), 0);
+}
+
+#[test]
+fn test_collect_cycles_verbose() {
+    struct Node(RefCell<Vec<Box<dyn Trace>>>);
+    impl Trace for Node {
+        fn trace(&self, tracer: &mut Tracer) {
+            self.0.trace(tracer);
+        }
+        fn type_name() -> &'static str {
+            "Node"
+        }
+    }
+    struct Leaf(Box<dyn Trace>);
+    impl Trace for Leaf {
+        fn trace(&self, tracer: &mut Tracer) {
+            self.0.trace(tracer);
+        }
+        fn type_name() -> &'static str {
+            "Leaf"
+        }
+    }
+
+    let space = crate::ObjectSpace::default();
+    let new_cycle = |size: usize| {
+        let nodes: Vec<_> = (0..size)
+            .map(|_| space.create(Node(RefCell::new(Vec::new()))))
+            .collect();
+        for (i, node) in nodes.iter().enumerate() {
+            let next = nodes[(i + 1) % size].clone();
+            node.0.borrow_mut().push(Box::new(next));
+        }
+        nodes[0].clone()
+    };
+    {
+        let a = new_cycle(3);
+        let leaf = space.create(Leaf(Box::new(1u8)));
+        a.0.borrow_mut().push(Box::new(leaf));
+        new_cycle(2);
+    }
+    let _alive = new_cycle(1);
+
+    let report = space.collect_cycles_verbose();
+    assert_eq!(report.total_collected, 6);
+    assert_eq!(report.total_scanned, 7);
+    assert_eq!(report.by_type.len(), 2);
+    assert_eq!(report.by_type["Node"], 5);
+    assert_eq!(report.by_type["Leaf"], 1);
+    assert_eq!(report.cycle_sizes, [4, 2]);
+
+    let report = space.collect_cycles_verbose();
+    assert_eq!(report.total_collected, 0);
+    assert_eq!(report.total_scanned, 1);
+    assert!(report.cycle_sizes.is_empty());
+}
+
+#[test]
+fn test_generation_bump() {
+    struct Node(RefCell<Option<Cc<Node>>>);
+    impl Trace for Node {
+        fn trace(&self, tracer: &mut Tracer) {
+            self.0.trace(tracer);
+        }
+    }
+    let space = crate::ObjectSpace::default();
+    let new_cycle = || {
+        let a = space.create(Node(RefCell::new(None)));
+        let b = space.create(Node(RefCell::new(Some(a.clone()))));
+        *a.0.borrow_mut() = Some(b);
+        a
+    };
+
+    let old = new_cycle();
+    let old_generation = old.generation().unwrap();
+    drop(old);
+    let young_generation = space.generation_bump();
+    assert_ne!(young_generation, old_generation);
+
+    let holder = space.create(Node(RefCell::new(None)));
+    let young = new_cycle();
+    assert_eq!(young.generation(), Some(young_generation));
+    assert_eq!(Cc::new(1u8).generation(), None);
+    drop(young);
+    assert_eq!(space.count_cycles_estimate(), 2);
+    assert_eq!(space.collect_cycles(), 2);
+    assert_eq!(space.count_tracked(), 3);
+
+    // An old object referring to a young object keeps it alive.
+    space.generation_bump();
+    let referred = new_cycle();
+    *holder.0.borrow_mut() = Some(referred.clone());
+    *referred.0.borrow().as_ref().unwrap().0.borrow_mut() = Some(holder.clone());
+    drop((holder, referred));
+    assert_eq!(space.collect_cycles(), 0);
+    assert_eq!(space.collect_cycles_full(), 5);
+    assert_eq!(space.count_tracked(), 0);
+
+    // Generation ids wrap around instead of overflowing.
+    let first = space.generation_bump();
+    let wrapped = (0..256).fold(first, |_, _| space.generation_bump());
+    assert_eq!(wrapped, first);
+}
+
+#[test]
+fn test_drop_space_collects_old_generations() {
+    static DROPPED: AtomicBool = AtomicBool::new(false);
+    struct Node(RefCell<Option<Cc<Node>>>);
+    impl Trace for Node {
+        fn trace(&self, tracer: &mut Tracer) {
+            self.0.trace(tracer);
+        }
+    }
+    impl Drop for Node {
+        fn drop(&mut self) {
+            DROPPED.store(true, SeqCst);
+        }
+    }
+    {
+        let space = crate::ObjectSpace::default();
+        let a = space.create(Node(RefCell::new(None)));
+        *a.0.borrow_mut() = Some(a.clone());
+        drop(a);
+        space.generation_bump();
+    }
+    assert!(DROPPED.load(SeqCst));
+}
+
+#[test]
+fn test_drop_space_ignores_policy() {
+    use crate::{CollectionPolicy, Finalize};
+
+    static DROPPED: AtomicUsize = AtomicUsize::new(0);
+    struct Node(RefCell<Option<Cc<Node>>>);
+    impl Trace for Node {
+        fn trace(&self, tracer: &mut Tracer) {
+            self.0.trace(tracer);
+        }
+        fn as_finalize(&self) -> Option<&dyn Finalize> {
+            Some(self)
+        }
+    }
+    impl Finalize for Node {
+        fn finalize(&self) {}
+    }
+    impl Drop for Node {
+        fn drop(&mut self) {
+            DROPPED.fetch_add(1, SeqCst);
+        }
+    }
+    {
+        let space = crate::ObjectSpace::with_collection_policy(CollectionPolicy {
+            min_tracked_to_collect: 100,
+            max_passes: 1,
+            ..Default::default()
+        });
+        let a = space.create(Node(RefCell::new(None)));
+        *a.0.borrow_mut() = Some(a.clone());
+        drop(a);
+        assert_eq!(space.collect_cycles(), 0);
+    }
+    assert_eq!(DROPPED.load(SeqCst), 1);
+}
+
+#[test]
+fn test_collect_cycles_step() {
+    use crate::StepResult;
+
+    struct Node(RefCell<Option<Cc<Node>>>);
+    impl Trace for Node {
+        fn trace(&self, tracer: &mut Tracer) {
+            self.0.trace(tracer);
+        }
+    }
+    let space = crate::ObjectSpace::default();
+    let new_cycle = || {
+        let a = space.create(Node(RefCell::new(None)));
+        let b = space.create(Node(RefCell::new(Some(a.clone()))));
+        *a.0.borrow_mut() = Some(b);
+    };
+    let _alive = space.create(Node(RefCell::new(None)));
+    for _ in 0..3 {
+        new_cycle();
+    }
+
+    // Cycles larger than the budget are not collected.
+    assert_eq!(space.collect_cycles_step(1), StepResult::Partial(6));
+    for _ in 0..5 {
+        assert!(matches!(
+            space.collect_cycles_step(1),
+            StepResult::Partial(_)
+        ));
+    }
+    assert_eq!(space.collect_cycles_step(1), StepResult::Complete(0));
+    assert_eq!(space.count_tracked(), 7);
+
+    assert_eq!(space.collect_cycles_step(2), StepResult::Partial(5));
+    assert_eq!(space.collect_cycles_step(2), StepResult::Partial(3));
+    assert_eq!(space.collect_cycles_step(2), StepResult::Partial(1));
+    assert_eq!(space.collect_cycles_step(2), StepResult::Complete(6));
+    assert_eq!(space.count_tracked(), 1);
+}
+
+#[test]
+fn test_collect_cycles_step_position() {
+    use crate::StepResult;
+
+    struct Node(RefCell<Option<Cc<Node>>>);
+    impl Trace for Node {
+        fn trace(&self, tracer: &mut Tracer) {
+            self.0.trace(tracer);
+        }
+    }
+    let space = crate::ObjectSpace::default();
+    let new = || space.create(Node(RefCell::new(None)));
+
+    // A cycle with other objects between its two ends.
+    let a = new();
+    let mut others: Vec<_> = (0..4).map(|_| new()).collect();
+    let b = new();
+    *b.0.borrow_mut() = Some(a.clone());
+    *a.0.borrow_mut() = Some(b);
+    drop(a);
+
+    assert!(matches!(
+        space.collect_cycles_step(2),
+        StepResult::Partial(_)
+    ));
+    assert_eq!(space.count_tracked(), 4);
+
+    // Objects created or released between steps do not move the position.
+    let _young = new();
+    others.remove(0);
+    assert_eq!(space.shrink_tracked_list(), 0);
+    assert_eq!(space.collect_cycles(), 0);
+    let mut steps = 0;
+    let collected = loop {
+        steps += 1;
+        match space.collect_cycles_step(1) {
+            StepResult::Partial(_) => continue,
+            StepResult::Complete(n) => break n,
+        }
+    };
+    assert_eq!(steps, 3);
+    assert_eq!(collected, 2);
+    assert_eq!(space.count_tracked(), 4);
+}
+
+#[test]
+fn test_collect_cycles_step_matches_full() {
+    use crate::StepResult;
+
+    struct Node(RefCell<Vec<Cc<Node>>>);
+    impl Trace for Node {
+        fn trace(&self, tracer: &mut Tracer) {
+            self.0.trace(tracer);
+        }
+    }
+    // A live chain, a chain released by reference counting, and garbage
+    // cycles of size 1 and 2.
+    let populate = |space: &crate::ObjectSpace| {
+        let new = || space.create(Node(RefCell::new(Vec::new())));
+        let live = new();
+        live.0.borrow_mut().push(new());
+        let chain = new();
+        chain.0.borrow_mut().push(new());
+        let a = new();
+        a.0.borrow_mut().push(a.clone());
+        let b = new();
+        let c = new();
+        b.0.borrow_mut().push(c.clone());
+        c.0.borrow_mut().push(b.clone());
+        live
+    };
+
+    let full = crate::ObjectSpace::default();
+    let _live_full = populate(&full);
+    assert_eq!(full.count_tracked(), 5);
+    let collected = full.collect_cycles();
+    assert_eq!(full.count_tracked(), 2);
+
+    let collect_stepped = |budget: usize| {
+        let stepped = crate::ObjectSpace::default();
+        let _live = populate(&stepped);
+        let mut steps = 0;
+        let stepped_collected = loop {
+            steps += 1;
+            match stepped.collect_cycles_step(budget) {
+                StepResult::Partial(_) => continue,
+                StepResult::Complete(n) => break n,
+            }
+        };
+        assert_eq!(stepped.count_tracked(), 5 - stepped_collected);
+        (stepped_collected, steps)
+    };
+    // Budgets of at least the largest cycle collect the same objects, no
+    // matter where the steps split the list.
+    for budget in 2..=5 {
+        let (stepped_collected, steps) = collect_stepped(budget);
+        assert!(budget >= 5 || steps > 1);
+        assert_eq!(stepped_collected, collected, "budget {}", budget);
+    }
+    // The 2-object cycle does not fit in 1 object.
+    assert_eq!(collect_stepped(1).0, 1);
+
+    // Objects are taken newest first. With budget 2, the first step takes
+    // `filler` and `c`, and the second one takes `b`. The cycle is still
+    // considered as a whole.
+    let space = crate::ObjectSpace::default();
+    let new = || space.create(Node(RefCell::new(Vec::new())));
+    let b = new();
+    let c = new();
+    b.0.borrow_mut().push(c.clone());
+    c.0.borrow_mut().push(b.clone());
+    let _filler = new();
+    drop((b, c));
+    assert_eq!(space.collect_cycles_step(2), StepResult::Partial(1));
+    assert_eq!(space.count_tracked(), 1);
+    assert_eq!(space.collect_cycles_step(2), StepResult::Complete(2));
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "outlived its ObjectSpace")]
+fn test_cc_outlives_object_space() {
+    struct Node(RefCell<Option<Cc<Node>>>);
+    impl Trace for Node {
+        fn trace(&self, tracer: &mut Tracer) {
+            self.0.trace(tracer);
+        }
+    }
+    let escaped = {
+        let space = crate::ObjectSpace::default();
+        space.create(Node(RefCell::new(None)))
+    };
+    drop(escaped);
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "in a different ObjectSpace")]
+fn test_cross_space_reference() {
+    let space1 = crate::ObjectSpace::default();
+    let space2 = crate::ObjectSpace::default();
+    let a: Cc<RefCell<Option<Box<dyn Trace>>>> = space1.create(RefCell::new(None));
+    let _b: Cc<Vec<Box<dyn Trace>>> = space2.create(vec![Box::new(a) as Box<dyn Trace>]);
+}
+
+#[test]
+#[cfg(feature = "sync")]
+fn test_threaded_cc_in_space() {
+    use crate::{ThreadedCc, ThreadedObjectSpace};
+    use std::sync::Mutex;
+
+    type ThreadedList = ThreadedCc<Mutex<Vec<Box<dyn Trace + Send + Sync>>>>;
+    let threaded = ThreadedObjectSpace::default();
+    let inner: ThreadedList = threaded.create(Mutex::new(Vec::new()));
+    // The header of `inner` is not a `GcHeader`, and is not checked.
+    let space = crate::ObjectSpace::default();
+    let a: Cc<RefCell<Vec<Box<dyn Trace>>>> = space.create(RefCell::new(vec![Box::new(inner)]));
+    a.borrow_mut().push(Box::new(a.clone()));
+    drop(a);
+    assert_eq!(space.collect_cycles(), 1);
+    assert_eq!(threaded.count_tracked(), 0);
+}
+
+#[test]
+fn test_collect_single_object_cycle() {
+    struct Node(RefCell<Option<Cc<Node>>>);
+    impl Trace for Node {
+        fn trace(&self, tracer: &mut Tracer) {
+            self.0.trace(tracer);
+        }
+    }
+    let space = crate::ObjectSpace::default();
+    let x = space.create(Node(RefCell::new(None)));
+    *x.0.borrow_mut() = Some(x.clone());
+    assert_eq!(space.count_tracked(), 1);
+    drop(x);
+    assert_eq!(space.collect_cycles(), 1);
+    assert_eq!(space.count_tracked(), 0);
+}
+
+#[test]
+fn test_drain_unreachable() {
+    type List = Cc<RefCell<Vec<Box<dyn Trace>>>>;
+    let space = crate::ObjectSpace::default();
+    let live: List = space.create(Default::default());
+    {
+        // Self-referred, keeping `child` alive.
+        let a: List = space.create(Default::default());
+        let child: List = space.create(Default::default());
+        a.borrow_mut().push(Box::new(a.clone()));
+        a.borrow_mut().push(Box::new(a.clone()));
+        a.borrow_mut().push(Box::new(child));
+        // A 2-object cycle.
+        let b: List = space.create(Default::default());
+        let c: List = space.create(Default::default());
+        b.borrow_mut().push(Box::new(c.clone()));
+        c.borrow_mut().push(Box::new(b.clone()));
+        assert_eq!(space.drain_unreachable(), 0);
+    }
+    assert_eq!(space.count_tracked(), 5);
+    assert_eq!(space.drain_unreachable(), 1);
+    assert_eq!(space.count_tracked(), 3);
+    assert_eq!(space.collect_cycles(), 2);
+    assert_eq!(space.count_tracked(), 1);
+    drop(live);
+}
+
+#[test]
+fn test_drain_unreachable_hooks() {
+    use crate::CollectionEvent;
+    use std::rc::Rc;
+
+    type List = Cc<RefCell<Vec<Box<dyn Trace>>>>;
+    let space = crate::ObjectSpace::default();
+    let events = Rc::new(RefCell::new(Vec::new()));
+    {
+        let events = events.clone();
+        space.on_collect(move |event| events.borrow_mut().push(event));
+    }
+    let live: List = space.create(Default::default());
+    let a: List = space.create(Default::default());
+    a.borrow_mut().push(Box::new(a.clone()));
+    drop(a);
+    assert_eq!(space.drain_unreachable(), 1);
+    assert_eq!(
+        *events.borrow(),
+        [
+            CollectionEvent::BeforeCollect,
+            CollectionEvent::AfterCollect(crate::CollectionStats {
+                collected: 1,
+                scanned: 2,
+            }),
+        ]
+    );
+
+    // Allocations were reset. The heuristic does not collect again.
+    assert_eq!(space.collect_cycles_heuristic(), 0);
+    assert_eq!(events.borrow().len(), 2);
+    drop(live);
+}
+
+#[test]
+fn test_get_mut() {
+    let mut a = Cc::new(vec![1u32]);
+    let mut b = a.clone();
+    assert!(Cc::get_mut(&mut a).is_none());
+    assert!(Cc::get_mut(&mut b).is_none());
+    drop(b);
+    Cc::get_mut(&mut a).unwrap().push(2);
+    assert_eq!(*a, [1, 2]);
+
+    let weak = a.downgrade();
+    assert!(Cc::get_mut(&mut a).is_none());
+    drop(weak);
+    assert!(Cc::get_mut(&mut a).is_some());
+
+    // Tracked objects can be read by the collector.
+    let mut c: Cc<Vec<Box<dyn Trace>>> = Cc::new(Vec::new());
+    assert!(Cc::get_mut(&mut c).is_none());
+}
+
+#[test]
+fn test_make_mut_tree() {
+    // A persistent tree. Without interior mutability it cannot form
+    // cycles, so it does not need to be tracked.
+    #[derive(Clone)]
+    struct Node {
+        value: u32,
+        children: Vec<Cc<Node>>,
+    }
+    impl Trace for Node {
+        fn trace(&self, tracer: &mut Tracer) {
+            self.children.trace(tracer);
+        }
+        fn is_type_tracked() -> bool {
+            false
+        }
+    }
+    let leaf = |value| {
+        Cc::new(Node {
+            value,
+            children: Vec::new(),
+        })
+    };
+
+    let mut root = Cc::new(Node {
+        value: 0,
+        children: vec![leaf(1), leaf(2)],
+    });
+    let old = root.clone();
+    let node = Cc::make_mut(&mut root);
+    node.value = 10;
+    Cc::make_mut(&mut node.children[1]).value = 20;
+
+    assert_eq!(old.value, 0);
+    assert_eq!(old.children[1].value, 2);
+    assert_eq!(root.value, 10);
+    assert_eq!(root.children[1].value, 20);
+    // Unchanged subtrees are shared.
+    assert!(Cc::ptr_eq(&old.children[0], &root.children[0]));
+    assert!(!Cc::ptr_eq(&old.children[1], &root.children[1]));
+
+    // Not shared anymore: updated in place.
+    let ptr = Cc::as_ptr(&root);
+    Cc::make_mut(&mut root).value = 11;
+    assert_eq!(Cc::as_ptr(&root), ptr);
+
+    drop(old);
+    drop(root);
+    assert_eq!(collect::count_thread_tracked(), 0);
+}
+
+#[test]
+#[should_panic(expected = "make_mut does not support tracked types")]
+fn test_make_mut_tracked() {
+    #[derive(Clone)]
+    struct Tracked;
+    impl Trace for Tracked {}
+    let mut a = Cc::new(Tracked);
+    Cc::make_mut(&mut a);
+}
+
+#[test]
+fn test_try_unwrap_with_weak() {
+    let space = crate::ObjectSpace::default();
+    let a: Cc<RefCell<Vec<Box<dyn Trace>>>> = space.create(Default::default());
+    let weak = a.downgrade();
+    let b = a.clone();
+    let a = Cc::try_unwrap(a).err().unwrap();
+    drop(b);
+
+    let value = Cc::try_unwrap(a).ok().unwrap();
+    assert!(value.borrow().is_empty());
+    assert!(!weak.is_alive());
+    // Kept for the weak reference, but not visited by the collector.
+    assert_eq!(space.count_tracked(), 1);
+    assert_eq!(space.collect_cycles(), 0);
+    drop(weak);
+    assert_eq!(space.count_tracked(), 0);
+}
+
+#[test]
+fn test_forget() {
+    type List = Cc<RefCell<Vec<Box<dyn Trace>>>>;
+    let space = crate::ObjectSpace::default();
+    let a: List = space.create(Default::default());
+    let b: List = space.create(Default::default());
+    b.borrow_mut().push(Box::new(b.clone()));
+    a.borrow_mut().push(Box::new(b));
+
+    let value = space.forget(a);
+    // `b` is kept alive by the forgotten value, which is not tracked.
+    assert_eq!(space.count_tracked(), 1);
+    assert_eq!(space.collect_cycles(), 0);
+    drop(value);
+    assert_eq!(space.collect_cycles(), 1);
+    assert_eq!(space.count_tracked(), 0);
+}
+
+#[test]
+#[should_panic(expected = "forget requires the only strong reference")]
+fn test_forget_shared() {
+    let space = crate::ObjectSpace::default();
+    let a: Cc<RefCell<Vec<Box<dyn Trace>>>> = space.create(Default::default());
+    let _b = a.clone();
+    space.forget(a);
+}
+
+#[test]
+fn test_into_box() {
+    static CLONES: AtomicUsize = AtomicUsize::new(0);
+    struct V(RefCell<Vec<Box<dyn Trace>>>);
+    impl Clone for V {
+        fn clone(&self) -> Self {
+            CLONES.fetch_add(1, SeqCst);
+            V(Default::default())
+        }
+    }
+    impl Trace for V {
+        fn trace(&self, tracer: &mut Tracer) {
+            self.0.trace(tracer);
+        }
+    }
+
+    let space = crate::ObjectSpace::default();
+    let a = space.create(V(Default::default()));
+    let b = a.clone();
+    let weak = a.downgrade();
+    let _: Box<V> = Cc::into_box(a);
+    assert_eq!(CLONES.load(SeqCst), 1);
+    assert_eq!(space.count_tracked(), 1);
+
+    // The only strong reference: moved out without cloning.
+    let _: Box<V> = Cc::into_box(b);
+    assert_eq!(CLONES.load(SeqCst), 1);
+    assert!(weak.upgrade().is_none());
+    drop(weak);
+    assert_eq!(space.count_tracked(), 0);
+}
+
+#[test]
+fn test_is_collecting() {
+    use std::rc::Rc;
+
+    thread_local!(static SEEN: RefCell<Vec<bool>> = const { RefCell::new(Vec::new()) });
+    struct V(RefCell<Vec<Box<dyn Trace>>>, Rc<crate::ObjectSpace>);
+    impl Trace for V {
+        fn trace(&self, tracer: &mut Tracer) {
+            SEEN.with(|s| s.borrow_mut().push(self.1.is_collecting()));
+            self.0.trace(tracer);
+        }
+    }
+    impl Drop for V {
+        fn drop(&mut self) {
+            SEEN.with(|s| s.borrow_mut().push(self.1.is_collecting()));
+        }
+    }
+
+    let space = Rc::new(crate::ObjectSpace::default());
+    assert!(!space.is_collecting());
+    {
+        let a = space.create(V(Default::default(), space.clone()));
+        a.0.borrow_mut().push(Box::new(a.clone()));
+    }
+    assert_eq!(space.count_cycles_estimate(), 1);
+    assert!(SEEN.with(|s| s.take()).iter().all(|c| !c));
+    assert_eq!(space.collect_cycles(), 1);
+    let seen = SEEN.with(|s| s.take());
+    assert!(!seen.is_empty());
+    assert!(seen.iter().all(|c| *c));
+    assert!(!space.is_collecting());
+}
+
+#[test]
+fn test_gc_vec() {
+    use crate::GcVec;
+
+    struct Node(GcVec<Node>);
+    impl Trace for Node {
+        fn trace(&self, tracer: &mut Tracer) {
+            self.0.trace(tracer);
+        }
+    }
+
+    let space = crate::ObjectSpace::default();
+    {
+        let a = space.create(Node(GcVec::new_with_space(&space)));
+        let b = space.create(Node(GcVec::new_with_space(&space)));
+        a.0.push(b.clone());
+        b.0.push(a.clone());
+        assert_eq!(a.0.len(), 1);
+        assert!(Cc::ptr_eq(&a.0.get(0).unwrap(), &b));
+        assert!(a.0.get(1).is_none());
+
+        // Pushing while iterating does not panic.
+        let mut visited = 0;
+        for x in &a.0 {
+            if visited == 0 {
+                a.0.push(x);
+            }
+            visited += 1;
+        }
+        assert_eq!(visited, 2);
+        assert_eq!(a.0.pop().map(|x| Cc::ptr_eq(&x, &b)), Some(true));
+    }
+    assert_eq!(space.count_tracked(), 4);
+    assert_eq!(space.collect_cycles(), 4);
+}
+
+#[test]
+fn test_find_root_objects() {
+    type List = Cc<RefCell<Vec<Box<dyn Trace>>>>;
+    let space = crate::ObjectSpace::default();
+    let new_list = || -> List { space.create(Default::default()) };
+
+    // a -> b <-> c, d <-> e
+    let (a, b, c, d, e) = (new_list(), new_list(), new_list(), new_list(), new_list());
+    a.borrow_mut().push(Box::new(b.clone()));
+    b.borrow_mut().push(Box::new(c.clone()));
+    c.borrow_mut().push(Box::new(b.clone()));
+    d.borrow_mut().push(Box::new(e.clone()));
+    e.borrow_mut().push(Box::new(d.clone()));
+    let a_id = a.object_id();
+    drop((b, c, d, e));
+
+    let roots: Vec<_> = space.find_root_objects().into_iter().map(Some).collect();
+    assert_eq!(roots, [a_id]);
+    assert_eq!(Cc::new(1u8).object_id(), None);
+
+    // Nothing is changed.
+    assert_eq!(space.collect_cycles(), 2);
+    drop(a);
+    assert_eq!(space.collect_cycles(), 2);
+}
+
+#[test]
+fn test_shrink_tracked_list() {
+    type List = Cc<RefCell<Vec<Box<dyn Trace>>>>;
+    let space = crate::ObjectSpace::default();
+    assert_eq!(space.shrink_tracked_list(), 0);
+    let a: List = space.create(Default::default());
+    {
+        let b: List = space.create(Default::default());
+        a.borrow_mut().push(Box::new(b.clone()));
+        b.borrow_mut().push(Box::new(a.clone()));
+    }
+    let _c: List = space.create(Default::default());
+    assert_eq!(space.shrink_tracked_list(), 0);
+    drop(a);
+    assert_eq!(space.collect_cycles(), 2);
+    assert_eq!(space.shrink_tracked_list(), 0);
+    assert_eq!(space.count_tracked(), 1);
+
+    // Break the list temporarily.
+    use crate::collect::Linked;
+    let head = space.list.borrow().next();
+    let prev = unsafe { (*head).prev() };
+    unsafe { (*head).set_prev(std::ptr::null()) };
+    assert_eq!(space.shrink_tracked_list(), 1);
+    unsafe { (*head).set_prev(prev) };
+    assert_eq!(space.shrink_tracked_list(), 0);
+}
+
+#[test]
+fn test_new_copy() {
+    #[derive(Clone, Default)]
+    struct Node(RefCell<Vec<Cc<Node>>>);
+    impl Trace for Node {
+        fn trace(&self, tracer: &mut Tracer) {
+            self.0.trace(tracer);
+        }
+    }
+    impl Deref for Node {
+        type Target = RefCell<Vec<Cc<Node>>>;
+        fn deref(&self) -> &Self::Target {
+            &self.0
+        }
+    }
+
+    let space = crate::ObjectSpace::default();
+    let a = space.create(Node::default());
+    a.borrow_mut().push(a.clone());
+    space.generation_bump();
+    let b = space.create_copy(&a);
+    assert!(!Cc::ptr_eq(&a, &b));
+    assert_ne!(b.generation(), a.generation());
+    assert_eq!(space.count_tracked(), 2);
+    assert_eq!(crate::count_thread_tracked(), 0);
+
+    b.borrow_mut().clear();
+    assert_eq!(a.borrow().len(), 1);
+    b.borrow_mut().push(b.clone());
+    drop((a, b));
+    assert_eq!(space.collect_cycles_full(), 2);
+
+    let c = space.create_copy(&space.create(1u32));
+    assert_eq!(*c, 1);
+
+    let d = Cc::new_copy(&Cc::new(Node::default()));
+    assert_eq!(d.borrow().len(), 0);
+    assert_eq!(crate::count_thread_tracked(), 1);
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "forget requires an object of this ObjectSpace")]
+fn test_forget_other_space() {
+    let space = crate::ObjectSpace::default();
+    let a = Cc::new(RefCell::new(Vec::<Cc<dyn Trace>>::new()));
+    space.forget(a);
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "create_copy requires an object of this ObjectSpace")]
+fn test_create_copy_other_space() {
+    let space = crate::ObjectSpace::default();
+    let a = Cc::new(RefCell::new(Vec::<Cc<dyn Trace>>::new()));
+    space.create_copy(&a);
+}
+
+#[test]
+fn test_new_cyclic_unsafe() {
+    struct Node {
+        this: *const Node,
+    }
+    impl Trace for Node {}
+
+    let tracked = unsafe { Cc::new_cyclic_unsafe(|this| Node { this }) };
+    assert!(std::ptr::eq(tracked.this, &*tracked));
+
+    // Untracked values are allocated without a GcHeader.
+    let untracked = unsafe { Cc::new_cyclic_unsafe(|this: *mut u32| this as usize as u32) };
+    assert_eq!(*untracked, &*untracked as *const u32 as usize as u32);
+    drop((tracked, untracked));
+    assert_eq!(crate::collect_thread_cycles(), 0);
+}
+
+#[test]
+fn test_count_tracked_snapshot() {
+    struct Named;
+    impl Trace for Named {
+        fn type_name() -> &'static str {
+            "Named"
+        }
+    }
+
+    type List = Cc<RefCell<Vec<Box<dyn Trace>>>>;
+    let space = crate::ObjectSpace::default();
+    let _named: Vec<_> = (0..3).map(|_| space.create(Named)).collect();
+    let _lists: Vec<List> = (0..2).map(|_| space.create(Default::default())).collect();
+    // Untracked objects are not counted.
+    let _untracked = (space.create("a".to_string()), space.create(1u32));
+    assert_eq!(
+        space.count_tracked_snapshot(),
+        [
+            ("Named".to_string(), 3),
+            (<RefCell<Vec<Box<dyn Trace>>>>::type_name().to_string(), 2),
+        ]
+    );
+}
+
+#[test]
+fn test_live_bytes() {
+    struct Payload([u64; 8]);
+    impl Trace for Payload {
+        fn is_type_tracked() -> bool {
+            true
+        }
+    }
+
+    let space = crate::ObjectSpace::default();
+    assert_eq!(space.live_bytes(), 0);
+    let objects: Vec<_> = (0..10).map(|_| space.create(Payload([0; 8]))).collect();
+    // Untracked objects are not counted.
+    let _untracked = space.create([0u64; 8]);
+    let bytes = space.live_bytes();
+    assert!(bytes > 10 * 64, "{}", bytes);
+    assert!(bytes <= 10 * (64 + 128), "{}", bytes);
+    assert!(objects.iter().all(|object| object.0 == [0; 8]));
+    drop(objects);
+    assert_eq!(space.live_bytes(), 0);
+}
+
+#[test]
+fn test_cc_fmt_pointer() {
+    let a = Cc::new(0u32);
+    let b = a.clone();
+    assert_eq!(format!("{:p}", a), format!("{:p}", b));
+    assert_eq!(format!("{:p}", a), format!("{:p}", &*a));
+    assert_ne!(format!("{:p}", a), format!("{:p}", Cc::new(0u32)));
+}
+
+#[test]
+fn test_dump_heap() {
+    struct Named(RefCell<Vec<Box<dyn Trace>>>);
+    impl Trace for Named {
+        fn trace(&self, tracer: &mut Tracer) {
+            self.0.trace(tracer);
+        }
+        fn type_name() -> &'static str {
+            "Named<\"quoted\">"
+        }
+    }
+
+    let space = crate::ObjectSpace::default();
+    let mut out = Vec::new();
+    space.dump_heap(&mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "[\n]\n");
+
+    let a = space.create(Named(Default::default()));
+    let b = space.create(Named(Default::default()));
+    a.0.borrow_mut().push(Box::new(b.clone()));
+    // Untracked objects are not reported.
+    a.0.borrow_mut().push(Box::new(space.create(1u32)));
+    let id = |cc: &Cc<Named>| format!("{:#x}", cc.inner() as *const _ as usize);
+    let mut out = Vec::new();
+    space.dump_heap(&mut out).unwrap();
+    // Newer objects are listed first.
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        format!(
+            concat!(
+                "[\n",
+                r#"{{"id":"{b}","type":"Named<\"quoted\">","ref_count":2,"references":[]}},"#,
+                "\n",
+                r#"{{"id":"{a}","type":"Named<\"quoted\">","ref_count":1,"references":["{b}"]}}"#,
+                "\n]\n"
+            ),
+            a = id(&a),
+            b = id(&b)
+        )
+    );
+}
+
+#[test]
+fn test_to_dot() {
+    struct Named(RefCell<Vec<Box<dyn Trace>>>);
+    impl Trace for Named {
+        fn trace(&self, tracer: &mut Tracer) {
+            self.0.trace(tracer);
+        }
+        fn type_name() -> &'static str {
+            "Named<\"quoted\">"
+        }
+    }
+
+    let space = crate::ObjectSpace::default();
+    assert_eq!(space.to_dot(), "digraph {\n}\n");
+
+    let a = space.create(Named(Default::default()));
+    let b = space.create(Named(Default::default()));
+    a.0.borrow_mut().push(Box::new(b.clone()));
+    b.0.borrow_mut().push(Box::new(a.clone()));
+    let id = |cc: &Cc<Named>| format!("{:#x}", cc.inner() as *const _ as usize);
+    // Newer objects are listed first.
+    assert_eq!(
+        space.to_dot(),
+        format!(
+            concat!(
+                "digraph {{\n",
+                "  \"{b}\" [label=\"Named<\\\"quoted\\\">\\n{b}\"];\n",
+                "  \"{b}\" -> \"{a}\";\n",
+                "  \"{a}\" [label=\"Named<\\\"quoted\\\">\\n{a}\"];\n",
+                "  \"{a}\" -> \"{b}\";\n",
+                "}}\n"
+            ),
+            a = id(&a),
+            b = id(&b)
+        )
+    );
+    a.0.borrow_mut().clear();
+}
+
+#[test]
+fn test_verify_integrity() {
+    let space = crate::ObjectSpace::default();
+    crate::testutil::verify_integrity(&space);
+    let a: Cc<RefCell<Vec<Box<dyn Trace>>>> = space.create(Default::default());
+    let b: Cc<RefCell<Vec<Box<dyn Trace>>>> = space.create(Default::default());
+    a.borrow_mut().push(Box::new(b.clone()));
+    b.borrow_mut().push(Box::new(a.clone()));
+    crate::testutil::verify_integrity(&space);
+    drop(a);
+    assert_eq!(space.collect_cycles(), 0);
+    crate::testutil::verify_integrity(&space);
+    drop(b);
+    assert_eq!(space.collect_cycles(), 2);
+    crate::testutil::verify_integrity(&space);
+}
+
+#[test]
+fn test_compare_and_swap_ptr() {
+    let a = Cc::new(1);
+    let b = Cc::new(1);
+    let cell = Cell::new(a.clone());
+    assert_eq!(a.strong_count(), 2);
+
+    // Equal values in different allocations do not match.
+    let c = Cc::new(2);
+    assert!(!Cc::compare_and_swap_ptr(&cell, &b, c.clone()));
+    assert_eq!(c.strong_count(), 1);
+    assert_eq!(a.strong_count(), 2);
+
+    assert!(Cc::compare_and_swap_ptr(&cell, &a, c.clone()));
+    assert_eq!(c.strong_count(), 2);
+    assert_eq!(a.strong_count(), 1);
+    assert!(Cc::ptr_eq(&cell.into_inner(), &c));
+}
+
+#[test]
+fn test_adopt() {
+    type List = Cc<RefCell<Vec<Box<dyn Trace>>>>;
+    let space1 = crate::ObjectSpace::default();
+    let space2 = crate::ObjectSpace::default();
+    let a: List = space1.create(Default::default());
+    let b: List = space2.create(Default::default());
+
+    let a = space2.adopt(a);
+    assert_eq!(space1.count_tracked(), 0);
+    assert_eq!(space2.count_tracked(), 2);
+
+    // Untracked objects are returned as-is.
+    let v = space2.adopt(space1.create(1u32));
+    assert_eq!(*v, 1);
+
+    a.borrow_mut().push(Box::new(b.clone()));
+    b.borrow_mut().push(Box::new(a.clone()));
+    drop((a, b));
+    assert_eq!(space1.collect_cycles(), 0);
+    assert_eq!(space2.collect_cycles(), 2);
+}
+
+#[test]
+#[should_panic(expected = "adopt requires the only strong reference")]
+fn test_adopt_shared() {
+    let space1 = crate::ObjectSpace::default();
+    let space2 = crate::ObjectSpace::default();
+    let a: Cc<RefCell<Vec<Box<dyn Trace>>>> = space1.create(Default::default());
+    let _b = a.clone();
+    space2.adopt(a);
+}
+
+#[test]
+fn test_tracer_visit_count() {
+    type List = Cc<RefCell<Vec<Box<dyn Trace>>>>;
+    let a: List = Default::default();
+    let b: List = Default::default();
+    a.borrow_mut().push(Box::new(b.clone()));
+    a.borrow_mut().push(Box::new(b.clone()));
+    // The untracked `Cc<u8>` is not visited.
+    a.borrow_mut().push(Box::new(Cc::new(1u8)));
+
+    let mut visit = |_| {};
+    let mut tracer = Tracer::new(&mut visit).with_visit_limit(2);
+    assert_eq!(tracer.visit_count(), 0);
+    a.borrow().trace(&mut tracer);
+    assert_eq!(tracer.visit_count(), 2);
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "visited more than 1 edges")]
+fn test_tracer_visit_limit() {
+    let value: Vec<Box<dyn Trace>> = vec![
+        Box::new(Cc::new(RefCell::new(None::<Box<dyn Trace>>))),
+        Box::new(Cc::new(RefCell::new(None::<Box<dyn Trace>>))),
+    ];
+    let mut visit = |_| {};
+    value.trace(&mut Tracer::new(&mut visit).with_visit_limit(1));
+}
+
+#[test]
+fn test_collection_policy() {
+    use crate::{CollectionPolicy, DropOrder, Finalize};
+    use std::rc::Rc;
+
+    struct Node {
+        name: &'static str,
+        next: RefCell<Vec<Cc<Node>>>,
+        dropped: Rc<RefCell<Vec<&'static str>>>,
+        finalize: bool,
+    }
+    impl Trace for Node {
+        fn trace(&self, tracer: &mut Tracer) {
+            self.next.trace(tracer);
+        }
+        fn as_finalize(&self) -> Option<&dyn Finalize> {
+            if self.finalize {
+                Some(self)
+            } else {
+                None
+            }
+        }
+    }
+    impl Finalize for Node {
+        fn finalize(&self) {}
+    }
+    impl Drop for Node {
+        fn drop(&mut self) {
+            self.dropped.borrow_mut().push(self.name);
+        }
+    }
+
+    let dropped = Rc::new(RefCell::new(Vec::new()));
+    // a <-> b -> c -> d
+    let create_graph = |space: &crate::ObjectSpace, finalize: bool| {
+        let new_node = |name| {
+            space.create(Node {
+                name,
+                next: Default::default(),
+                dropped: dropped.clone(),
+                finalize,
+            })
+        };
+        // Newer objects come first in the list, and are dropped first by
+        // `DropOrder::Arbitrary`.
+        let (d, c, b, a) = (new_node("d"), new_node("c"), new_node("b"), new_node("a"));
+        c.next.borrow_mut().push(d);
+        b.next.borrow_mut().push(c);
+        b.next.borrow_mut().push(a.clone());
+        a.next.borrow_mut().push(b);
+    };
+
+    let space = crate::ObjectSpace::with_collection_policy(CollectionPolicy {
+        min_tracked_to_collect: 5,
+        drop_order: DropOrder::TopologicalChildrenFirst,
+        ..Default::default()
+    });
+    create_graph(&space, false);
+    assert_eq!(space.collect_cycles(), 0);
+    let _e = space.create(RefCell::new(None::<Box<dyn Trace>>));
+    assert_eq!(space.collect_cycles(), 4);
+    let order = dropped.take();
+    assert_eq!(order[..2], ["d", "c"]);
+
+    // Finalizers run in the first pass. Objects are dropped in the next
+    // collection.
+    let space = crate::ObjectSpace::with_collection_policy(CollectionPolicy {
+        max_passes: 1,
+        ..Default::default()
+    });
+    create_graph(&space, true);
+    assert_eq!(space.collect_cycles(), 0);
+    assert_eq!(space.collect_cycles(), 4);
+}
+
+#[test]
+fn test_collect_cycles_heuristic() {
+    type Node = Cc<RefCell<Option<Box<dyn Trace>>>>;
+    let space = collect::ObjectSpace::default();
+    let create_cycle = || {
+        let a: Node = space.create(RefCell::new(None));
+        *a.borrow_mut() = Some(Box::new(a.clone()));
+    };
+
+    let live: Vec<Node> = (0..4).map(|_| space.create(RefCell::new(None))).collect();
+    assert_eq!(space.collect_cycles_heuristic(), 0);
+
+    // 1 of 5 objects is new. Below the default threshold 0.25.
+    create_cycle();
+    assert_eq!(space.collect_cycles_heuristic(), 0);
+    assert_eq!(space.count_tracked(), 5);
+
+    // 2 of 6 objects are new.
+    create_cycle();
+    assert_eq!(space.collect_cycles_heuristic(), 2);
+    assert_eq!(space.count_tracked(), 4);
+
+    // Explicit collections reset the counter.
+    create_cycle();
+    create_cycle();
+    assert_eq!(space.collect_cycles(), 2);
+    assert_eq!(space.collect_cycles_heuristic(), 0);
+    drop(live);
+}
+
+#[test]
+fn test_on_collect() {
+    use crate::CollectionEvent;
+    use std::rc::Rc;
+
+    let space = crate::ObjectSpace::default();
+    let events = Rc::new(RefCell::new(Vec::new()));
+    for name in ["first", "second"] {
+        let events = events.clone();
+        space.on_collect(move |event| events.borrow_mut().push((name, event)));
+    }
+    let a: Cc<RefCell<Vec<Box<dyn Trace>>>> = space.create(Default::default());
+    a.borrow_mut().push(Box::new(a.clone()));
+    drop(a);
+    assert_eq!(space.collect_cycles(), 1);
+    let stats = crate::CollectionStats {
+        collected: 1,
+        scanned: 1,
+    };
+    assert_eq!(
+        *events.borrow(),
+        [
+            ("first", CollectionEvent::BeforeCollect),
+            ("second", CollectionEvent::BeforeCollect),
+            ("first", CollectionEvent::AfterCollect(stats)),
+            ("second", CollectionEvent::AfterCollect(stats)),
+        ]
+    );
+
+    // Nothing is tracked. Hooks are not called.
+    assert_eq!(space.collect_cycles(), 0);
+    assert_eq!(events.borrow().len(), 4);
+}
+
+#[test]
+fn test_for_each_tracked() {
+    type List = RefCell<Vec<Box<dyn Trace>>>;
+    struct Leaf(Box<u8>);
+    impl Trace for Leaf {
+        fn is_type_tracked() -> bool {
+            true
+        }
+    }
+
+    let space = collect::ObjectSpace::default();
+    let a: Cc<List> = space.create(Default::default());
+    let b: Cc<List> = space.create(Default::default());
+    a.borrow_mut().push(Box::new(b.clone()));
+    b.borrow_mut()
+        .push(Box::new(space.create(Leaf(Box::new(3)))));
+    // Untracked.
+    let _c = space.create(1u32);
+
+    let mut lists = 0;
+    let mut leaves = Vec::new();
+    space.for_each_tracked(|value| {
+        if value.is::<List>() {
+            lists += 1;
+        } else if let Some(leaf) = value.downcast_ref::<Leaf>() {
+            leaves.push(*leaf.0);
+        } else {
+            panic!("unexpected type");
+        }
+    });
+    assert_eq!(lists, 2);
+    assert_eq!(leaves, [3]);
+
+    // Dropping objects while visiting is fine.
+    let mut visited = 0;
+    let mut b = Some(b);
+    space.for_each_tracked(|_| {
+        b.take();
+        a.borrow_mut().clear();
+        visited += 1;
+    });
+    assert_eq!(visited, 3);
+    assert_eq!(space.count_tracked(), 1);
+}
+
+#[test]
+fn test_gc_threshold() {
+    type Node = Cc<RefCell<Option<Box<dyn Trace>>>>;
+    let space = collect::ObjectSpace::with_collection_policy(collect::CollectionPolicy {
+        auto_collect_threshold: Some(3),
+        ..Default::default()
+    });
+    let create_cycle = || {
+        let a: Node = space.create(RefCell::new(None));
+        *a.borrow_mut() = Some(Box::new(a.clone()));
+    };
+
+    create_cycle();
+    create_cycle();
+    create_cycle();
+    assert_eq!(space.count_tracked(), 3);
+
+    // The 4th object is created after collecting the first 3.
+    let live: Node = space.create(RefCell::new(None));
+    assert_eq!(space.count_tracked(), 1);
+
+    create_cycle();
+    create_cycle();
+    assert_eq!(space.count_tracked(), 3);
+    create_cycle();
+    assert_eq!(space.count_tracked(), 2);
+    drop(live);
+}
+
+#[test]
+#[cfg(feature = "allocator-api")]
+fn test_create_in_allocator() {
+    use std::alloc::{AllocError, Allocator, Global, Layout};
+    use std::ptr::NonNull;
+    use std::rc::Rc;
+
+    #[derive(Clone, Default)]
+    struct Counting(Rc<Cell<isize>>);
+    unsafe impl Allocator for Counting {
+        fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+            self.0.set(self.0.get() + 1);
+            Global.allocate(layout)
+        }
+        unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+            self.0.set(self.0.get() - 1);
+            Global.deallocate(ptr, layout)
+        }
+    }
+
+    type List<A = Global> = Cc<RefCell<Vec<Box<dyn Trace>>>, A>;
+    let allocator = Counting::default();
+    let space = crate::ObjectSpace::default();
+    {
+        let a: List<Counting> = space.create_in(Default::default(), allocator.clone());
+        let b: List = space.create(Default::default());
+        let v = space.create_in(1u32, allocator.clone());
+        assert_eq!(allocator.0.get(), 2);
+        a.borrow_mut().push(Box::new(b.clone()));
+        b.borrow_mut().push(Box::new(a.clone()));
+        a.borrow_mut().push(Box::new(v));
+    }
+    assert_eq!(space.collect_cycles(), 2);
+    assert_eq!(allocator.0.get(), 0);
+
+    let weak = Cc::new_in(5u32, allocator.clone()).downgrade();
+    assert_eq!(allocator.0.get(), 1);
+    drop(weak);
+    assert_eq!(allocator.0.get(), 0);
+}
+
+#[test]
+#[cfg(feature = "track-rc")]
+fn test_track_rc_cycle() {
+    use std::rc::Rc;
+
+    type List = Cc<RefCell<Vec<Rc<Box<dyn Trace>>>>>;
+    let space = crate::ObjectSpace::default();
+    {
+        let a: List = space.create(Default::default());
+        a.borrow_mut().push(Rc::new(Box::new(a.clone())));
+    }
+    assert_eq!(space.collect_cycles(), 1);
+
+    // A shared `Rc` is not traced, so the cycle is kept alive.
+    let a: List = space.create(Default::default());
+    let shared = Rc::new(Box::new(a.clone()) as Box<dyn Trace>);
+    a.borrow_mut().push(shared.clone());
+    drop(a);
+    assert_eq!(space.collect_cycles(), 0);
+    drop(shared);
+    assert_eq!(space.collect_cycles(), 1);
+}
@@ -1,12 +1,13 @@
 //! Test utilities.
 
-use crate::{collect, debug, Cc, Trace, Tracer};
+use crate::collect::Linked;
+use crate::{collect, debug, Cc, ObjectSpace, Trace, Tracer};
 use std::cell::Cell;
 use std::cell::RefCell;
 use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
 use std::sync::Arc;
 
-thread_local!(static NEXT_TRACKED_OVERRIDE: Cell<bool> = Cell::new(true));
+thread_local!(static NEXT_TRACKED_OVERRIDE: Cell<bool> = const { Cell::new(true) });
 
 /// Track count of drop(). Store result in AtomicUsize.
 /// The bool value controls whether this type is tracked.
@@ -25,11 +26,13 @@ impl<T> Drop for DropCounter<T> {
     }
 }
 
+type TestObject = Cc<DropCounter<RefCell<Vec<Box<dyn Trace>>>>>;
+
 pub(crate) fn create_objects(
     n: usize,
     atomic_bits: u16,
     drop_count: Arc<AtomicUsize>,
-) -> Vec<Cc<DropCounter<RefCell<Vec<Box<dyn Trace>>>>>> {
+) -> Vec<TestObject> {
     assert!(n <= 16);
     let is_tracked = |n| -> bool { (atomic_bits >> n) & 1 == 0 };
     (0..n)
@@ -103,3 +106,20 @@ pub fn test_small_graph(n: usize, edges: &[u8], atomic_bits: u16, collect_bits:
         edge_descs,
     );
 }
+
+/// Check that the linked list of `space` is well-formed: `prev` and `next`
+/// pointers agree, and no collector state is left in them.
+pub fn verify_integrity(space: &ObjectSpace) {
+    let list: &collect::GcHeader = &space.list.borrow();
+    let mut prev: *const collect::GcHeader = list;
+    collect::visit_list(list, |header| {
+        assert_eq!(header.prev(), prev, "bug: prev pointer is inconsistent");
+        assert!(!header.next().is_null(), "bug: tracked object is unlinked");
+        prev = header;
+    });
+    assert_eq!(
+        list.prev(),
+        prev,
+        "bug: prev pointer of the list head is inconsistent"
+    );
+}
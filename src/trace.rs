@@ -1,6 +1,168 @@
-/// Callback function that serves as the parameter of
+use crate::cc::CcAllocator;
+use crate::cc::RawCc;
+use crate::collect::AbstractObjectSpace;
+use crate::debug;
+use std::any::TypeId;
+
+/// Visitor that serves as the parameter of
 /// [`Trace::trace`](trait.Trace.html#method.trace).
-pub type Tracer<'a> = dyn FnMut(*const ()) + 'a;
+///
+/// It receives the `Cc<T>` edges of the object graph.
+///
+/// Before 0.4, this was an alias of `dyn FnMut(*const ())`. `Trace`
+/// implementations that forward to fields, like `self.x.trace(tracer)`, work
+/// unchanged. Code passing a closure as the tracer should wrap it by
+/// [`Tracer::new`](#method.new).
+pub struct Tracer<'a> {
+    visit: &'a mut dyn FnMut(*const ()),
+    mode: TracerMode,
+    depth: usize,
+    depth_limit: Option<usize>,
+    visit_count: usize,
+    visit_limit: Option<usize>,
+    header_type: Option<TypeId>,
+}
+
+/// Why a [`Tracer`](struct.Tracer.html) is visiting objects.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TracerMode {
+    /// Collecting cycles. Unreachable objects will be dropped.
+    Collect,
+    /// Running the collection algorithm without dropping anything.
+    DryRun,
+    /// Inspecting the object graph, for example, to report cycles.
+    Inspect,
+}
+
+impl<'a> Tracer<'a> {
+    /// Constructs a [`Tracer`](struct.Tracer.html) that calls `visit` with
+    /// the address of the collector metadata of each visited tracked object.
+    pub fn new(visit: &'a mut dyn FnMut(*const ())) -> Self {
+        Self::with_mode(visit, TracerMode::Collect)
+    }
+
+    /// Like [`new`](#method.new), with an explicit [`TracerMode`](enum.TracerMode.html).
+    pub fn with_mode(visit: &'a mut dyn FnMut(*const ()), mode: TracerMode) -> Self {
+        Self {
+            visit,
+            mode,
+            depth: 0,
+            depth_limit: None,
+            visit_count: 0,
+            visit_limit: None,
+            header_type: None,
+        }
+    }
+
+    /// Cap the nesting of [`trace_nested`](#method.trace_nested) calls at
+    /// `limit`.
+    ///
+    /// Values nested deeper are not traced. The `Cc<T>` objects they refer
+    /// to are treated as alive, so cycles going through them can leak.
+    /// This trades possible leaks for not overflowing the stack on very
+    /// deep structures.
+    ///
+    /// The collector traces each object with a limit of 1000.
+    pub fn with_depth_limit(mut self, limit: usize) -> Self {
+        self.depth_limit = Some(limit);
+        self
+    }
+
+    /// Expect at most `limit` visited `Cc<T>` edges.
+    ///
+    /// Visiting more panics on debug builds, which helps to catch runaway
+    /// `Trace` implementations. Release builds do not check the limit.
+    pub fn with_visit_limit(mut self, limit: usize) -> Self {
+        self.visit_limit = Some(limit);
+        self
+    }
+
+    /// Only visit objects whose collector metadata is of type `H`, that is,
+    /// objects of the object spaces using `H`. Metadata of other object
+    /// spaces has a different layout.
+    pub(crate) fn with_header_type<H: 'static>(mut self) -> Self {
+        self.header_type = Some(TypeId::of::<H>());
+        self
+    }
+
+    /// Whether objects with collector metadata of type `H` are visited.
+    #[inline]
+    pub(crate) fn visits_header<H: 'static>(&self) -> bool {
+        self.header_type.map_or(true, |t| t == TypeId::of::<H>())
+    }
+
+    /// Number of `Cc<T>` edges visited by this tracer so far.
+    ///
+    /// Untracked objects are not counted, since they are not visited.
+    pub fn visit_count(&self) -> usize {
+        self.visit_count
+    }
+
+    /// Current nesting level of [`trace_nested`](#method.trace_nested).
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Trace a value one nesting level deeper.
+    ///
+    /// `Trace` implementations of recursive structures, like `Box<T>`, use
+    /// this instead of calling `value.trace(tracer)` directly so the depth
+    /// limit can be enforced.
+    pub fn trace_nested<T: Trace + ?Sized>(&mut self, value: &T) {
+        if let Some(limit) = self.depth_limit {
+            if self.depth >= limit {
+                debug::log(|| ("tracer", format!("depth limit {} reached", limit)));
+                return;
+            }
+        }
+        self.depth += 1;
+        value.trace(self);
+        self.depth -= 1;
+    }
+
+    /// Why this [`Tracer`](struct.Tracer.html) is visiting objects.
+    pub fn mode(&self) -> TracerMode {
+        self.mode
+    }
+
+    /// Whether this is a dry-run pass that does not drop objects.
+    ///
+    /// `Trace` implementations with expensive side effects can skip them
+    /// in this case.
+    pub fn is_dry_run(&self) -> bool {
+        self.mode == TracerMode::DryRun
+    }
+
+    /// Whether this pass only inspects the object graph, for example, for
+    /// [`ObjectSpace::find_strongly_connected_components`](struct.ObjectSpace.html#method.find_strongly_connected_components).
+    pub fn is_inspecting(&self) -> bool {
+        self.mode == TracerMode::Inspect
+    }
+
+    /// Visit a `Cc<T>` edge. This is what `Cc::trace` does.
+    ///
+    /// Untracked objects are skipped, since they cannot form cycles.
+    pub fn trace_ptr<T: ?Sized, O: AbstractObjectSpace, A: CcAllocator>(
+        &mut self,
+        ptr: &RawCc<T, O, A>,
+    ) {
+        ptr.inner().trace_t(self);
+    }
+
+    /// Report the metadata address of a tracked object.
+    #[inline]
+    pub(crate) fn visit_header(&mut self, header: *const ()) {
+        self.visit_count += 1;
+        if let Some(limit) = self.visit_limit {
+            debug_assert!(
+                self.visit_count <= limit,
+                "bug: visited more than {} edges (is Trace implemented correctly?)",
+                limit
+            );
+        }
+        (self.visit)(header)
+    }
+}
 
 /// Defines how the cycle collector should collect a type.
 ///
@@ -34,6 +196,7 @@ pub trait Trace: 'static {
     ///
     /// Ideally this can be generated by the compiler, since the
     /// compiler already knows how to generate `Drop::drop`.
+    #[inline(always)]
     fn trace(&self, tracer: &mut Tracer) {
         let _ = tracer;
     }
@@ -44,7 +207,7 @@ pub trait Trace: 'static {
     /// be tracked. This allows the collector to visit the `Cc` values from
     /// its parents and count references correctly.
     ///
-    /// If a type `T` is tracked, `Cc<T>` will be 3 `usize` larger and the
+    /// If a type `T` is tracked, `Cc<T>` will be 4 `usize` larger and the
     /// collector will check them.
     ///
     /// For example,
@@ -75,4 +238,72 @@ pub trait Trace: 'static {
         // Fallback implementation: Opt-in the collector for correctness.
         true
     }
+
+    /// Name of this type, used to describe tracked objects.
+    ///
+    /// The default implementation uses `std::any::type_name`. Override it
+    /// to provide a more readable name, for example, for closure types.
+    fn type_name() -> &'static str
+    where
+        Self: Sized,
+    {
+        std::any::type_name::<Self>()
+    }
+
+    /// The [`Finalize`](trait.Finalize.html) implementation of this value,
+    /// if any.
+    ///
+    /// Types implementing `Finalize` should return `Some(self)`. The default
+    /// implementation returns `None`, meaning no finalizer. This is called
+    /// while the collector is running, and should not do anything else.
+    fn as_finalize(&self) -> Option<&dyn Finalize> {
+        None
+    }
+}
+
+/// [`Trace::is_type_tracked`](trait.Trace.html#method.is_type_tracked)
+/// known at compile time.
+///
+/// This is a separate trait since associated constants would make
+/// `dyn Trace` impossible. It is implemented by
+/// [`trace_acyclic!`](macro.trace_acyclic.html), and by
+/// [`trace_fields!`](macro.trace_fields.html) if all type parameters
+/// implement it. `IS_TYPE_TRACKED` must match `is_type_tracked()`.
+///
+/// ```
+/// use jrsonnet_gcmodule::{trace_fields, Trace, TypeTracked};
+///
+/// struct Foo<T>(T, String);
+/// trace_fields!(Foo<T> { 0: T, 1 });
+///
+/// const _: () = assert!(!Foo::<u32>::IS_TYPE_TRACKED);
+/// assert!(!Foo::<u32>::is_type_tracked());
+/// ```
+pub trait TypeTracked: Trace {
+    /// Whether this type should be tracked by the collector.
+    const IS_TYPE_TRACKED: bool;
+}
+
+/// Cleanup that runs before the collector drops an object.
+///
+/// When the collector finds an unreachable object that has a finalizer (see
+/// [`Trace::as_finalize`](trait.Trace.html#method.as_finalize)), it calls
+/// `finalize` before dropping anything in the cycle. Unlike `Drop::drop`,
+/// `finalize` can still dereference other `Cc<T>` objects safely, for
+/// example, to remove `self` from a table of weak references.
+///
+/// `finalize` runs at most once per object, even if the object is collected
+/// later than the first time it was found unreachable.
+///
+/// ## Resurrection
+///
+/// `finalize` takes `&self` since other objects in the cycle can still refer
+/// to `self`. It can clone a `Cc<T>` of the cycle and store it somewhere
+/// reachable. The collector checks reachability again after running
+/// finalizers, so resurrected objects, and objects they refer to, survive
+/// with their reference counts incremented. Their finalizers will not run
+/// again.
+pub trait Finalize {
+    /// Run cleanup logic. See the [trait-level documentation](trait.Finalize.html).
+    fn finalize(&self);
 }
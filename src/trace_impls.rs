@@ -19,13 +19,43 @@ use crate::trace::{Trace, Tracer};
 /// trace_acyclic!(Y);
 /// trace_acyclic!(<T> Z<T>);
 /// ```
+///
+/// Trait objects are supported, one per invocation:
+///
+/// ```
+/// use jrsonnet_gcmodule::trace_acyclic;
+///
+/// trait Callback {}
+///
+/// trace_acyclic!(dyn Callback + Send);
+/// ```
 #[macro_export]
 macro_rules! trace_acyclic {
+    // `is_type_tracked` requires `Sized`, so it is not implemented for
+    // trait objects.
+    ( <$( $g:ident ),*> dyn $( $t: tt )+ ) => {
+        impl<$( $g: 'static ),*> $crate::Trace for dyn $($t)+ {
+            #[inline(always)]
+            fn trace(&self, _tracer: &mut $crate::Tracer) {}
+        }
+        impl<$( $g: 'static ),*> $crate::TypeTracked for dyn $($t)+ {
+            const IS_TYPE_TRACKED: bool = false;
+        }
+    };
+    ( dyn $( $t: tt )+ ) => {
+        trace_acyclic!(<> dyn $($t)+);
+    };
     ( <$( $g:ident ),*> $( $t: tt )* ) => {
         impl<$( $g: 'static ),*> $crate::Trace for $($t)* {
-            #[inline]
+            #[inline(always)]
+            fn trace(&self, _tracer: &mut $crate::Tracer) {}
+
+            #[inline(always)]
             fn is_type_tracked() -> bool where Self: Sized { false }
         }
+        impl<$( $g: 'static ),*> $crate::TypeTracked for $($t)* {
+            const IS_TYPE_TRACKED: bool = false;
+        }
     };
     ( $( $t: ty ),* ) => {
         $( trace_acyclic!(<> $t); )*
@@ -65,13 +95,17 @@ macro_rules! trace_fields {
                     false
                 }
             }
+            impl< $( $( $tp: $crate::TypeTracked )? ),* > $crate::TypeTracked for $type {
+                const IS_TYPE_TRACKED: bool =
+                    false $( $( || <$tp as $crate::TypeTracked>::IS_TYPE_TRACKED )? )*;
+            }
         )*
     };
 }
 
 trace_acyclic!(bool, char, f32, f64, i16, i32, i64, i8, isize, u16, u32, u64, u8, usize);
 trace_acyclic!(());
-trace_acyclic!(String, &'static str);
+trace_acyclic!(String, &'static str, Box<str>);
 
 mod tuples {
     trace_fields!(
@@ -82,6 +116,56 @@ mod tuples {
         (A, B, C, D, E,) { 0: A, 1: B, 2: C, 3: D, 4: E }
         (A, B, C, D, E, F,) { 0: A, 1: B, 2: C, 3: D, 4: E, 5: F }
         (A, B, C, D, E, F, G,) { 0: A, 1: B, 2: C, 3: D, 4: E, 5: F, 6: G }
+        (A, B, C, D, E, F, G, H,) { 0: A, 1: B, 2: C, 3: D, 4: E, 5: F, 6: G, 7: H }
+        (A, B, C, D, E, F, G, H, I,) { 0: A, 1: B, 2: C, 3: D, 4: E, 5: F, 6: G, 7: H, 8: I }
+        (A, B, C, D, E, F, G, H, I, J,) { 0: A, 1: B, 2: C, 3: D, 4: E, 5: F, 6: G, 7: H, 8: I, 9: J }
+        (A, B, C, D, E, F, G, H, I, J, K,) { 0: A, 1: B, 2: C, 3: D, 4: E, 5: F, 6: G, 7: H, 8: I, 9: J, 10: K }
+        (A, B, C, D, E, F, G, H, I, J, K, L,) { 0: A, 1: B, 2: C, 3: D, 4: E, 5: F, 6: G, 7: H, 8: I, 9: J, 10: K, 11: L }
+    );
+}
+
+mod array {
+    use super::*;
+
+    // `is_type_tracked` requires `Sized`. Containers of `[T]` decide
+    // tracking from `T`.
+    impl<T: Trace> Trace for [T] {
+        fn trace(&self, tracer: &mut Tracer) {
+            for t in self {
+                t.trace(tracer);
+            }
+        }
+    }
+
+    impl<T: Trace, const N: usize> Trace for [T; N] {
+        fn trace(&self, tracer: &mut Tracer) {
+            for t in self {
+                t.trace(tracer);
+            }
+        }
+
+        #[inline]
+        fn is_type_tracked() -> bool {
+            T::is_type_tracked()
+        }
+    }
+}
+
+mod atomic {
+    use std::sync::atomic::*;
+
+    trace_acyclic!(
+        AtomicBool,
+        AtomicI8,
+        AtomicU8,
+        AtomicI16,
+        AtomicU16,
+        AtomicI32,
+        AtomicU32,
+        AtomicI64,
+        AtomicU64,
+        AtomicIsize,
+        AtomicUsize
     );
 }
 
@@ -111,7 +195,7 @@ mod boxed {
 
     impl<T: Trace> Trace for Box<T> {
         fn trace(&self, tracer: &mut Tracer) {
-            self.as_ref().trace(tracer);
+            tracer.trace_nested(self.as_ref());
         }
 
         #[inline]
@@ -122,7 +206,7 @@ mod boxed {
 
     impl Trace for Box<dyn Trace> {
         fn trace(&self, tracer: &mut Tracer) {
-            self.as_ref().trace(tracer);
+            tracer.trace_nested(self.as_ref());
         }
 
         #[inline]
@@ -134,7 +218,7 @@ mod boxed {
 
     impl Trace for Box<dyn Trace + Send> {
         fn trace(&self, tracer: &mut Tracer) {
-            self.as_ref().trace(tracer);
+            tracer.trace_nested(self.as_ref());
         }
 
         #[inline]
@@ -145,7 +229,7 @@ mod boxed {
 
     impl Trace for Box<dyn Trace + Send + Sync> {
         fn trace(&self, tracer: &mut Tracer) {
-            self.as_ref().trace(tracer);
+            tracer.trace_nested(self.as_ref());
         }
 
         #[inline]
@@ -153,12 +237,28 @@ mod boxed {
             true
         }
     }
+
+    impl<T: Trace> Trace for Box<[T]> {
+        fn trace(&self, tracer: &mut Tracer) {
+            for t in self.iter() {
+                t.trace(tracer);
+            }
+        }
+
+        #[inline]
+        fn is_type_tracked() -> bool {
+            T::is_type_tracked()
+        }
+    }
 }
 
 mod cell {
     use super::*;
     use std::cell;
 
+    // See `LazyLock` for why the value is not traced.
+    trace_acyclic!(<T, F> cell::LazyCell<T, F>);
+
     impl<T: Copy + Trace> Trace for cell::Cell<T> {
         fn trace(&self, tracer: &mut Tracer) {
             self.get().trace(tracer);
@@ -207,6 +307,19 @@ mod collections {
     use std::collections;
     use std::hash;
 
+    impl<T: Ord + Trace> Trace for collections::BinaryHeap<T> {
+        fn trace(&self, tracer: &mut Tracer) {
+            for t in self {
+                t.trace(tracer);
+            }
+        }
+
+        #[inline]
+        fn is_type_tracked() -> bool {
+            T::is_type_tracked()
+        }
+    }
+
     impl<K: Trace, V: Trace> Trace for collections::BTreeMap<K, V> {
         fn trace(&self, tracer: &mut Tracer) {
             for (k, v) in self {
@@ -221,6 +334,19 @@ mod collections {
         }
     }
 
+    impl<T: Trace> Trace for collections::BTreeSet<T> {
+        fn trace(&self, tracer: &mut Tracer) {
+            for t in self {
+                t.trace(tracer);
+            }
+        }
+
+        #[inline]
+        fn is_type_tracked() -> bool {
+            T::is_type_tracked()
+        }
+    }
+
     impl<K: Eq + hash::Hash + Trace, V: Trace> Trace for collections::HashMap<K, V> {
         fn trace(&self, tracer: &mut Tracer) {
             for (k, v) in self {
@@ -235,6 +361,19 @@ mod collections {
         }
     }
 
+    impl<T: Eq + hash::Hash + Trace> Trace for collections::HashSet<T> {
+        fn trace(&self, tracer: &mut Tracer) {
+            for t in self {
+                t.trace(tracer);
+            }
+        }
+
+        #[inline]
+        fn is_type_tracked() -> bool {
+            T::is_type_tracked()
+        }
+    }
+
     impl<T: Trace> Trace for collections::LinkedList<T> {
         fn trace(&self, tracer: &mut Tracer) {
             for t in self {
@@ -343,6 +482,26 @@ mod net {
     );
 }
 
+mod nonzero {
+    use std::num::*;
+
+    trace_acyclic!(
+        NonZeroU8,
+        NonZeroU16,
+        NonZeroU32,
+        NonZeroU64,
+        NonZeroU128,
+        NonZeroUsize,
+        NonZeroI8,
+        NonZeroI16,
+        NonZeroI32,
+        NonZeroI64,
+        NonZeroI128,
+        NonZeroIsize,
+        TryFromIntError
+    );
+}
+
 mod option {
     use super::*;
 
@@ -359,6 +518,59 @@ mod option {
     }
 }
 
+#[cfg(feature = "parking_lot")]
+mod parking_lot {
+    use super::*;
+    use ::parking_lot::{Mutex, ReentrantMutex, ReentrantMutexGuard, RwLock};
+
+    impl<T: Trace> Trace for Mutex<T> {
+        fn trace(&self, tracer: &mut Tracer) {
+            // See `sync::Mutex` for why locking is optional.
+            if let Some(x) = self.try_lock() {
+                x.trace(tracer);
+            }
+        }
+
+        #[inline]
+        fn is_type_tracked() -> bool {
+            T::is_type_tracked()
+        }
+    }
+
+    impl<T: Trace> Trace for RwLock<T> {
+        fn trace(&self, tracer: &mut Tracer) {
+            // See `sync::RwLock`.
+            if let Some(x) = self.try_read() {
+                x.trace(tracer);
+            }
+        }
+
+        #[inline]
+        fn is_type_tracked() -> bool {
+            T::is_type_tracked()
+        }
+    }
+
+    impl<T: Trace> Trace for ReentrantMutex<T> {
+        fn trace(&self, tracer: &mut Tracer) {
+            // See `sync::Mutex` for why locking is optional. `try_lock`
+            // succeeds if the current thread holds the lock, which is fine
+            // since the lock only hands out shared references.
+            if let Some(x) = self.try_lock() {
+                T::trace(&x, tracer);
+            }
+        }
+
+        #[inline]
+        fn is_type_tracked() -> bool {
+            T::is_type_tracked()
+        }
+    }
+
+    // `Trace` requires `'static`, so only `'static` guards are covered.
+    trace_acyclic!(<T> ReentrantMutexGuard<'static, T>);
+}
+
 mod path {
     use std::path;
 
@@ -380,9 +592,59 @@ mod process {
     );
 }
 
+mod range {
+    use super::*;
+    use std::ops;
+
+    impl<T: Trace> Trace for ops::Range<T> {
+        fn trace(&self, tracer: &mut Tracer) {
+            self.start.trace(tracer);
+            self.end.trace(tracer);
+        }
+
+        #[inline]
+        fn is_type_tracked() -> bool {
+            T::is_type_tracked()
+        }
+    }
+
+    impl<T: Trace> Trace for ops::RangeInclusive<T> {
+        fn trace(&self, tracer: &mut Tracer) {
+            self.start().trace(tracer);
+            self.end().trace(tracer);
+        }
+
+        #[inline]
+        fn is_type_tracked() -> bool {
+            T::is_type_tracked()
+        }
+    }
+}
+
 mod rc {
     use std::rc;
 
+    // With `track-rc`, `Rc<T>` is tracked if `T` is. This lets the collector
+    // see cycles through `Rc`, at the cost of every `Rc<T>` holding tracked
+    // types going through the slower tracked path.
+    #[cfg(feature = "track-rc")]
+    impl<T: super::Trace> super::Trace for rc::Rc<T> {
+        fn trace(&self, tracer: &mut super::Tracer) {
+            // A shared `Rc` would be visited once per clone, so only trace it
+            // when it is owned like a `Box`. Skipping is conservative: the
+            // referred objects are treated as reachable.
+            if rc::Rc::strong_count(self) == 1 {
+                tracer.trace_nested(self.as_ref());
+            }
+        }
+
+        #[inline]
+        fn is_type_tracked() -> bool {
+            T::is_type_tracked()
+        }
+    }
+
+    #[cfg(not(feature = "track-rc"))]
     trace_acyclic!(<T> rc::Rc<T>);
     trace_acyclic!(<T> rc::Weak<T>);
 }
@@ -411,6 +673,11 @@ mod sync {
     // See comment in Mutex for why this is acyclic.
     trace_acyclic!(<T> sync::Arc<T>);
 
+    // Reading the value without forcing it (`LazyLock::get`) is unstable.
+    // Forcing it would run arbitrary code in the middle of a collection.
+    // So the value is not traced. Cycles through it are not collected.
+    trace_acyclic!(<T, F> sync::LazyLock<T, F>);
+
     impl<T: Trace> Trace for sync::Mutex<T> {
         fn trace(&self, tracer: &mut Tracer) {
             // For single-thread collector (ObjectSpace):
@@ -437,13 +704,27 @@ mod sync {
         }
     }
 
+    impl<T: Trace> Trace for sync::OnceLock<T> {
+        fn trace(&self, tracer: &mut Tracer) {
+            if let Some(x) = self.get() {
+                x.trace(tracer)
+            }
+        }
+
+        #[inline]
+        fn is_type_tracked() -> bool {
+            T::is_type_tracked()
+        }
+    }
+
     impl<T: Trace> Trace for sync::RwLock<T> {
         fn trace(&self, tracer: &mut Tracer) {
             // See Mutex for why locking is optional.
             //
-            // If read or write locks are already taken, that indicates
-            // outstanding references that keeps the objects alive.
-            if let Ok(x) = self.try_write() {
+            // Tracing only needs shared access, so outstanding readers do
+            // not prevent it. If a write lock is already taken, that
+            // indicates outstanding references that keeps the objects alive.
+            if let Ok(x) = self.try_read() {
                 x.trace(tracer);
             }
         }
@@ -455,6 +736,13 @@ mod sync {
     }
 }
 
+mod task {
+    use std::task;
+
+    // `Trace` requires `'static`, so only `Context<'static>` is covered.
+    trace_acyclic!(task::Waker, task::Context<'static>);
+}
+
 mod thread {
     use std::thread;
 
@@ -463,6 +751,18 @@ mod thread {
     trace_acyclic!(thread::Thread);
 }
 
+mod time {
+    use std::time;
+
+    trace_acyclic!(
+        time::Duration,
+        time::Instant,
+        time::SystemTime,
+        time::SystemTimeError,
+        time::TryFromFloatSecsError
+    );
+}
+
 mod phantom {
     use std::marker::PhantomData;
 
@@ -483,7 +783,50 @@ mod tests {
         assert!(!String::is_type_tracked());
         assert!(!Option::<u32>::is_type_tracked());
         assert!(!Vec::<u8>::is_type_tracked());
+        assert!(!<[u8; 4]>::is_type_tracked());
+        assert!(!std::collections::HashSet::<u8>::is_type_tracked());
+        assert!(!std::collections::BTreeSet::<u8>::is_type_tracked());
+        assert!(!std::collections::BinaryHeap::<u8>::is_type_tracked());
+        assert!(!std::sync::OnceLock::<u8>::is_type_tracked());
+        assert!(std::sync::OnceLock::<Box<dyn Trace>>::is_type_tracked());
+        assert!(!std::sync::LazyLock::<Box<dyn Trace>>::is_type_tracked());
+        assert!(!std::cell::LazyCell::<Box<dyn Trace>>::is_type_tracked());
+        {
+            use std::sync::atomic::*;
+            assert!(!AtomicBool::is_type_tracked());
+            assert!(!AtomicI8::is_type_tracked());
+            assert!(!AtomicU8::is_type_tracked());
+            assert!(!AtomicI16::is_type_tracked());
+            assert!(!AtomicU16::is_type_tracked());
+            assert!(!AtomicI32::is_type_tracked());
+            assert!(!AtomicU32::is_type_tracked());
+            assert!(!AtomicI64::is_type_tracked());
+            assert!(!AtomicU64::is_type_tracked());
+            assert!(!AtomicIsize::is_type_tracked());
+            assert!(!AtomicUsize::is_type_tracked());
+        }
+        assert!(!std::num::NonZeroU32::is_type_tracked());
+        assert!(!std::num::NonZeroI128::is_type_tracked());
+        assert!(!std::num::TryFromIntError::is_type_tracked());
+        assert!(!std::time::Duration::is_type_tracked());
+        assert!(!std::time::Instant::is_type_tracked());
+        assert!(!std::time::SystemTime::is_type_tracked());
+        assert!(!std::time::SystemTimeError::is_type_tracked());
+        assert!(!std::time::TryFromFloatSecsError::is_type_tracked());
+        assert!(!std::ops::Range::<usize>::is_type_tracked());
+        assert!(!std::ops::RangeInclusive::<u32>::is_type_tracked());
+        assert!(std::ops::Range::<Box<dyn Trace>>::is_type_tracked());
+        assert!(<[Box<dyn Trace>; 0]>::is_type_tracked());
         assert!(!<(bool, f64)>::is_type_tracked());
+        assert!(!std::task::Waker::is_type_tracked());
+        #[cfg(feature = "parking_lot")]
+        {
+            use ::parking_lot::ReentrantMutex;
+            assert!(!ReentrantMutex::<u32>::is_type_tracked());
+            assert!(ReentrantMutex::<Box<dyn Trace>>::is_type_tracked());
+            assert!(!::parking_lot::Mutex::<u32>::is_type_tracked());
+            assert!(::parking_lot::RwLock::<Box<dyn Trace>>::is_type_tracked());
+        }
         assert!(!Cell::<u32>::is_type_tracked());
         assert!(!RefCell::<String>::is_type_tracked());
         assert!(Box::<dyn Trace>::is_type_tracked());
@@ -492,11 +835,62 @@ mod tests {
         assert!(Vec::<RefCell::<Box::<dyn Trace>>>::is_type_tracked());
         assert!(!Cc::<u8>::is_type_tracked());
         assert!(!Vec::<Cc::<u8>>::is_type_tracked());
+        assert!(!Rc::<u8>::is_type_tracked());
+        assert_eq!(
+            Rc::<Box<dyn Trace>>::is_type_tracked(),
+            cfg!(feature = "track-rc")
+        );
+        assert!(<(u8, u8, u8, u8, u8, u8, u8, u8, u8, u8, u8, Box<dyn Trace>)>::is_type_tracked());
 
         assert!(!<fn(u8) -> u8>::is_type_tracked());
         assert!(!<fn(&u8) -> u8>::is_type_tracked());
     }
 
+    trait Local {}
+    impl Local for () {}
+    struct S<T>(T);
+    trace_acyclic!(dyn Local + Send);
+    trace_acyclic!(dyn std::error::Error);
+    trace_acyclic!(
+        S<Vec<Box<dyn std::error::Error>>>,
+        S<Box<dyn std::fmt::Debug>>
+    );
+    trace_acyclic!(<T> S<(T, Box<dyn std::fmt::Debug + Send>)>);
+
+    #[test]
+    fn test_trace_acyclic_complex_types() {
+        assert!(!S::<Vec<Box<dyn std::error::Error>>>::is_type_tracked());
+        assert!(!S::<Box<dyn std::fmt::Debug>>::is_type_tracked());
+        assert!(!S::<(u8, Box<dyn std::fmt::Debug + Send>)>::is_type_tracked());
+        let value: &(dyn Local + Send) = &();
+        let mut count = 0;
+        value.trace(&mut Tracer::new(&mut |_| count += 1));
+        let error: &dyn std::error::Error = &std::fmt::Error;
+        error.trace(&mut Tracer::new(&mut |_| count += 1));
+        assert_eq!(count, 0);
+    }
+
+    #[test]
+    #[allow(clippy::assertions_on_constants)]
+    fn test_is_type_tracked_const() {
+        use crate::TypeTracked;
+
+        struct X<T>(T);
+        trace_fields!(X<T> { 0: T });
+        struct Y<T>(T);
+        impl<T: 'static> Trace for Y<T> {}
+        impl<T: 'static> TypeTracked for Y<T> {
+            const IS_TYPE_TRACKED: bool = true;
+        }
+
+        const _: () = assert!(!u8::IS_TYPE_TRACKED);
+        const _: () = assert!(!<(String, u32)>::IS_TYPE_TRACKED);
+        const _: () = assert!(!X::<(u8, X<String>)>::IS_TYPE_TRACKED);
+        const _: () = assert!(<(u8, Y<u8>)>::IS_TYPE_TRACKED);
+        assert!(!X::<(u8, X<String>)>::is_type_tracked());
+        assert!(<(u8, Y<u8>)>::is_type_tracked());
+    }
+
     #[test]
     fn test_is_cyclic_type_tracked() {
         type C1 = RefCell<Option<Rc<Box<S1>>>>;
@@ -523,7 +917,142 @@ mod tests {
             }
         }
 
+        // With `track-rc`, `S1` is infinitely recursive like `S2`.
+        #[cfg(not(feature = "track-rc"))]
         assert!(!S1::is_type_tracked());
         assert!(S2::is_type_tracked());
     }
+
+    #[test]
+    fn test_array_cycle() {
+        type Node = Cc<RefCell<Option<Box<dyn Trace>>>>;
+        let space = crate::ObjectSpace::default();
+        let nodes: [Node; 4] = std::array::from_fn(|_| space.create(RefCell::new(None)));
+        let array = space.create(nodes.clone());
+        for node in &nodes {
+            *node.borrow_mut() = Some(Box::new(array.clone()));
+        }
+        drop((nodes, array));
+        assert_eq!(space.count_tracked(), 5);
+        assert_eq!(space.collect_cycles(), 5);
+        assert_eq!(space.count_tracked(), 0);
+    }
+
+    /// Orders by `0` only, so `1` can be any traced value.
+    struct Keyed(u32, Box<dyn Trace>);
+
+    impl PartialEq for Keyed {
+        fn eq(&self, other: &Self) -> bool {
+            self.0 == other.0
+        }
+    }
+
+    impl Eq for Keyed {}
+
+    impl PartialOrd for Keyed {
+        fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl Ord for Keyed {
+        fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+            self.0.cmp(&other.0)
+        }
+    }
+
+    impl std::hash::Hash for Keyed {
+        fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+            self.0.hash(state)
+        }
+    }
+
+    impl Trace for Keyed {
+        fn trace(&self, tracer: &mut Tracer) {
+            self.1.trace(tracer)
+        }
+    }
+
+    #[test]
+    fn test_set_and_heap_cycle() {
+        use std::collections::{BTreeSet, BinaryHeap, HashSet};
+
+        let space = crate::ObjectSpace::default();
+        let a = space.create(RefCell::new(HashSet::new()));
+        let b = space.create(RefCell::new(BTreeSet::new()));
+        let c = space.create(RefCell::new(BinaryHeap::new()));
+        a.borrow_mut().insert(Keyed(1, Box::new(b.clone())));
+        b.borrow_mut().insert(Keyed(2, Box::new(c.clone())));
+        c.borrow_mut().push(Keyed(3, Box::new(a.clone())));
+        drop((a, b, c));
+        assert_eq!(space.collect_cycles(), 3);
+        assert_eq!(space.count_tracked(), 0);
+    }
+
+    #[test]
+    fn test_once_lock_cycle() {
+        let space = crate::ObjectSpace::default();
+        let a = space.create(std::sync::OnceLock::<Box<dyn Trace>>::new());
+        assert!(a.set(Box::new(a.clone())).is_ok());
+        drop(a);
+        assert_eq!(space.collect_cycles(), 1);
+    }
+
+    #[test]
+    fn test_trace_unsafe_cell_cycle() {
+        let space = crate::ObjectSpace::default();
+        // safety: The value is only changed below, without a collection.
+        let a = space.create(unsafe { crate::TraceUnsafeCell::new(None::<Box<dyn Trace>>) });
+        // safety: No other references to the value exist.
+        unsafe { *a.get() = Some(Box::new(a.clone())) };
+        drop(a);
+        assert_eq!(space.collect_cycles(), 1);
+        assert_eq!(space.count_tracked(), 0);
+    }
+
+    #[test]
+    fn test_range_and_slice_trace() {
+        let node = || -> Box<dyn Trace> { Box::new(Cc::new(RefCell::new(None::<Box<dyn Trace>>))) };
+        let count_edges = |value: &dyn Trace| {
+            let mut count = 0;
+            value.trace(&mut Tracer::new(&mut |_| count += 1));
+            count
+        };
+        assert_eq!(count_edges(&(node()..node())), 2);
+        assert_eq!(count_edges(&(node()..=node())), 2);
+
+        let slice: &[Box<dyn Trace>] = &[node(), node(), node()];
+        let mut count = 0;
+        slice.trace(&mut Tracer::new(&mut |_| count += 1));
+        assert_eq!(count, 3);
+    }
+
+    #[test]
+    #[cfg(feature = "parking_lot")]
+    fn test_parking_lot_cycle() {
+        use ::parking_lot::{Mutex, RwLock};
+
+        let space = crate::ObjectSpace::default();
+        let a = space.create(Mutex::new(None::<Box<dyn Trace>>));
+        let b = space.create(RwLock::new(None::<Box<dyn Trace>>));
+        *a.lock() = Some(Box::new(b.clone()));
+        *b.write() = Some(Box::new(a.clone()));
+        drop((a, b));
+        assert_eq!(space.collect_cycles(), 2);
+        assert_eq!(space.count_tracked(), 0);
+    }
+
+    #[test]
+    fn test_rwlock_trace_with_read_guard() {
+        let lock: std::sync::RwLock<Box<dyn Trace>> =
+            std::sync::RwLock::new(Box::new(Cc::new(RefCell::new(None::<Box<dyn Trace>>))));
+        let count_edges = |lock: &std::sync::RwLock<Box<dyn Trace>>| {
+            let mut count = 0;
+            lock.trace(&mut Tracer::new(&mut |_| count += 1));
+            count
+        };
+        assert_eq!(count_edges(&lock), 1);
+        let _guard = lock.read().unwrap();
+        assert_eq!(count_edges(&lock), 1);
+    }
 }
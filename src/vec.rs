@@ -0,0 +1,140 @@
+//! `GcVec<T>`: shared growable vectors of `Cc<T>`.
+
+use crate::collect::ObjectSpace;
+use crate::Cc;
+use crate::Trace;
+use crate::Tracer;
+use std::cell::RefCell;
+use std::fmt;
+
+/// A growable vector of [`Cc<T>`](type.Cc.html), shared by reference
+/// counting.
+///
+/// This is `Cc<RefCell<Vec<Cc<T>>>>` with methods that borrow the
+/// `RefCell` internally. The borrows do not outlive the method calls, so
+/// the vector can be changed while iterating.
+///
+/// Cloning a `GcVec<T>` clones the reference, not the elements.
+///
+/// ```
+/// use jrsonnet_gcmodule::{Cc, GcVec};
+///
+/// let v: GcVec<u32> = GcVec::new();
+/// v.push(Cc::new(1));
+/// v.clone().push(Cc::new(2));
+/// assert_eq!(v.iter().map(|x| *x).collect::<Vec<_>>(), [1, 2]);
+/// assert_eq!(v.pop().map(|x| *x), Some(2));
+/// assert_eq!(v.len(), 1);
+/// ```
+pub struct GcVec<T>(Cc<RefCell<Vec<Cc<T>>>>);
+
+impl<T: Trace> GcVec<T> {
+    /// Constructs an empty `GcVec<T>` in a thread-local storage.
+    pub fn new() -> Self {
+        Self(Cc::new(RefCell::new(Vec::new())))
+    }
+
+    /// Constructs an empty `GcVec<T>` in the given
+    /// [`ObjectSpace`](struct.ObjectSpace.html).
+    pub fn new_with_space(space: &ObjectSpace) -> Self {
+        Self(space.create(RefCell::new(Vec::new())))
+    }
+}
+
+impl<T: Trace> Default for GcVec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> GcVec<T> {
+    /// Appends `value` to the end.
+    pub fn push(&self, value: Cc<T>) {
+        self.0.borrow_mut().push(value);
+    }
+
+    /// Removes the last element and returns it, or `None` if empty.
+    pub fn pop(&self) -> Option<Cc<T>> {
+        self.0.borrow_mut().pop()
+    }
+
+    /// Returns the element at `index`, or `None` if out of bounds.
+    pub fn get(&self, index: usize) -> Option<Cc<T>> {
+        self.0.borrow().get(index).cloned()
+    }
+
+    /// Returns the number of elements.
+    pub fn len(&self) -> usize {
+        self.0.borrow().len()
+    }
+
+    /// Returns `true` if there are no elements.
+    pub fn is_empty(&self) -> bool {
+        self.0.borrow().is_empty()
+    }
+
+    /// Iterate through elements by index. Elements pushed while iterating
+    /// are also visited.
+    pub fn iter(&self) -> GcVecIter<'_, T> {
+        GcVecIter {
+            vec: self,
+            index: 0,
+        }
+    }
+
+    /// Returns `true` if the two `GcVec`s point to the same allocation.
+    #[inline]
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        Cc::ptr_eq(&this.0, &other.0)
+    }
+}
+
+/// Iterator of [`GcVec<T>`](struct.GcVec.html) elements, returned by
+/// [`GcVec::iter`](struct.GcVec.html#method.iter).
+pub struct GcVecIter<'a, T> {
+    vec: &'a GcVec<T>,
+    index: usize,
+}
+
+impl<T> Iterator for GcVecIter<'_, T> {
+    type Item = Cc<T>;
+
+    fn next(&mut self) -> Option<Cc<T>> {
+        let item = self.vec.get(self.index)?;
+        self.index += 1;
+        Some(item)
+    }
+}
+
+impl<'a, T> IntoIterator for &'a GcVec<T> {
+    type Item = Cc<T>;
+    type IntoIter = GcVecIter<'a, T>;
+
+    fn into_iter(self) -> GcVecIter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<T> Clone for GcVec<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for GcVec<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.0.borrow().iter()).finish()
+    }
+}
+
+impl<T: Trace> Trace for GcVec<T> {
+    fn trace(&self, tracer: &mut Tracer) {
+        self.0.trace(tracer)
+    }
+
+    #[inline]
+    fn is_type_tracked() -> bool {
+        T::is_type_tracked()
+    }
+}